@@ -8,6 +8,30 @@
 extern "C" {
 #endif
 
+/* Result codes returned by every exchange function. */
+#define EXCHANGE_OK 0
+#define EXCHANGE_ERR_NOT_EXISTS 1
+#define EXCHANGE_ERR_PERMISSION_DENIED 2
+#define EXCHANGE_ERR_ALREADY_EXISTS 3
+#define EXCHANGE_ERR_SAME_PATH 4
+#define EXCHANGE_ERR_INVALID_PATH 5
+#define EXCHANGE_ERR_UNSUPPORTED_FILE_TYPE 6
+#define EXCHANGE_ERR_ROLLBACK_FAILED 7
+#define EXCHANGE_ERR_CANCELLED 8
+#define EXCHANGE_ERR_CROSS_DEVICE 9
+#define EXCHANGE_ERR_NAME_TOO_LONG 10
+#define EXCHANGE_ERR_BUSY 11
+#define EXCHANGE_ERR_SYMLINK_LOOP 12
+#define EXCHANGE_ERR_NESTED_DIRECTORIES 13
+#define EXCHANGE_ERR_READ_ONLY_FILESYSTEM 14
+#define EXCHANGE_ERR_LOCK_TIMEOUT 15
+#define EXCHANGE_ERR_CHANGED 16
+#define EXCHANGE_ERR_ABORTED 17
+#define EXCHANGE_ERR_VERIFICATION_FAILED 18
+#define EXCHANGE_ERR_MOUNT_POINT 19
+#define EXCHANGE_ERR_INVALID_OPERATION_ID 20
+#define EXCHANGE_ERR_UNKNOWN 255
+
 /* preserve_ext must be 0 or 1. Paths are UTF-8. */
 int32_t exchange(const char *path1, const char *path2, uint8_t preserve_ext);
 
@@ -16,6 +40,241 @@ int32_t exchange_n(const uint8_t *path1, size_t path1_len,
                    const uint8_t *path2, size_t path2_len,
                    uint8_t preserve_ext);
 
+/* Like exchange/exchange_n, but on Unix the bytes are an opaque path that need not be UTF-8.
+   Embedded NUL bytes are rejected. Other platforms still require UTF-8. */
+int32_t exchange_raw(const char *path1, const char *path2, uint8_t preserve_ext);
+int32_t exchange_raw_n(const uint8_t *path1, size_t path1_len,
+                       const uint8_t *path2, size_t path2_len,
+                       uint8_t preserve_ext);
+
+#define EXCHANGE_EVENT_TEMP_DIR_CREATED 0u
+#define EXCHANGE_EVENT_RENAMED 1u
+#define EXCHANGE_EVENT_ROLLBACK_STARTED 2u
+#define EXCHANGE_EVENT_ROLLBACK_STEP 3u
+#define EXCHANGE_EVENT_CLEANUP 4u
+/* from is the history file; the exchange or undo itself stands. */
+#define EXCHANGE_EVENT_HISTORY_FAILED 5u
+
+/* Paths are not NUL-terminated and are valid only during the callback; absent paths are NULL.
+   stage is 1-3 for renames, otherwise 0. result is 0 or an error code. */
+typedef struct exchange_event {
+    uint32_t kind;
+    uint32_t stage;
+    const uint8_t *from;
+    size_t from_len;
+    const uint8_t *to;
+    size_t to_len;
+    int32_t result;
+} exchange_event;
+
+typedef void (*exchange_event_callback)(const exchange_event *event, void *user_data);
+
+/* callback may be NULL; it runs synchronously on the calling thread. */
+int32_t exchange_n_observed(const uint8_t *path1, size_t path1_len,
+                            const uint8_t *path2, size_t path2_len,
+                            uint8_t preserve_ext,
+                            exchange_event_callback callback, void *user_data);
+
+/* Details of the last failed call on the calling thread; a successful call clears them.
+   Copies at most len - 1 bytes plus a NUL terminator and returns the full message length,
+   or 0 when there is no error. buf may be NULL when len is 0. */
+size_t exchange_last_error_message(char *buf, size_t len);
+
+/* OS error code behind the last failure on the calling thread, or 0. */
+int32_t exchange_last_error_os_code(void);
+
+typedef struct exchange_lib_version_info {
+    uint32_t major;
+    uint32_t minor;
+    uint32_t patch;
+} exchange_lib_version_info;
+
+exchange_lib_version_info exchange_lib_version(void);
+
+/* Bit 0 is reserved and never set. */
+#define EXCHANGE_CAP_BATCH (1ull << 1)
+#define EXCHANGE_CAP_HANDLES (1ull << 2)
+#define EXCHANGE_CAP_RAW_PATHS (1ull << 3)
+#define EXCHANGE_CAP_ASYNC (1ull << 4)
+#define EXCHANGE_CAP_TRACING (1ull << 5)
+#define EXCHANGE_CAP_DIRECTORY_HANDLES (1ull << 6)
+#define EXCHANGE_CAP_TWO_PHASE (1ull << 7)
+/* Entries on different file systems can be exchanged; each is renamed within its directory. */
+#define EXCHANGE_CAP_CROSS_DEVICE (1ull << 8)
+#define EXCHANGE_CAP_DURABILITY (1ull << 9)
+#define EXCHANGE_CAP_VERIFY (1ull << 10)
+#define EXCHANGE_CAP_PRECONDITIONS (1ull << 11)
+#define EXCHANGE_CAP_DIAGNOSTICS (1ull << 12)
+/* exchanger_options_set_history and exchanger_options_set_operation_id record exchanges. */
+#define EXCHANGE_CAP_HISTORY (1ull << 13)
+
+/* Bits are computed from build features and the target platform. Every feature added to the C
+   API gets a bit; unknown bits may be added later and should be ignored. */
+uint64_t exchange_lib_capabilities(void);
+
+/* Opaque handles; release each with its _free function. NULL is ignored by _free, which leaves
+   the last error unchanged. */
+typedef struct exchanger_options exchanger_options;
+typedef struct exchanger_plan exchanger_plan;
+
+/* Returns NULL if allocation fails. */
+exchanger_options *exchanger_options_new(void);
+void exchanger_options_free(exchanger_options *options);
+int32_t exchanger_options_set_preserve_ext(exchanger_options *options, uint8_t preserve_ext);
+/* With verify set to 1, checks after each exchange that the new names hold the entries that had
+   the old ones and that the temporary directory is gone, exchanging the entries back and
+   returning EXCHANGE_ERR_VERIFICATION_FAILED otherwise. Needs Unix. */
+int32_t exchanger_options_set_verify(exchanger_options *options, uint8_t verify);
+#define EXCHANGE_DURABILITY_NONE 0
+#define EXCHANGE_DURABILITY_DIRECTORIES 1
+#define EXCHANGE_DURABILITY_FULL 2
+
+/* With EXCHANGE_DURABILITY_DIRECTORIES, journals each exchange like exchanger_plan_prepare and
+   syncs every directory a rename changes, so that after a power loss the entries have either
+   their old or their new names, or a journal for exchanger_recover. EXCHANGE_DURABILITY_FULL
+   also syncs the exchanged files first. A failed sync restores the original names. */
+int32_t exchanger_options_set_durability(exchanger_options *options, uint8_t durability);
+/* A negative timeout waits forever. */
+int32_t exchanger_options_set_lock_timeout_ms(exchanger_options *options, int64_t timeout_ms);
+/* Resolves relative paths against an absolute base directory instead of the working
+   directory. path uses exchange_raw_n rules; NULL restores the working directory. */
+int32_t exchanger_options_set_base_dir(exchanger_options *options,
+                                       const uint8_t *path, size_t path_len);
+/* Records completed exchanges in the history file at path (exchange_raw_n rules); NULL stops
+   recording. Recording needs device and inode numbers and fails outside Unix. */
+int32_t exchanger_options_set_history(exchanger_options *options,
+                                      const uint8_t *path, size_t path_len);
+/* Repeating an exchange of the same paths with the same non-empty UTF-8 operation ID succeeds
+   without renaming while the recorded entries are still in place, and fails with
+   EXCHANGE_ERR_CHANGED otherwise. Requires a history; NULL clears the ID. An empty ID, or an
+   exchange without a history or of other paths, fails with EXCHANGE_ERR_INVALID_OPERATION_ID. */
+int32_t exchanger_options_set_operation_id(exchanger_options *options,
+                                           const uint8_t *operation_id,
+                                           size_t operation_id_len);
+#define EXCHANGE_EXPECT_IDENTITY (1u << 0)
+#define EXCHANGE_EXPECT_SIZE (1u << 1)
+#define EXCHANGE_EXPECT_MODIFIED (1u << 2)
+#define EXCHANGE_EXPECT_SHA256 (1u << 3)
+
+/* Expected state of one entry; fields names the members that are checked. The modification
+   time is modified_sec seconds (negative before 1970) plus modified_nsec nanoseconds since the
+   Unix epoch. Identities can only be checked on Unix. */
+typedef struct exchange_precondition {
+    uint32_t fields;
+    uint64_t dev;
+    uint64_t ino;
+    uint64_t size;
+    int64_t modified_sec;
+    uint32_t modified_nsec;
+    uint8_t sha256[32];
+} exchange_precondition;
+
+/* Exchanges the entry at path (exchange_raw_n rules) only while it still matches
+   *precondition: it is checked while planning and again just before the first rename, and a
+   mismatch returns EXCHANGE_ERR_CHANGED without renaming. A later precondition for the same
+   path replaces an earlier one; a NULL path removes all preconditions. */
+int32_t exchanger_options_expect(exchanger_options *options,
+                                 const uint8_t *path, size_t path_len,
+                                 const exchange_precondition *precondition);
+/* callback may be NULL. It runs synchronously on the thread executing the exchange, which may
+   differ from the thread that set it; user_data must stay valid while the options or plans made
+   from them are used. */
+int32_t exchanger_options_set_observer(exchanger_options *options,
+                                       exchange_event_callback callback, void *user_data);
+
+#define EXCHANGE_SEVERITY_ERROR 1u
+#define EXCHANGE_SEVERITY_WARNING 2u
+
+/* path is not NUL-terminated and is NULL when the finding concerns no path; message is
+   NUL-terminated. Both are valid only during the callback. */
+typedef struct exchange_finding {
+    uint32_t severity;
+    int32_t code;
+    const uint8_t *path;
+    size_t path_len;
+    const char *message;
+} exchange_finding;
+
+typedef void (*exchange_finding_callback)(const exchange_finding *finding, void *user_data);
+
+/* Runs every check an exchange would make, renaming nothing, and passes each problem found to
+   callback, which may be NULL. Returns 0 when no finding is an error, otherwise the code of the
+   first error finding. options may be NULL for defaults. */
+int32_t exchanger_diagnose(const exchanger_options *options,
+                           const uint8_t *path1, size_t path1_len,
+                           const uint8_t *path2, size_t path2_len,
+                           exchange_finding_callback callback, void *user_data);
+
+/* options may be NULL for defaults. On failure *out_plan is set to NULL. */
+int32_t exchanger_plan(const exchanger_options *options,
+                       const uint8_t *path1, size_t path1_len,
+                       const uint8_t *path2, size_t path2_len,
+                       exchanger_plan **out_plan);
+size_t exchanger_plan_step_count(const exchanger_plan *plan);
+/* Copy step paths like exchange_last_error_message; return 0 for an out-of-range index. */
+size_t exchanger_plan_source(const exchanger_plan *plan, size_t index, char *buf, size_t len);
+size_t exchanger_plan_target(const exchanger_plan *plan, size_t index, char *buf, size_t len);
+/* Validates again and returns EXCHANGE_ERR_CHANGED if the entries changed since planning.
+   A plan executes at most once; later calls return EXCHANGE_ERR_INVALID_PATH. */
+int32_t exchanger_plan_execute(exchanger_plan *plan);
+/* Two-phase alternative to exchanger_plan_execute: prepare validates again and moves the second
+   entry into a temporary directory beside it, next to a journal; commit finishes the renames and
+   abort restores the original names. Freeing a prepared plan aborts it. Commit and abort return
+   EXCHANGE_ERR_INVALID_PATH unless the plan is prepared. */
+int32_t exchanger_plan_prepare(exchanger_plan *plan);
+int32_t exchanger_plan_commit(exchanger_plan *plan);
+int32_t exchanger_plan_abort(exchanger_plan *plan);
+/* Copies the journal directory of a prepared plan like exchange_last_error_message; returns 0
+   when nothing is staged. */
+size_t exchanger_plan_journal_dir(const exchanger_plan *plan, char *buf, size_t len);
+void exchanger_plan_free(exchanger_plan *plan);
+
+#define EXCHANGER_RECOVERED_PREPARED 0
+#define EXCHANGER_RECOVERED_COMMITTED 1
+#define EXCHANGER_RECOVERED_ABORTED 2
+
+/* Resumes the exchange journaled in dir (exchange_raw_n rules) by a process that died between
+   prepare and commit or abort. *out_state receives an EXCHANGER_RECOVERED_* value; for
+   EXCHANGER_RECOVERED_PREPARED, *out_plan receives a prepared plan to commit or abort, and is
+   NULL otherwise. Journals of committed or aborted exchanges are removed. */
+int32_t exchanger_recover(const exchanger_options *options,
+                          const uint8_t *dir, size_t dir_len,
+                          exchanger_plan **out_plan, uint8_t *out_state);
+
+#ifndef _WIN32
+/* Like exchange_raw_n, but each name is a single component inside an open directory
+   descriptor and is renamed with renameat; no path is resolved. Descriptors must be
+   non-negative, so AT_FDCWD is rejected. options may be NULL for defaults; history, operation
+   IDs, preconditions, verification, and durability are rejected. */
+int32_t exchange_at(int dirfd1, const uint8_t *name1, size_t name1_len,
+                    int dirfd2, const uint8_t *name2, size_t name2_len,
+                    const exchanger_options *options);
+#endif
+
+typedef struct exchange_path_pair {
+    const uint8_t *path1;
+    size_t path1_len;
+    const uint8_t *path2;
+    size_t path2_len;
+} exchange_path_pair;
+
+typedef struct exchange_path {
+    const uint8_t *path;
+    size_t len;
+} exchange_path;
+
+/* Exchanges the pairs in order; if one fails, completed pairs are exchanged back.
+   options may be NULL for defaults. results may be NULL, otherwise it receives count codes:
+   0 on success, the failing pair's code, EXCHANGE_ERR_ABORTED for pairs undone or never
+   started, and EXCHANGE_ERR_ROLLBACK_FAILED for pairs that could not be undone. */
+int32_t exchange_batch_n(const exchanger_options *options,
+                         const exchange_path_pair *pairs, size_t count, int32_t *results);
+
+/* Each entry takes the next entry's name and the last takes the first's; count must be at
+   least 2. A failure undoes every completed rename. */
+int32_t exchange_rotate_n(const exchanger_options *options,
+                          const exchange_path *paths, size_t count);
+
 #ifdef __cplusplus
 }
 #endif
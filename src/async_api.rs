@@ -0,0 +1,261 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use tokio::task::{spawn_blocking, JoinError};
+
+use crate::{
+    batch, history,
+    lock::{lock_paths_async, Access},
+    mount::MountTable,
+    plan, resolve_all, transaction, BatchError, ExchangeError, ExchangeOptions, Phase,
+    PlannedExchange, RenameError,
+};
+
+/// Swaps names of two files, directories, or symbolic links without blocking the async runtime.
+///
+/// Filesystem work runs on Tokio's blocking pool and waiting for the path lock does not occupy a
+/// runtime thread. Dropping the future before the first rename cancels the exchange; once the
+/// first rename has started, the exchange runs to completion in the background.
+///
+/// # Errors
+///
+/// Returns [`RenameError`] when validation, renaming, or rollback fails.
+pub async fn exchange_async(
+    path1: &Path,
+    path2: &Path,
+    preserve_ext: bool,
+) -> Result<(), RenameError> {
+    exchange_async_with(
+        path1,
+        path2,
+        &ExchangeOptions::new().preserve_ext(preserve_ext),
+    )
+    .await
+    .map_err(RenameError::from)
+}
+
+/// Async counterpart of [`exchange_with`](crate::exchange_with).
+///
+/// Observer callbacks run on the blocking pool. Cancellation follows [`exchange_async`]. A lock
+/// timeout requires a runtime with the time driver enabled.
+///
+/// # Errors
+///
+/// Returns [`ExchangeError`] when validation, renaming, or rollback fails.
+pub async fn exchange_async_with(
+    path1: &Path,
+    path2: &Path,
+    options: &ExchangeOptions,
+) -> Result<(), ExchangeError> {
+    let options = options.clone();
+    let pair = resolve_pair(path1, path2, &options).await?;
+    let keys = pair.lock_keys();
+    run_locked(pair, keys, options.lock_timeout, move |pair, cancelled| {
+        if history::completed(&options, [&pair])? {
+            return Ok(());
+        }
+        let plan = plan::ExchangePlan::build(pair, &options, &MountTable::default())?;
+        history::recorded(&options, &plan, || {
+            transaction::execute(
+                &plan,
+                options.observer.as_ref(),
+                Some(cancelled),
+                options.verify,
+                options.durability,
+            )
+        })
+    })
+    .await?
+}
+
+/// Async counterpart of [`exchange_batch`](crate::exchange_batch).
+///
+/// Cancellation follows [`exchange_async`]: dropping the future before the first pair is renamed
+/// cancels the batch, and afterwards it runs to completion in the background.
+///
+/// # Errors
+///
+/// Returns [`BatchError`] like [`exchange_batch`](crate::exchange_batch).
+pub async fn exchange_batch_async(
+    pairs: &[(&Path, &Path)],
+    options: &ExchangeOptions,
+) -> Result<(), BatchError> {
+    let options = options.clone();
+    let owned = pairs
+        .iter()
+        .map(|&(path1, path2)| (path1.to_path_buf(), path2.to_path_buf()))
+        .collect::<Vec<_>>();
+    let resolved = {
+        let options = options.clone();
+        spawn_blocking(move || {
+            let pairs = owned
+                .iter()
+                .map(|(path1, path2)| (path1.as_path(), path2.as_path()))
+                .collect::<Vec<_>>();
+            batch::resolve(&pairs, &options)
+        })
+    };
+    let pairs = resolved
+        .await
+        .map_err(|error| BatchError::new(None, join_error(error)))??;
+    let keys = plan::lock_keys(pairs.iter().flat_map(|pair| [&pair.first, &pair.second]));
+    run_locked(
+        pairs,
+        keys,
+        options.lock_timeout,
+        move |pairs, cancelled| {
+            if history::completed(&options, &pairs).map_err(|error| BatchError::new(None, error))? {
+                return Ok(());
+            }
+            batch::execute(pairs, &options, Some(cancelled))
+        },
+    )
+    .await
+    .map_err(|error| BatchError::new(None, error))?
+}
+
+/// Async counterpart of [`rotate`](crate::rotate); cancellation follows [`exchange_async`].
+///
+/// # Errors
+///
+/// Fails like [`rotate`](crate::rotate).
+pub async fn rotate_async(paths: &[&Path], options: &ExchangeOptions) -> Result<(), ExchangeError> {
+    let options = options.clone();
+    let paths = paths
+        .iter()
+        .map(|path| path.to_path_buf())
+        .collect::<Vec<_>>();
+    let resolved = {
+        let options = options.clone();
+        spawn_blocking(move || {
+            let paths = paths.iter().map(PathBuf::as_path).collect::<Vec<_>>();
+            resolve_all(&paths, &options)
+        })
+    };
+    let paths = resolved.await.map_err(join_error)??;
+    let keys = plan::lock_keys(&paths);
+    run_locked(
+        paths,
+        keys,
+        options.lock_timeout,
+        move |paths, cancelled| {
+            let plan = plan::RotationPlan::build(paths, &options, &MountTable::default())?;
+            transaction::execute_rotation(&plan, options.observer.as_ref(), Some(cancelled))
+        },
+    )
+    .await?
+}
+
+/// Async counterpart of [`plan_exchange`](crate::plan_exchange).
+///
+/// # Errors
+///
+/// Returns [`ExchangeError`] when resolution or validation fails.
+pub async fn plan_exchange_async(
+    path1: &Path,
+    path2: &Path,
+    options: &ExchangeOptions,
+) -> Result<PlannedExchange, ExchangeError> {
+    let options = options.clone();
+    let pair = resolve_pair(path1, path2, &options).await?;
+    let keys = pair.lock_keys();
+    run_locked(pair, keys, options.lock_timeout, move |pair, _| {
+        Ok(PlannedExchange {
+            plan: plan::ExchangePlan::build(pair, &options, &MountTable::default())?,
+            options,
+        })
+    })
+    .await?
+}
+
+impl PlannedExchange {
+    /// Async counterpart of [`execute`](Self::execute); cancellation follows [`exchange_async`].
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`execute`](Self::execute).
+    pub async fn execute_async(self) -> Result<(), ExchangeError> {
+        let pair = self.plan.pair();
+        let keys = pair.lock_keys();
+        run_locked(
+            pair,
+            keys,
+            self.options.lock_timeout,
+            move |pair, cancelled| {
+                if history::completed(&self.options, [&pair])? {
+                    return Ok(());
+                }
+                let plan = self.plan.revalidate(pair, &self.options)?;
+                history::recorded(&self.options, &plan, || {
+                    transaction::execute(
+                        &plan,
+                        self.options.observer.as_ref(),
+                        Some(cancelled),
+                        self.options.verify,
+                        self.options.durability,
+                    )
+                })
+            },
+        )
+        .await?
+    }
+}
+
+async fn resolve_pair(
+    path1: &Path,
+    path2: &Path,
+    options: &ExchangeOptions,
+) -> Result<plan::ResolvedPair, ExchangeError> {
+    let (path1, path2, options) = (path1.to_path_buf(), path2.to_path_buf(), options.clone());
+    spawn_blocking(move || plan::ResolvedPair::resolve(&path1, &path2, &options))
+        .await
+        .map_err(join_error)?
+}
+
+/// Waits for the lock on `keys` without blocking, then runs `operation` on `subject` on the
+/// blocking pool, returning its result unless locking or the task failed.
+async fn run_locked<S: Send + 'static, T: Send + 'static>(
+    subject: S,
+    keys: Vec<(PathBuf, Access)>,
+    timeout: Option<Duration>,
+    operation: impl FnOnce(S, &AtomicBool) -> T + Send + 'static,
+) -> Result<T, ExchangeError> {
+    let lock = match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, lock_paths_async(keys))
+            .await
+            .map_err(|_| ExchangeError::new(RenameError::LockTimeout, Phase::Lock))?,
+        None => lock_paths_async(keys).await,
+    };
+
+    let cancel = CancelOnDrop::default();
+    let cancelled = Arc::clone(&cancel.0);
+    spawn_blocking(move || {
+        let _lock = lock;
+        operation(subject, &cancelled)
+    })
+    .await
+    .map_err(join_error)
+}
+
+/// Flags the blocking task as cancelled when the owning future is dropped.
+#[derive(Default)]
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+fn join_error(error: JoinError) -> ExchangeError {
+    match error.try_into_panic() {
+        Ok(payload) => std::panic::resume_unwind(payload),
+        Err(_) => ExchangeError::new(RenameError::Cancelled, Phase::Rename),
+    }
+}
@@ -0,0 +1,430 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fs,
+    hash::{BuildHasher, RandomState},
+    io,
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd},
+        unix::{ffi::OsStrExt, fs::MetadataExt},
+    },
+    path::{Component, Path, PathBuf},
+    process,
+};
+
+use crate::{
+    entry::{Entry, EntryKind},
+    identity::Identity,
+    lock::{directory_key, lock_paths, Access},
+    plan::target_for,
+    sys::{self, EntryStat},
+    transaction::{STAGED_NAME, TEMP_DIR_PREFIX},
+    Durability, ExchangeError, ExchangeObserver, ExchangeOptions, Phase, RenameError, RenameStage,
+};
+
+/// Names tried for the temporary directory before giving up.
+const TEMP_DIR_ATTEMPTS: u32 = 16;
+
+/// An open directory and what the checks need to know about it.
+struct Directory<'a> {
+    fd: BorrowedFd<'a>,
+    identity: Identity,
+    uid: u32,
+    sticky: bool,
+    /// How errors show the directory.
+    shown: PathBuf,
+}
+
+/// An entry named inside an open directory.
+struct Named<'a> {
+    dir: &'a Directory<'a>,
+    name: &'a OsStr,
+    stat: EntryStat,
+    entry: Entry,
+}
+
+/// A name inside an open directory, and how observers and errors show it.
+#[derive(Clone, Copy)]
+struct At<'a> {
+    dir: BorrowedFd<'a>,
+    name: &'a Path,
+    shown: &'a Path,
+}
+
+impl<'a> At<'a> {
+    fn new(dir: BorrowedFd<'a>, name: &'a OsStr) -> Self {
+        Self {
+            dir,
+            name: Path::new(name),
+            shown: Path::new(name),
+        }
+    }
+}
+
+/// Exchanges two entries named inside open directories without resolving any path.
+///
+/// Observers and errors see the names, and the temporary directory's name inside `dir2`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip(dir1, dir2, options), err)
+)]
+pub(crate) fn exchange(
+    dir1: BorrowedFd<'_>,
+    name1: &Path,
+    dir2: BorrowedFd<'_>,
+    name2: &Path,
+    options: &ExchangeOptions,
+) -> Result<(), ExchangeError> {
+    reject_path_options(options)?;
+    let (name1, name2) = (single_name(name1)?, single_name(name2)?);
+    let (dir1, dir2) = (Directory::open(dir1)?, Directory::open(dir2)?);
+    let _lock = lock_paths(
+        [dir1.identity, dir2.identity].map(|identity| (directory_key(identity), Access::Exclusive)),
+        options.lock_timeout,
+    )
+    .ok_or_else(|| ExchangeError::new(RenameError::LockTimeout, Phase::Lock))?;
+
+    let first = Named::inspect(&dir1, name1)?;
+    let second = Named::inspect(&dir2, name2)?;
+    if first.stat.identity == second.stat.identity {
+        return Err(
+            ExchangeError::new(RenameError::SamePath, Phase::Plan).with_path(Path::new(name2))
+        );
+    }
+    reject_nested(&first, &second)?;
+    reject_nested(&second, &first)?;
+    let target1 = target_for(&first.entry, &second.entry, options.preserve_ext).into_os_string();
+    let target2 = target_for(&second.entry, &first.entry, options.preserve_ext).into_os_string();
+    ensure_available(&first, &target1, &second)?;
+    ensure_available(&second, &target2, &first)?;
+    check_name_length(&dir1, &target1)?;
+    check_name_length(&dir2, &target2)?;
+    preflight([&first, &second])?;
+
+    swap(
+        (At::new(dir1.fd, name1), At::new(dir1.fd, &target1)),
+        (At::new(dir2.fd, name2), At::new(dir2.fd, &target2)),
+        options.observer.as_ref(),
+    )
+}
+
+/// Fails for options that name paths or need them to journal, sync, or record the exchange.
+fn reject_path_options(options: &ExchangeOptions) -> Result<(), ExchangeError> {
+    let option = if options.history.is_some() {
+        "a history"
+    } else if options.operation_id.is_some() {
+        "operation IDs"
+    } else if !options.preconditions.is_empty() {
+        "preconditions"
+    } else if options.verify {
+        "verification"
+    } else if options.durability != Durability::None {
+        "durability"
+    } else {
+        return Ok(());
+    };
+    Err(ExchangeError::new(
+        RenameError::InvalidPath(format!(
+            "exchanges relative to directory handles do not support {option}"
+        )),
+        Phase::Resolve,
+    ))
+}
+
+fn single_name(name: &Path) -> Result<&OsStr, ExchangeError> {
+    let mut components = name.components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(single)), None) => Ok(single),
+        _ => Err(ExchangeError::new(
+            RenameError::InvalidPath("name must be a single component of its directory".to_owned()),
+            Phase::Resolve,
+        )
+        .with_path(name)),
+    }
+}
+
+impl<'a> Directory<'a> {
+    /// Checks that `fd` refers to a directory and reads it.
+    fn open(fd: BorrowedFd<'a>) -> Result<Self, ExchangeError> {
+        const S_ISVTX: u32 = 0o1000;
+
+        let shown = PathBuf::from(format!("fd {}", fd.as_raw_fd()));
+        let metadata = fd
+            .try_clone_to_owned()
+            .map(fs::File::from)
+            .and_then(|file| file.metadata())
+            .map_err(|error| ExchangeError::io(Phase::Resolve, &shown, error))?;
+        if !metadata.is_dir() {
+            return Err(ExchangeError::new(
+                RenameError::InvalidPath("descriptor does not refer to a directory".to_owned()),
+                Phase::Resolve,
+            )
+            .with_path(&shown));
+        }
+        Ok(Self {
+            fd,
+            identity: Identity {
+                dev: metadata.dev(),
+                ino: metadata.ino(),
+            },
+            uid: metadata.uid(),
+            sticky: metadata.mode() & S_ISVTX != 0,
+            shown,
+        })
+    }
+}
+
+impl<'a> Named<'a> {
+    fn inspect(dir: &'a Directory<'a>, name: &'a OsStr) -> Result<Self, ExchangeError> {
+        let path = Path::new(name);
+        let stat = sys::stat_at(dir.fd, name)
+            .map_err(|error| ExchangeError::io(Phase::Inspect, path, error))?;
+        let kind = stat.kind.ok_or_else(|| {
+            ExchangeError::new(
+                RenameError::UnsupportedFileType(path.to_path_buf()),
+                Phase::Inspect,
+            )
+            .with_path(path)
+        })?;
+        Ok(Self {
+            dir,
+            name,
+            stat,
+            entry: Entry::named(name, kind),
+        })
+    }
+}
+
+/// Fails when `other` lies inside the directory `entry`, found by walking up from its directory.
+fn reject_nested(entry: &Named<'_>, other: &Named<'_>) -> Result<(), ExchangeError> {
+    if !entry.entry.is_directory() {
+        return Ok(());
+    }
+    let mut current = other.dir.identity;
+    let mut up = PathBuf::new();
+    loop {
+        if current == entry.stat.identity {
+            return Err(
+                ExchangeError::new(RenameError::NestedDirectories, Phase::Plan)
+                    .with_path(Path::new(entry.name)),
+            );
+        }
+        up.push("..");
+        let parent = sys::stat_at(other.dir.fd, up.as_os_str())
+            .map_err(|error| ExchangeError::io(Phase::Plan, &up, error))?
+            .identity;
+        // Only the root is its own parent.
+        if parent == current {
+            return Ok(());
+        }
+        current = parent;
+    }
+}
+
+/// Checks that `target` is free or names one of the two entries.
+fn ensure_available(
+    entry: &Named<'_>,
+    target: &OsStr,
+    other: &Named<'_>,
+) -> Result<(), ExchangeError> {
+    if target == entry.name || (entry.dir.identity == other.dir.identity && target == other.name) {
+        return Ok(());
+    }
+    let path = Path::new(target);
+    match sys::stat_at(entry.dir.fd, target) {
+        Ok(_) => Err(ExchangeError::new(RenameError::AlreadyExists, Phase::Plan).with_path(path)),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(ExchangeError::io(Phase::Plan, path, error)),
+    }
+}
+
+/// Fails with [`RenameError::NameTooLong`] when the file system of `dir` cannot hold `target`.
+fn check_name_length(dir: &Directory<'_>, target: &OsStr) -> Result<(), ExchangeError> {
+    if sys::name_max_at(dir.fd).is_some_and(|max| target.as_bytes().len() > max) {
+        return Err(
+            ExchangeError::new(RenameError::NameTooLong, Phase::Plan).with_path(Path::new(target))
+        );
+    }
+    Ok(())
+}
+
+/// Fails with every reason the system would refuse to rename `entries`, like the preflight of
+/// path-based exchanges; queries that fail are left to the renames.
+///
+/// Mount points are recognized by their device numbers, so bind mounts within one file system
+/// are not.
+fn preflight(entries: [&Named<'_>; 2]) -> Result<(), ExchangeError> {
+    let refused = |path: &Path, kind, reason: &str| {
+        ExchangeError::io(Phase::Preflight, path, io::Error::new(kind, reason))
+    };
+    let flagged =
+        |dir: BorrowedFd<'_>, name: &OsStr, path: &Path, what: &str| match sys::inode_flags_at(
+            dir, name,
+        ) {
+            Ok(flags) if flags.immutable => Some(refused(
+                path,
+                io::ErrorKind::PermissionDenied,
+                &format!("{what} is immutable"),
+            )),
+            Ok(flags) if flags.append_only => Some(refused(
+                path,
+                io::ErrorKind::PermissionDenied,
+                &format!("{what} is append-only"),
+            )),
+            _ => None,
+        };
+    let mut problems = Vec::new();
+
+    let mut dirs = Vec::<&Directory<'_>>::new();
+    for entry in entries {
+        if !dirs.iter().any(|dir| dir.identity == entry.dir.identity) {
+            dirs.push(entry.dir);
+        }
+    }
+    for dir in dirs {
+        if sys::is_read_only_mount_at(dir.fd).unwrap_or(false) {
+            problems.push(refused(
+                &dir.shown,
+                io::ErrorKind::ReadOnlyFilesystem,
+                "file system is mounted read-only",
+            ));
+            continue;
+        }
+        if let Err(error) = sys::check_access_at(dir.fd) {
+            problems.push(ExchangeError::io(Phase::Preflight, &dir.shown, error));
+        }
+        problems.extend(flagged(dir.fd, OsStr::new("."), &dir.shown, "directory"));
+    }
+
+    let user = sys::effective_uid();
+    for entry in entries {
+        let path = Path::new(entry.name);
+        // Only the root of another file system has another device number.
+        if entry.stat.identity.dev != entry.dir.identity.dev {
+            problems.push(
+                ExchangeError::new(RenameError::MountPoint, Phase::Preflight).with_path(path),
+            );
+        }
+        // Symbolic links carry no flags of their own.
+        if entry.entry.kind != EntryKind::Symlink {
+            problems.extend(flagged(entry.dir.fd, entry.name, path, "entry"));
+        }
+        if entry.dir.sticky && user != 0 && ![entry.dir.uid, entry.stat.uid].contains(&user) {
+            problems.push(refused(
+                path,
+                io::ErrorKind::PermissionDenied,
+                "sticky directory only lets the owner of the entry or directory rename it",
+            ));
+        }
+    }
+    let mut problems = problems.into_iter();
+    match problems.next() {
+        Some(first) => Err(first.with_more(problems.collect())),
+        None => Ok(()),
+    }
+}
+
+/// Moves the second entry aside, the first to its target, and then the second to its target,
+/// undoing completed renames when one fails.
+fn swap(
+    (source1, target1): (At<'_>, At<'_>),
+    (source2, target2): (At<'_>, At<'_>),
+    observer: &dyn ExchangeObserver,
+) -> Result<(), ExchangeError> {
+    let (temp_name, temp_dir) = temp_dir(source2.dir, observer)?;
+    let shown = Path::new(&temp_name).join(STAGED_NAME);
+    let staged = At {
+        dir: temp_dir.as_fd(),
+        name: Path::new(STAGED_NAME),
+        shown: &shown,
+    };
+    let result = step(observer, RenameStage::ToTemporary, source2, staged).and_then(|()| {
+        if let Err(error) = step(observer, RenameStage::FirstToTarget, source1, target1) {
+            return Err(roll_back(observer, error, &[(staged, source2)]));
+        }
+        if let Err(error) = step(observer, RenameStage::TemporaryToTarget, staged, target2) {
+            return Err(roll_back(
+                observer,
+                error,
+                &[(target1, source1), (staged, source2)],
+            ));
+        }
+        Ok(())
+    });
+    // Fails while an entry is still stranded inside, which keeps it for manual recovery.
+    let cleanup = sys::remove_dir_at(source2.dir, &temp_name);
+    observer.cleanup(Path::new(&temp_name), cleanup.as_ref().map(|&()| ()));
+    result
+}
+
+/// Creates and opens a temporary directory with an unused name inside `dir`.
+fn temp_dir(
+    dir: BorrowedFd<'_>,
+    observer: &dyn ExchangeObserver,
+) -> Result<(OsString, OwnedFd), ExchangeError> {
+    let state = RandomState::new();
+    let mut attempt = 0;
+    let name = loop {
+        let name = OsString::from(format!(
+            "{TEMP_DIR_PREFIX}{:016x}",
+            state.hash_one((process::id(), attempt))
+        ));
+        match sys::create_dir_at(dir, &name) {
+            Ok(()) => break name,
+            Err(error)
+                if error.kind() == io::ErrorKind::AlreadyExists
+                    && attempt + 1 < TEMP_DIR_ATTEMPTS =>
+            {
+                attempt += 1;
+            }
+            Err(error) => return Err(ExchangeError::io(Phase::Rename, Path::new(&name), error)),
+        }
+    };
+    observer.temp_dir_created(Path::new(&name));
+    match sys::open_dir_at(dir, &name) {
+        Ok(temp_dir) => Ok((name, temp_dir)),
+        Err(error) => {
+            let error = ExchangeError::io(Phase::Rename, Path::new(&name), error);
+            let cleanup = sys::remove_dir_at(dir, &name);
+            observer.cleanup(Path::new(&name), cleanup.as_ref().map(|&()| ()));
+            Err(error)
+        }
+    }
+}
+
+/// Undoes `renames`, each given as its current and its original name, after `operation` failed.
+fn roll_back(
+    observer: &dyn ExchangeObserver,
+    operation: ExchangeError,
+    renames: &[(At<'_>, At<'_>)],
+) -> ExchangeError {
+    observer.rollback_started(&operation);
+    let failures = renames
+        .iter()
+        .filter_map(|&(from, to)| {
+            let result = rename(Phase::Rollback, from, to);
+            observer.rollback_step(from.shown, to.shown, result.as_ref().map(|&()| ()));
+            result.err()
+        })
+        .collect::<Vec<_>>();
+    if failures.is_empty() {
+        operation
+    } else {
+        ExchangeError::rollback_failed(operation, failures)
+    }
+}
+
+fn step(
+    observer: &dyn ExchangeObserver,
+    stage: RenameStage,
+    from: At<'_>,
+    to: At<'_>,
+) -> Result<(), ExchangeError> {
+    let result = rename(Phase::Rename, from, to);
+    observer.renamed(stage, from.shown, to.shown, result.as_ref().map(|&()| ()));
+    result
+}
+
+fn rename(phase: Phase, from: At<'_>, to: At<'_>) -> Result<(), ExchangeError> {
+    sys::rename_at(from.dir, from.name, to.dir, to.name)
+        .map_err(|error| ExchangeError::io(phase, from.shown, error))
+}
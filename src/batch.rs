@@ -0,0 +1,119 @@
+use std::{path::Path, sync::atomic::AtomicBool};
+
+use crate::{
+    history,
+    mount::MountTable,
+    plan::{ExchangePlan, ResolvedPair},
+    transaction, BatchError, Durability, ExchangeError, ExchangeObserver, ExchangeOptions,
+};
+
+/// Resolves every pair, naming the first that fails.
+pub(crate) fn resolve(
+    pairs: &[(&Path, &Path)],
+    options: &ExchangeOptions,
+) -> Result<Vec<ResolvedPair>, BatchError> {
+    pairs
+        .iter()
+        .enumerate()
+        .map(|(index, (path1, path2))| {
+            ResolvedPair::resolve(path1, path2, options)
+                .map_err(|error| BatchError::new(Some(index), error))
+        })
+        .collect()
+}
+
+/// Exchanges each pair in order, undoing completed pairs in reverse order when one fails.
+///
+/// Each plan is built just before it runs, so later pairs see the names earlier pairs produced.
+/// Callers hold the path lock of every pair.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(pairs = pairs.len()), err)
+)]
+pub(crate) fn execute(
+    pairs: Vec<ResolvedPair>,
+    options: &ExchangeOptions,
+    cancelled: Option<&AtomicBool>,
+) -> Result<(), BatchError> {
+    let observer = options.observer.as_ref();
+    let mut completed = Vec::with_capacity(pairs.len());
+    let mut records = Vec::with_capacity(pairs.len());
+    let mounts = MountTable::default();
+    for (index, pair) in pairs.into_iter().enumerate() {
+        // Each pair is recorded just before it runs; later pairs may move the same entries.
+        let result = ExchangePlan::build(pair, options, &mounts).and_then(|plan| {
+            let pending = history::begin(options, &plan)?;
+            // Once the first pair was exchanged, the batch runs to completion.
+            let cancelled = cancelled.filter(|_| index == 0);
+            match transaction::execute(
+                &plan,
+                observer,
+                cancelled,
+                options.verify,
+                options.durability,
+            ) {
+                Ok(()) => Ok((plan, pending)),
+                Err(error) => {
+                    pending.abort(observer);
+                    Err(error)
+                }
+            }
+        });
+        match result {
+            Ok((plan, pending)) => {
+                completed.push(plan);
+                records.push(pending);
+            }
+            Err(error) => {
+                let error = undo(
+                    &completed,
+                    BatchError::new(Some(index), error),
+                    options.durability,
+                    observer,
+                );
+                // Exchanges that stay exchanged keep their records for undoing them.
+                history::Pending::merge(
+                    records
+                        .into_iter()
+                        .enumerate()
+                        .filter(|(index, _)| !error.unrestored().contains(index))
+                        .map(|(_, pending)| pending),
+                )
+                .abort(observer);
+                return Err(error);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn undo(
+    completed: &[ExchangePlan],
+    failure: BatchError,
+    durability: Durability,
+    observer: &dyn ExchangeObserver,
+) -> BatchError {
+    if completed.is_empty() {
+        return failure;
+    }
+    observer.rollback_started(failure.error());
+    let mut unrestored = Vec::new();
+    let mut failures = Vec::new();
+    for (index, plan) in completed.iter().enumerate().rev() {
+        if let Err(error) = transaction::execute(&plan.inverse(), observer, None, false, durability)
+        {
+            unrestored.push(index);
+            failures.push(error);
+        }
+    }
+    if failures.is_empty() {
+        return failure;
+    }
+    let index = failure.index();
+    unrestored.reverse();
+    BatchError::new(
+        index,
+        ExchangeError::rollback_failed(failure.into_error(), failures),
+    )
+    .with_unrestored(unrestored)
+}
@@ -0,0 +1,220 @@
+use std::{ffi::OsString, path::PathBuf};
+
+use exchange_name_lib::Durability;
+
+pub const USAGE: &str = "\
+Usage: exchange-name [OPTIONS] <A> <B>
+       exchange-name [OPTIONS] --batch <FILE> [-0] [--continue-on-error]
+       exchange-name [--json | --jsonl] [--verify] [--durable] undo [ID]
+
+Exchanges the names of two files, directories, or symbolic links. Completed exchanges are
+recorded under $XDG_STATE_HOME/exchange-name (default ~/.local/state); 'undo' restores the
+names of exchange ID, or of the most recent exchange not undone yet. Name a file called
+'undo' as './undo'.
+
+Options:
+      --preserve-ext       Keep each regular file's extension and exchange only the stems
+      --dry-run            Validate and print the renames without performing them
+      --diagnose           Run every check and print each problem found, renaming nothing
+      --json               Print the result as one JSON object on standard output
+      --jsonl              Stream one JSON object per step, then the result, on standard output
+      --batch <FILE>       Read pairs from FILE, or standard input for '-', one 'A<TAB>B' per line
+  -0, --null               Read batch paths separated by NUL bytes, taken two at a time
+      --continue-on-error  Exchange the remaining pairs after a failure instead of undoing all
+      --verify             Check that each entry arrived at its new name, exchanging back if not
+      --durable            Sync the entries and their directories to storage before finishing
+      --no-history         Do not record the exchanges for 'undo'
+      --operation-id <ID>  Succeed without renaming if exchange ID already completed
+  -h, --help               Print this help
+  -V, --version            Print the version
+
+The exit status is 0 on success, otherwise the library's error code of the first failure.
+";
+
+pub enum Command {
+    Help,
+    Version,
+    Exchange(ExchangeArgs),
+}
+
+#[derive(Debug)]
+pub struct ExchangeArgs {
+    pub preserve_ext: bool,
+    pub dry_run: bool,
+    pub verify: bool,
+    pub durability: Durability,
+    pub recording: Recording,
+    pub output: Output,
+    pub input: Input,
+}
+
+#[derive(Debug)]
+pub enum Recording {
+    /// `--no-history`.
+    Off,
+    /// Records exchanges in the default history, with an optional operation ID.
+    On(Option<String>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Human,
+    Json,
+    JsonLines,
+}
+
+#[derive(Debug)]
+pub enum Input {
+    Pair(PathBuf, PathBuf),
+    /// Reports every problem an exchange of the pair would run into.
+    Diagnose(PathBuf, PathBuf),
+    Batch(Batch),
+    /// Undoes the recorded exchange with this id, or the latest one.
+    Undo(Option<u64>),
+}
+
+#[derive(Debug)]
+pub struct Batch {
+    /// `None` reads standard input.
+    pub file: Option<PathBuf>,
+    pub null_separated: bool,
+    pub continue_on_error: bool,
+}
+
+pub fn parse(args: impl IntoIterator<Item = OsString>) -> Result<Command, String> {
+    let mut preserve_ext = false;
+    let mut dry_run = false;
+    let mut diagnose = false;
+    let mut verify = false;
+    let mut durability = Durability::None;
+    let mut history = true;
+    let mut undo = false;
+    let mut operation_id = None;
+    let mut output = Output::Human;
+    let mut paths = Vec::new();
+    let mut batch_file = None;
+    let mut null_separated = false;
+    let mut continue_on_error = false;
+    let mut options_done = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if options_done || !arg.to_string_lossy().starts_with('-') || arg == "-" {
+            if !options_done && !undo && paths.is_empty() && arg == "undo" {
+                undo = true;
+            } else {
+                paths.push(PathBuf::from(arg));
+            }
+            continue;
+        }
+        match arg.to_str() {
+            Some("--") => options_done = true,
+            Some("-h" | "--help") => return Ok(Command::Help),
+            Some("-V" | "--version") => return Ok(Command::Version),
+            Some("--preserve-ext") => preserve_ext = true,
+            Some("--dry-run") => dry_run = true,
+            Some("--diagnose") => diagnose = true,
+            Some("--verify") => verify = true,
+            Some("--durable") => durability = Durability::Full,
+            Some("--no-history") => history = false,
+            Some("--operation-id") => operation_id = Some(parse_operation_id(args.next())?),
+            Some("--json") => output = select_output(output, Output::Json)?,
+            Some("--jsonl") => output = select_output(output, Output::JsonLines)?,
+            Some("--batch") => {
+                let file = args
+                    .next()
+                    .ok_or("--batch needs a file, or '-' for stdin")?;
+                batch_file = Some(PathBuf::from(file));
+            }
+            Some("-0" | "--null") => null_separated = true,
+            Some("--continue-on-error") => continue_on_error = true,
+            _ => return Err(format!("unknown option '{}'", arg.to_string_lossy())),
+        }
+    }
+
+    if operation_id.is_some() && !history {
+        return Err("--operation-id cannot be combined with --no-history".to_owned());
+    }
+    let input = if undo {
+        if preserve_ext
+            || dry_run
+            || diagnose
+            || !history
+            || batch_file.is_some()
+            || null_separated
+            || continue_on_error
+            || operation_id.is_some()
+        {
+            return Err("undo only accepts --json, --jsonl, --verify, and --durable".to_owned());
+        }
+        undo_id(&paths)?
+    } else if let Some(file) = batch_file {
+        if !paths.is_empty() {
+            return Err("paths cannot be combined with --batch".to_owned());
+        }
+        if diagnose {
+            return Err("--diagnose cannot be combined with --batch".to_owned());
+        }
+        Input::Batch(Batch {
+            file: (file.as_os_str() != "-").then_some(file),
+            null_separated,
+            continue_on_error,
+        })
+    } else if null_separated || continue_on_error {
+        return Err("-0 and --continue-on-error require --batch".to_owned());
+    } else {
+        pair(paths, diagnose)?
+    };
+    Ok(Command::Exchange(ExchangeArgs {
+        preserve_ext,
+        dry_run,
+        verify,
+        durability,
+        recording: if history {
+            Recording::On(operation_id)
+        } else {
+            Recording::Off
+        },
+        output,
+        input,
+    }))
+}
+
+fn undo_id(paths: &[PathBuf]) -> Result<Input, String> {
+    match paths {
+        [] => Ok(Input::Undo(None)),
+        [id] => Ok(Input::Undo(Some(
+            id.to_str()
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(|| format!("invalid exchange id '{}'", id.display()))?,
+        ))),
+        _ => Err("undo takes at most one exchange id".to_owned()),
+    }
+}
+
+fn pair(paths: Vec<PathBuf>, diagnose: bool) -> Result<Input, String> {
+    let [first, second] = <[PathBuf; 2]>::try_from(paths)
+        .map_err(|paths| format!("expected two paths, got {}", paths.len()))?;
+    Ok(if diagnose {
+        Input::Diagnose(first, second)
+    } else {
+        Input::Pair(first, second)
+    })
+}
+
+fn parse_operation_id(value: Option<OsString>) -> Result<String, String> {
+    let id = value
+        .ok_or("--operation-id needs an ID")?
+        .into_string()
+        .map_err(|_| "the operation ID must be UTF-8".to_owned())?;
+    if id.is_empty() {
+        return Err("the operation ID must not be empty".to_owned());
+    }
+    Ok(id)
+}
+
+fn select_output(current: Output, requested: Output) -> Result<Output, String> {
+    if current != Output::Human && current != requested {
+        return Err("--json and --jsonl cannot be combined".to_owned());
+    }
+    Ok(requested)
+}
@@ -0,0 +1,279 @@
+use std::{
+    fs,
+    io::{self, Read as _},
+    path::{Path, PathBuf},
+};
+
+use exchange_name_lib::{
+    exchange_batch, plan_exchange, ExchangeOptions, RenameError, ReportStatus,
+};
+use serde::Serialize;
+
+use crate::{args::Batch, report, Fallback};
+
+/// Result of one pair of a batch.
+#[derive(Serialize)]
+pub struct PairReport {
+    pub first: String,
+    pub second: String,
+    /// `0`, or the library error code; [`RenameError::Aborted`] marks pairs left unchanged
+    /// because another pair failed.
+    pub code: i32,
+    pub message: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct BatchReport {
+    pub status: ReportStatus,
+    /// Exit code of the whole batch.
+    pub code: i32,
+    pub dry_run: bool,
+    pub pairs: Vec<PairReport>,
+    pub summary: Summary,
+    /// Failure that is not tied to one pair.
+    pub message: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Summary {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+/// Failure to read or parse the batch input.
+pub struct InputError {
+    pub message: String,
+    pub code: i32,
+}
+
+pub fn read_pairs(batch: &Batch) -> Result<Vec<(PathBuf, PathBuf)>, InputError> {
+    let input = if let Some(file) = &batch.file {
+        fs::read(file).map_err(|error| InputError::io(file, &error))?
+    } else {
+        let mut input = Vec::new();
+        io::stdin()
+            .read_to_end(&mut input)
+            .map_err(|error| InputError::io(Path::new("<stdin>"), &error))?;
+        input
+    };
+    if batch.null_separated {
+        parse_null_separated(&input)
+    } else {
+        parse_lines(&input)
+    }
+}
+
+pub fn run(
+    pairs: Vec<(PathBuf, PathBuf)>,
+    options: &ExchangeOptions,
+    fallback: Option<Fallback<'_>>,
+    dry_run: bool,
+    continue_on_error: bool,
+) -> BatchReport {
+    if dry_run || continue_on_error {
+        run_each(pairs, options, fallback, dry_run, continue_on_error)
+    } else {
+        run_all_or_nothing(&pairs, options, fallback)
+    }
+}
+
+impl BatchReport {
+    fn new(pairs: Vec<PairReport>, code: i32, message: Option<String>, dry_run: bool) -> Self {
+        let aborted = RenameError::Aborted.to_code();
+        let skipped = pairs.iter().filter(|pair| pair.code == aborted).count();
+        let succeeded = pairs.iter().filter(|pair| pair.code == 0).count();
+        Self {
+            status: if code == 0 {
+                ReportStatus::Ok
+            } else {
+                ReportStatus::Error
+            },
+            code,
+            dry_run,
+            summary: Summary {
+                succeeded,
+                failed: pairs.len() - succeeded - skipped,
+                skipped,
+            },
+            pairs,
+            message,
+        }
+    }
+}
+
+impl PairReport {
+    fn new(first: &Path, second: &Path, code: i32, message: Option<String>) -> Self {
+        Self {
+            first: first.to_string_lossy().into_owned(),
+            second: second.to_string_lossy().into_owned(),
+            code,
+            message,
+        }
+    }
+}
+
+/// Plans, and unless `dry_run` executes, each pair on its own.
+fn run_each(
+    pairs: Vec<(PathBuf, PathBuf)>,
+    options: &ExchangeOptions,
+    fallback: Option<Fallback<'_>>,
+    dry_run: bool,
+    continue_on_error: bool,
+) -> BatchReport {
+    let mut reports = Vec::with_capacity(pairs.len());
+    let mut code = 0;
+    for (first, second) in pairs {
+        let (pair_code, message) = if code != 0 && !continue_on_error {
+            (RenameError::Aborted.to_code(), None)
+        } else {
+            let exchange = |options| {
+                plan_exchange(&first, &second, options).and_then(|plan| {
+                    if dry_run {
+                        Ok(())
+                    } else {
+                        plan.execute()
+                    }
+                })
+            };
+            let result = exchange(options)
+                .or_else(|error| Fallback::retry(fallback, &error).map_or(Err(error), exchange));
+            match result {
+                Ok(()) => (0, None),
+                Err(error) => (error.to_code(), Some(report::message(&error))),
+            }
+        };
+        if code == 0 && pair_code != 0 {
+            code = pair_code;
+        }
+        reports.push(PairReport::new(&first, &second, pair_code, message));
+    }
+    BatchReport::new(reports, code, None, dry_run)
+}
+
+fn run_all_or_nothing(
+    pairs: &[(PathBuf, PathBuf)],
+    options: &ExchangeOptions,
+    fallback: Option<Fallback<'_>>,
+) -> BatchReport {
+    let borrowed = pairs
+        .iter()
+        .map(|(first, second)| (first.as_path(), second.as_path()))
+        .collect::<Vec<_>>();
+    let failure = exchange_batch(&borrowed, options)
+        .or_else(|failure| {
+            // Pairs exchanged before the failing one were exchanged back.
+            Fallback::retry(fallback, failure.error())
+                .map_or(Err(failure), |options| exchange_batch(&borrowed, options))
+        })
+        .err();
+    let code = if failure.is_some() {
+        RenameError::Aborted.to_code()
+    } else {
+        0
+    };
+    let mut reports = pairs
+        .iter()
+        .map(|(first, second)| PairReport::new(first, second, code, None))
+        .collect::<Vec<_>>();
+    let Some(failure) = failure else {
+        return BatchReport::new(reports, 0, None, false);
+    };
+
+    let error = failure.error();
+    for &index in failure.unrestored() {
+        reports[index].code = error.to_code();
+        reports[index].message = Some("could not be exchanged back".to_owned());
+    }
+    let message = if let Some(index) = failure.index() {
+        let own = if failure.unrestored().is_empty() {
+            error
+        } else {
+            error.operation().unwrap_or(error)
+        };
+        reports[index].code = own.to_code();
+        reports[index].message = Some(report::message(own));
+        None
+    } else {
+        Some(report::message(error))
+    };
+    BatchReport::new(reports, error.to_code(), message, false)
+}
+
+fn parse_null_separated(input: &[u8]) -> Result<Vec<(PathBuf, PathBuf)>, InputError> {
+    let input = input.strip_suffix(b"\0").unwrap_or(input);
+    if input.is_empty() {
+        return Ok(Vec::new());
+    }
+    let paths = input
+        .split(|&byte| byte == 0)
+        .enumerate()
+        .map(|(index, bytes)| path_from_bytes(bytes, || format!("path {}", index + 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if paths.len() % 2 != 0 {
+        return Err(InputError::invalid(format!(
+            "expected an even number of NUL-separated paths, got {}",
+            paths.len()
+        )));
+    }
+    let mut paths = paths.into_iter();
+    Ok(std::iter::from_fn(|| Some((paths.next()?, paths.next()?))).collect())
+}
+
+fn parse_lines(input: &[u8]) -> Result<Vec<(PathBuf, PathBuf)>, InputError> {
+    let mut pairs = Vec::new();
+    for (index, line) in input.split(|&byte| byte == b'\n').enumerate() {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            continue;
+        }
+        let location = || format!("line {}", index + 1);
+        let mut fields = line.split(|&byte| byte == b'\t');
+        let (Some(first), Some(second), None) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(InputError::invalid(format!(
+                "{}: expected two paths separated by one tab",
+                location()
+            )));
+        };
+        pairs.push((
+            path_from_bytes(first, location)?,
+            path_from_bytes(second, location)?,
+        ));
+    }
+    Ok(pairs)
+}
+
+fn path_from_bytes(bytes: &[u8], location: impl Fn() -> String) -> Result<PathBuf, InputError> {
+    if bytes.is_empty() {
+        return Err(InputError::invalid(format!("{}: empty path", location())));
+    }
+    #[cfg(unix)]
+    {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        Ok(PathBuf::from(OsStr::from_bytes(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        std::str::from_utf8(bytes)
+            .map(PathBuf::from)
+            .map_err(|_| InputError::invalid(format!("{}: path is not UTF-8", location())))
+    }
+}
+
+impl InputError {
+    fn invalid(message: String) -> Self {
+        Self {
+            code: RenameError::InvalidPath(message.clone()).to_code(),
+            message,
+        }
+    }
+
+    fn io(path: &Path, error: &io::Error) -> Self {
+        Self {
+            message: format!("cannot read {}: {error}", path.display()),
+            code: RenameError::from(error).to_code(),
+        }
+    }
+}
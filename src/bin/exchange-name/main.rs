@@ -0,0 +1,174 @@
+//! `exchange-name`: exchanges the names of two filesystem entries from the command line.
+
+mod args;
+mod batch;
+mod report;
+
+use std::{env, path::Path, process::ExitCode, sync::Arc};
+
+use args::{Command, Input, Output, Recording, USAGE};
+use exchange_name_lib::{
+    diagnose_exchange, plan_exchange, DiagnosticsReport, ExchangeError, ExchangeObserver,
+    ExchangeOptions, History, OutcomeReport, Phase, PlanReport, RenameError, StepReport,
+};
+
+fn main() -> ExitCode {
+    let args = match args::parse(env::args_os().skip(1)) {
+        Ok(Command::Help) => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Version) => {
+            println!("exchange-name {}", env!("CARGO_PKG_VERSION"));
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Exchange(args)) => args,
+        Err(message) => {
+            eprintln!("exchange-name: {message}\n\n{USAGE}");
+            return exit_code(RenameError::InvalidPath(message).to_code());
+        }
+    };
+
+    let observer: Arc<dyn ExchangeObserver> = if args.output == Output::JsonLines {
+        Arc::new(report::JsonLinesObserver)
+    } else {
+        Arc::new(report::WarningObserver)
+    };
+    let unrecorded = ExchangeOptions::new()
+        .preserve_ext(args.preserve_ext)
+        .verify(args.verify)
+        .durability(args.durability)
+        .observer(observer.clone());
+    let mut options = unrecorded.clone();
+    let mut fallback = None;
+    let history = History::default_path().map(History::new);
+    if let Recording::On(operation_id) = &args.recording {
+        // Recording needs device and inode numbers, which only Unix provides.
+        if let Some(history) = history.as_ref().filter(|_| cfg!(unix)) {
+            options = options.history(history.clone());
+        } else if cfg!(unix)
+            && !args.dry_run
+            && matches!(args.input, Input::Pair(..) | Input::Batch(_))
+        {
+            eprintln!(
+                "exchange-name: warning: cannot locate the history; set XDG_STATE_HOME or HOME \
+                 to record exchanges for undo"
+            );
+        }
+        if let Some(operation_id) = operation_id {
+            options = options.operation_id(operation_id.clone());
+        } else {
+            // Without an operation ID, the history is only a convenience for undoing.
+            fallback = Some(Fallback {
+                options: &unrecorded,
+                observer: observer.as_ref(),
+            });
+        }
+    }
+    match &args.input {
+        Input::Undo(id) => {
+            let Some(history) = history else {
+                let message = "cannot locate the history; set XDG_STATE_HOME or HOME";
+                eprintln!("exchange-name: {message}");
+                return exit_code(RenameError::InvalidPath(message.to_owned()).to_code());
+            };
+            let outcome = undo(&history, *id, &options);
+            report::outcome(&outcome, args.output);
+            exit_code(outcome.code)
+        }
+        Input::Pair(first, second) => {
+            let outcome = run(first, second, &options, fallback, args.dry_run);
+            report::outcome(&outcome, args.output);
+            exit_code(outcome.code)
+        }
+        Input::Diagnose(first, second) => {
+            let findings = diagnose_exchange(first, second, &options);
+            let report = DiagnosticsReport::from(findings.as_slice());
+            report::diagnostics(&report, args.output);
+            exit_code(report.code)
+        }
+        Input::Batch(input) => match batch::read_pairs(input) {
+            Ok(pairs) => {
+                let result = batch::run(
+                    pairs,
+                    &options,
+                    fallback,
+                    args.dry_run,
+                    input.continue_on_error,
+                );
+                report::batch(&result, args.output);
+                exit_code(result.code)
+            }
+            Err(error) => {
+                eprintln!("exchange-name: {}", error.message);
+                exit_code(error.code)
+            }
+        },
+    }
+}
+
+/// Options that run an exchange again without recording it, when recording it ahead failed.
+#[derive(Clone, Copy)]
+struct Fallback<'a> {
+    options: &'a ExchangeOptions,
+    observer: &'a dyn ExchangeObserver,
+}
+
+impl<'a> Fallback<'a> {
+    /// Returns the options to retry with after `error`, having reported it as a warning.
+    ///
+    /// Only history failures qualify; they happen before the first rename, or are reported by
+    /// the library itself once the entries were renamed.
+    fn retry(fallback: Option<Self>, error: &ExchangeError) -> Option<&'a ExchangeOptions> {
+        let fallback = fallback.filter(|_| error.phase() == Phase::History)?;
+        fallback
+            .observer
+            .history_failed(error.path().unwrap_or(Path::new("")), error);
+        Some(fallback.options)
+    }
+}
+
+fn run(
+    first: &Path,
+    second: &Path,
+    options: &ExchangeOptions,
+    fallback: Option<Fallback<'_>>,
+    dry_run: bool,
+) -> OutcomeReport {
+    let plan = match plan_exchange(first, second, options) {
+        Ok(plan) => plan,
+        Err(error) => return OutcomeReport::failure(None, &error),
+    };
+    let steps = PlanReport::from(&plan);
+    if dry_run {
+        return OutcomeReport::success(steps, false);
+    }
+    match plan.execute() {
+        Ok(()) => OutcomeReport::success(steps, true),
+        Err(error) => match Fallback::retry(fallback, &error) {
+            Some(unrecorded) => run(first, second, unrecorded, None, dry_run),
+            None => OutcomeReport::failure(Some(steps), &error),
+        },
+    }
+}
+
+fn undo(history: &History, id: Option<u64>, options: &ExchangeOptions) -> OutcomeReport {
+    let result = match id {
+        Some(id) => history.undo(id, options),
+        None => history.undo_last(options),
+    };
+    match result {
+        Ok(entry) => {
+            let steps = entry
+                .steps()
+                .map(|(original, exchanged)| StepReport::new(exchanged, original))
+                .collect();
+            OutcomeReport::success(PlanReport { steps }, true)
+        }
+        Err(error) => OutcomeReport::failure(None, &error),
+    }
+}
+
+fn exit_code(code: i32) -> ExitCode {
+    ExitCode::from(u8::try_from(code).unwrap_or(u8::MAX))
+}
@@ -0,0 +1,192 @@
+use std::{
+    io::{self, Write as _},
+    path::Path,
+};
+
+use exchange_name_lib::{
+    DiagnosticsReport, ErrorReport, EventReport, ExchangeError, ExchangeObserver, OutcomeReport,
+    RenameError, RenameStage, Severity,
+};
+use serde::Serialize;
+
+use crate::{
+    args::Output,
+    batch::{BatchReport, Summary},
+};
+
+pub fn outcome(outcome: &OutcomeReport, output: Output) {
+    match output {
+        Output::Json => emit(outcome),
+        Output::JsonLines => emit(&Final {
+            event: "outcome",
+            report: outcome,
+        }),
+        Output::Human => {
+            if let Some(error) = &outcome.error {
+                eprintln!("exchange-name: {}", error.message);
+                return;
+            }
+            let verb = if outcome.executed {
+                "renamed"
+            } else {
+                "would rename"
+            };
+            for step in &outcome.steps {
+                println!("{verb} {} -> {}", step.from, step.to);
+            }
+        }
+    }
+}
+
+pub fn diagnostics(report: &DiagnosticsReport, output: Output) {
+    match output {
+        Output::Json => emit(report),
+        Output::JsonLines => emit(&Final {
+            event: "diagnostics",
+            report,
+        }),
+        Output::Human => {
+            if report.findings.is_empty() {
+                println!("no problems found");
+            }
+            for finding in &report.findings {
+                let severity = match finding.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                };
+                println!("{severity}: {}", finding.error.message);
+            }
+        }
+    }
+}
+
+pub fn batch(report: &BatchReport, output: Output) {
+    match output {
+        Output::Json => return emit(report),
+        Output::JsonLines => {
+            return emit(&Final {
+                event: "batch",
+                report,
+            })
+        }
+        Output::Human => {}
+    }
+
+    let aborted = RenameError::Aborted.to_code();
+    let verb = if report.dry_run {
+        "would exchange"
+    } else {
+        "exchanged"
+    };
+    for pair in &report.pairs {
+        let (first, second) = (&pair.first, &pair.second);
+        match (pair.code, &pair.message) {
+            (0, _) => println!("{verb} {first} <-> {second}"),
+            (code, None) if code == aborted => {
+                eprintln!("not exchanged {first} <-> {second}: another pair failed");
+            }
+            (_, message) => eprintln!(
+                "exchange-name: {first} <-> {second}: {}",
+                message.as_deref().unwrap_or("failed")
+            ),
+        }
+    }
+    if let Some(message) = &report.message {
+        eprintln!("exchange-name: {message}");
+    }
+    let Summary {
+        succeeded,
+        failed,
+        skipped,
+    } = report.summary;
+    let done = if report.dry_run { "valid" } else { "exchanged" };
+    println!("{succeeded} {done}, {failed} failed, {skipped} not exchanged");
+}
+
+/// Formats an error followed by its chain of causes.
+pub fn message(error: &ExchangeError) -> String {
+    ErrorReport::from(error).message
+}
+
+/// Streams every step of an exchange as one JSON line.
+pub struct JsonLinesObserver;
+
+impl ExchangeObserver for JsonLinesObserver {
+    fn temp_dir_created(&self, path: &Path) {
+        emit(&EventReport::TempDirCreated { path: lossy(path) });
+    }
+
+    fn renamed(
+        &self,
+        stage: RenameStage,
+        from: &Path,
+        to: &Path,
+        result: Result<(), &ExchangeError>,
+    ) {
+        emit(&EventReport::Renamed {
+            stage,
+            from: lossy(from),
+            to: lossy(to),
+            error: result.err().map(ErrorReport::from),
+        });
+    }
+
+    fn rollback_started(&self, cause: &ExchangeError) {
+        emit(&EventReport::RollbackStarted {
+            cause: cause.into(),
+        });
+    }
+
+    fn rollback_step(&self, from: &Path, to: &Path, result: Result<(), &ExchangeError>) {
+        emit(&EventReport::RollbackStep {
+            from: lossy(from),
+            to: lossy(to),
+            error: result.err().map(ErrorReport::from),
+        });
+    }
+
+    fn cleanup(&self, path: &Path, result: Result<(), &io::Error>) {
+        emit(&EventReport::Cleanup {
+            path: lossy(path),
+            error: result.err().map(ToString::to_string),
+        });
+    }
+
+    fn history_failed(&self, path: &Path, error: &ExchangeError) {
+        emit(&EventReport::HistoryFailed {
+            path: lossy(path),
+            error: error.into(),
+        });
+    }
+}
+
+/// Warns on standard error when the history falls behind an exchange that stands.
+pub struct WarningObserver;
+
+impl ExchangeObserver for WarningObserver {
+    fn history_failed(&self, _path: &Path, error: &ExchangeError) {
+        eprintln!("exchange-name: warning: {}", message(error));
+    }
+}
+
+/// Last line of a JSON-lines stream, tagged like the step events.
+#[derive(Serialize)]
+struct Final<'a, T> {
+    event: &'static str,
+    #[serde(flatten)]
+    report: &'a T,
+}
+
+/// Writes one JSON line and flushes it, so consumers see each event as it happens.
+fn emit(value: &impl Serialize) {
+    let mut stdout = io::stdout().lock();
+    // Output errors such as a closed pipe leave nothing useful to report.
+    let _ = serde_json::to_writer(&mut stdout, value)
+        .map_err(io::Error::from)
+        .and_then(|()| writeln!(stdout))
+        .and_then(|()| stdout.flush());
+}
+
+fn lossy(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+
+use crate::{
+    entry::Entry,
+    mount::MountTable,
+    plan::{self, lock_entries},
+    resolver::{base_dir, resolve},
+    ExchangeError, ExchangeOptions,
+};
+
+/// How much a [`Finding`] stands in the way of an exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Severity {
+    /// The exchange would fail.
+    Error,
+    /// A check could not be completed; the exchange may still fail for its reason.
+    Warning,
+}
+
+/// One problem found by [`diagnose_exchange`](crate::diagnose_exchange).
+#[derive(Debug)]
+pub struct Finding {
+    severity: Severity,
+    error: ExchangeError,
+}
+
+impl Finding {
+    pub(crate) fn new(severity: Severity, error: ExchangeError) -> Self {
+        Self { severity, error }
+    }
+
+    #[must_use]
+    pub const fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the error the exchange would fail with, or the one that stopped a check.
+    #[must_use]
+    pub const fn error(&self) -> &ExchangeError {
+        &self.error
+    }
+
+    /// Returns the path the problem concerns, when there is one.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.error.path()
+    }
+
+    #[must_use]
+    pub fn into_error(self) -> ExchangeError {
+        self.error
+    }
+}
+
+/// Inspects both paths and runs every check on the pair, recording each problem.
+pub(crate) fn diagnose(path1: &Path, path2: &Path, options: &ExchangeOptions) -> Vec<Finding> {
+    let base_dir = match base_dir(options) {
+        Ok(base_dir) => base_dir,
+        Err(error) => return vec![Finding::new(Severity::Error, error)],
+    };
+    let mut findings = Vec::new();
+    let mut paths = Vec::new();
+    for path in [path1, path2] {
+        match resolve(path, &base_dir) {
+            Ok(path) => paths.push(path.into_path()),
+            Err(error) => findings.push(Finding::new(Severity::Error, error)),
+        }
+    }
+    let _lock = match lock_entries(&paths, options) {
+        Ok(lock) => lock,
+        Err(error) => {
+            findings.push(Finding::new(Severity::Error, error));
+            return findings;
+        }
+    };
+
+    let mut entries = Vec::new();
+    for path in paths {
+        match Entry::inspect(path) {
+            Ok(entry) => entries.push(entry),
+            Err(error) => findings.push(Finding::new(Severity::Error, error)),
+        }
+    }
+    plan::diagnose(&entries, options, &MountTable::default(), &mut findings);
+    findings
+}
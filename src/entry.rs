@@ -4,7 +4,7 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::RenameError;
+use crate::{ExchangeError, Phase, RenameError};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum EntryKind {
@@ -24,8 +24,18 @@ pub(crate) struct Entry {
 }
 
 impl Entry {
-    pub(crate) fn inspect(path: PathBuf) -> Result<Self, RenameError> {
-        let file_type = fs::symlink_metadata(&path)?.file_type();
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", fields(kind), err)
+    )]
+    pub(crate) fn inspect(path: PathBuf) -> Result<Self, ExchangeError> {
+        let file_type = fs::symlink_metadata(&path)
+            .map_err(|error| {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(io_error_kind = ?error.kind(), os_error = error.raw_os_error());
+                ExchangeError::io(Phase::Inspect, &path, error)
+            })?
+            .file_type();
         let kind = if file_type.is_symlink() {
             EntryKind::Symlink
         } else if file_type.is_file() {
@@ -33,8 +43,14 @@ impl Entry {
         } else if file_type.is_dir() {
             EntryKind::Directory
         } else {
-            return Err(RenameError::UnsupportedFileType(path));
+            return Err(ExchangeError::new(
+                RenameError::UnsupportedFileType(path.clone()),
+                Phase::Inspect,
+            )
+            .with_path(&path));
         };
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("kind", tracing::field::debug(kind));
 
         let parent = path
             .parent()
@@ -60,13 +76,35 @@ impl Entry {
         })
     }
 
+    /// Describes an entry known only by its name inside an open directory.
+    #[cfg(unix)]
+    pub(crate) fn named(file_name: &OsStr, kind: EntryKind) -> Self {
+        let (stem, extension) = if kind == EntryKind::File {
+            split_file_name(file_name)
+        } else {
+            (file_name.to_os_string(), None)
+        };
+        Self {
+            path: PathBuf::from(file_name),
+            parent: PathBuf::new(),
+            file_name: file_name.to_os_string(),
+            stem,
+            extension,
+            kind,
+        }
+    }
+
     pub(crate) fn is_directory(&self) -> bool {
         self.kind == EntryKind::Directory
     }
 }
 
-fn invalid_component(path: &Path, component: &str) -> RenameError {
-    RenameError::InvalidPath(format!("path has no {component}: {}", path.display()))
+fn invalid_component(path: &Path, component: &str) -> ExchangeError {
+    ExchangeError::new(
+        RenameError::InvalidPath(format!("path has no {component}: {}", path.display())),
+        Phase::Inspect,
+    )
+    .with_path(path)
 }
 
 fn split_file_name(file_name: &OsStr) -> (OsString, Option<OsString>) {
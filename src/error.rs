@@ -1,6 +1,11 @@
-use std::{fmt, io, path::PathBuf};
+use std::{
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum RenameError {
     PermissionDenied,
     AlreadyExists,
@@ -9,6 +14,19 @@ pub enum RenameError {
     InvalidPath(String),
     UnsupportedFileType(PathBuf),
     RollbackFailed { operation: String, rollback: String },
+    Cancelled,
+    CrossDevice,
+    NameTooLong,
+    Busy,
+    SymlinkLoop,
+    NestedDirectories,
+    ReadOnlyFilesystem,
+    LockTimeout,
+    Changed,
+    Aborted,
+    VerificationFailed(String),
+    MountPoint,
+    InvalidOperationId(String),
     Unknown(String),
 }
 
@@ -23,6 +41,19 @@ impl RenameError {
             Self::InvalidPath(_) => 5,
             Self::UnsupportedFileType(_) => 6,
             Self::RollbackFailed { .. } => 7,
+            Self::Cancelled => 8,
+            Self::CrossDevice => 9,
+            Self::NameTooLong => 10,
+            Self::Busy => 11,
+            Self::SymlinkLoop => 12,
+            Self::NestedDirectories => 13,
+            Self::ReadOnlyFilesystem => 14,
+            Self::LockTimeout => 15,
+            Self::Changed => 16,
+            Self::Aborted => 17,
+            Self::VerificationFailed(_) => 18,
+            Self::MountPoint => 19,
+            Self::InvalidOperationId(_) => 20,
             Self::Unknown(_) => 255,
         }
     }
@@ -46,28 +77,350 @@ impl fmt::Display for RenameError {
                 f,
                 "rename failed ({operation}) and rollback also failed ({rollback}); filesystem state may be inconsistent"
             ),
+            Self::Cancelled => f.write_str("cancelled before any entry was renamed"),
+            Self::CrossDevice => f.write_str("entries are on different filesystems"),
+            Self::NameTooLong => f.write_str("file name or path is too long"),
+            Self::Busy => f.write_str("entry is busy or in use"),
+            Self::SymlinkLoop => f.write_str("too many levels of symbolic links"),
+            Self::NestedDirectories => {
+                f.write_str("ancestor/descendant paths cannot be exchanged safely")
+            }
+            Self::ReadOnlyFilesystem => f.write_str("filesystem is read-only"),
+            Self::LockTimeout => f.write_str("timed out waiting for another exchange of these paths"),
+            Self::Changed => f.write_str("entries changed after the exchange was planned"),
+            Self::Aborted => f.write_str("undone or skipped because another item of the batch failed"),
+            Self::VerificationFailed(detail) => {
+                write!(f, "exchanged entries failed verification: {detail}")
+            }
+            Self::MountPoint => f.write_str("entry is a mount point"),
+            Self::InvalidOperationId(message) => write!(f, "invalid operation ID: {message}"),
             Self::Unknown(message) => write!(f, "unknown error: {message}"),
         }
     }
 }
 
-impl std::error::Error for RenameError {}
+impl Error for RenameError {}
 
 impl From<io::Error> for RenameError {
     fn from(value: io::Error) -> Self {
+        Self::from(&value)
+    }
+}
+
+impl From<&io::Error> for RenameError {
+    fn from(value: &io::Error) -> Self {
+        if let Some(error) = value.raw_os_error().and_then(from_os_code) {
+            return error;
+        }
         match value.kind() {
             io::ErrorKind::NotFound => Self::NotExists,
-            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
-                Self::PermissionDenied
-            }
+            io::ErrorKind::PermissionDenied => Self::PermissionDenied,
+            io::ErrorKind::ReadOnlyFilesystem => Self::ReadOnlyFilesystem,
             io::ErrorKind::AlreadyExists | io::ErrorKind::DirectoryNotEmpty => Self::AlreadyExists,
+            io::ErrorKind::CrossesDevices => Self::CrossDevice,
+            io::ErrorKind::ResourceBusy | io::ErrorKind::ExecutableFileBusy => Self::Busy,
             io::ErrorKind::InvalidInput
             | io::ErrorKind::InvalidFilename
             | io::ErrorKind::NotADirectory => Self::InvalidPath(value.to_string()),
-            io::ErrorKind::CrossesDevices => {
-                Self::Unknown(format!("cross-device rename is not supported: {value}"))
-            }
             _ => Self::Unknown(value.to_string()),
         }
     }
 }
+
+/// Maps OS errors that `io::ErrorKind` folds into broader or unstable kinds.
+#[cfg(unix)]
+fn from_os_code(code: i32) -> Option<RenameError> {
+    match code {
+        libc::ENAMETOOLONG => Some(RenameError::NameTooLong),
+        libc::ELOOP => Some(RenameError::SymlinkLoop),
+        _ => None,
+    }
+}
+
+/// Maps OS errors that `io::ErrorKind` folds into broader or unstable kinds.
+#[cfg(windows)]
+fn from_os_code(code: i32) -> Option<RenameError> {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+    const ERROR_FILENAME_EXCED_RANGE: i32 = 206;
+    const ERROR_CANT_RESOLVE_FILENAME: i32 = 1921;
+    match code {
+        ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION => Some(RenameError::Busy),
+        ERROR_FILENAME_EXCED_RANGE => Some(RenameError::NameTooLong),
+        ERROR_CANT_RESOLVE_FILENAME => Some(RenameError::SymlinkLoop),
+        _ => None,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn from_os_code(_code: i32) -> Option<RenameError> {
+    None
+}
+
+impl From<ExchangeError> for RenameError {
+    fn from(value: ExchangeError) -> Self {
+        value.kind
+    }
+}
+
+/// Stage of an exchange in which an [`ExchangeError`] occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+#[non_exhaustive]
+pub enum Phase {
+    /// Expanding and resolving the input paths.
+    Resolve,
+    /// Waiting for other exchanges of the same paths to finish.
+    Lock,
+    /// Reading the metadata of an existing entry.
+    Inspect,
+    /// Validating the pair and computing target names.
+    Plan,
+    /// Checking permissions and writability before anything is renamed.
+    Preflight,
+    /// Creating the temporary directory or renaming an entry.
+    Rename,
+    /// Undoing completed renames after a failure.
+    Rollback,
+    /// Checking that the entries arrived at their new names.
+    Verify,
+    /// Reading or appending to the exchange [`History`](crate::History).
+    History,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Resolve => "resolve",
+            Self::Lock => "lock",
+            Self::Inspect => "inspect",
+            Self::Plan => "plan",
+            Self::Preflight => "preflight",
+            Self::Rename => "rename",
+            Self::Rollback => "rollback",
+            Self::Verify => "verify",
+            Self::History => "history",
+        })
+    }
+}
+
+/// Error with the phase, the offending path, and the underlying cause of a failed exchange.
+///
+/// [`ExchangeError::kind`] is the flat [`RenameError`] used by [`exchange_rs`](crate::exchange_rs)
+/// and the C API, so [`ExchangeError::to_code`] matches [`RenameError::to_code`].
+#[derive(Debug)]
+pub struct ExchangeError {
+    kind: RenameError,
+    phase: Phase,
+    path: Option<PathBuf>,
+    source: Option<Source>,
+    operation: Option<Box<ExchangeError>>,
+    /// Problems found alongside this one by the pre-flight checks.
+    more: Box<[ExchangeError]>,
+}
+
+#[derive(Debug)]
+enum Source {
+    Io(io::Error),
+    Exchange(Box<ExchangeError>),
+}
+
+impl ExchangeError {
+    pub(crate) fn new(kind: RenameError, phase: Phase) -> Self {
+        Self {
+            kind,
+            phase,
+            path: None,
+            source: None,
+            operation: None,
+            more: Box::default(),
+        }
+    }
+
+    pub(crate) fn io(phase: Phase, path: &Path, error: io::Error) -> Self {
+        let mut result = Self::new(RenameError::from(&error), phase).with_path(path);
+        result.source = Some(Source::Io(error));
+        result
+    }
+
+    pub(crate) fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Combines a failed operation with the rollback failure that followed it.
+    pub(crate) fn rollback_failed(operation: Self, rollback: Vec<Self>) -> Self {
+        let kind = RenameError::RollbackFailed {
+            operation: operation.to_string(),
+            rollback: rollback
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("; "),
+        };
+        let mut rollback = rollback.into_iter();
+        let first = rollback.next();
+        Self {
+            kind,
+            phase: Phase::Rollback,
+            path: first.as_ref().and_then(|error| error.path.clone()),
+            source: first.map(|error| Source::Exchange(Box::new(error))),
+            operation: Some(Box::new(operation)),
+            more: Box::default(),
+        }
+    }
+
+    /// Reports the first of several problems, keeping the others for [`problems`](Self::problems).
+    pub(crate) fn with_more(mut self, more: Vec<Self>) -> Self {
+        self.more = more.into_boxed_slice();
+        self
+    }
+
+    /// Returns the flat error kind, as reported by [`exchange_rs`](crate::exchange_rs).
+    #[must_use]
+    pub const fn kind(&self) -> &RenameError {
+        &self.kind
+    }
+
+    #[must_use]
+    pub const fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Returns the path the failing step operated on, when there is one.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the OS error code of the underlying I/O failure, when there is one.
+    #[must_use]
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self.source.as_ref()? {
+            Source::Io(error) => error.raw_os_error(),
+            Source::Exchange(error) => error.raw_os_error(),
+        }
+    }
+
+    /// Returns the failure that triggered a failed rollback.
+    #[must_use]
+    pub fn operation(&self) -> Option<&Self> {
+        self.operation.as_deref()
+    }
+
+    /// Returns this error followed by the other problems the pre-flight checks found with it.
+    ///
+    /// Every other error is its only problem.
+    pub fn problems(&self) -> impl Iterator<Item = &Self> {
+        std::iter::once(self).chain(&*self.more)
+    }
+
+    #[must_use]
+    pub const fn to_code(&self) -> i32 {
+        self.kind.to_code()
+    }
+
+    /// Describes this error followed by its chain of causes.
+    pub(crate) fn message(&self) -> String {
+        let mut message = self.to_string();
+        let mut source = self.source();
+        while let Some(cause) = source {
+            message.push_str(": ");
+            message.push_str(&cause.to_string());
+            source = cause.source();
+        }
+        message
+    }
+}
+
+impl fmt::Display for ExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed: {}", self.phase, self.kind)?;
+        if let Some(path) = &self.path {
+            write!(f, " ({})", path.display())?;
+        }
+        match self.more.len() {
+            0 => {}
+            1 => f.write_str(" and 1 more problem")?,
+            more => write!(f, " and {more} more problems")?,
+        }
+        Ok(())
+    }
+}
+
+impl Error for ExchangeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self.source.as_ref()? {
+            Source::Io(error) => Some(error),
+            Source::Exchange(error) => Some(error.as_ref()),
+        }
+    }
+}
+
+/// Failure of an all-or-nothing batch of exchanges.
+///
+/// Exchanges completed before the failure are undone in reverse order; [`BatchError::unrestored`]
+/// lists those that could not be.
+#[derive(Debug)]
+pub struct BatchError {
+    index: Option<usize>,
+    error: Box<ExchangeError>,
+    unrestored: Vec<usize>,
+}
+
+impl BatchError {
+    pub(crate) fn new(index: Option<usize>, error: ExchangeError) -> Self {
+        Self {
+            index,
+            error: Box::new(error),
+            unrestored: Vec::new(),
+        }
+    }
+
+    pub(crate) fn with_unrestored(mut self, unrestored: Vec<usize>) -> Self {
+        self.unrestored = unrestored;
+        self
+    }
+
+    /// Returns the position of the failing pair, or `None` when the batch failed as a whole.
+    #[must_use]
+    pub const fn index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// Returns the failure, wrapped in [`RenameError::RollbackFailed`] if undoing failed.
+    #[must_use]
+    pub fn error(&self) -> &ExchangeError {
+        &self.error
+    }
+
+    #[must_use]
+    pub fn into_error(self) -> ExchangeError {
+        *self.error
+    }
+
+    /// Returns the positions of completed pairs that remain exchanged.
+    #[must_use]
+    pub fn unrestored(&self) -> &[usize] {
+        &self.unrestored
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            Some(index) => write!(f, "pair {index}: {}", self.error),
+            None => self.error.fmt(f),
+        }
+    }
+}
+
+impl Error for BatchError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
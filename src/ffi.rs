@@ -1,11 +1,71 @@
 use std::{
-    ffi::{c_char, CStr},
+    any::Any,
+    cell::RefCell,
+    ffi::{c_char, c_void, CStr},
+    io,
     panic::{catch_unwind, AssertUnwindSafe},
-    path::PathBuf,
-    slice, str,
+    path::{Path, PathBuf},
+    ptr, slice, str,
+    sync::Arc,
 };
 
-use crate::{exchange_rs, RenameError};
+#[cfg(unix)]
+mod at;
+mod batch;
+mod diagnostics;
+mod handles;
+mod info;
+mod precondition;
+
+#[cfg(unix)]
+pub use at::exchange_at;
+pub use batch::{exchange_batch_n, exchange_rotate_n, ExchangePath, ExchangePathPair};
+pub use diagnostics::{exchanger_diagnose, ExchangeFinding, ExchangeFindingCallback};
+pub use handles::{
+    exchanger_options_free, exchanger_options_new, exchanger_options_set_base_dir,
+    exchanger_options_set_durability, exchanger_options_set_history,
+    exchanger_options_set_lock_timeout_ms, exchanger_options_set_observer,
+    exchanger_options_set_operation_id, exchanger_options_set_preserve_ext,
+    exchanger_options_set_verify, exchanger_plan, exchanger_plan_abort, exchanger_plan_commit,
+    exchanger_plan_execute, exchanger_plan_free, exchanger_plan_journal_dir,
+    exchanger_plan_prepare, exchanger_plan_source, exchanger_plan_step_count,
+    exchanger_plan_target, exchanger_recover, ExchangerOptions, ExchangerPlan,
+};
+pub use info::{exchange_lib_capabilities, exchange_lib_version, ExchangeLibVersion};
+pub use precondition::{exchanger_options_expect, ExchangePrecondition};
+
+use crate::{
+    exchange_with, ExchangeError, ExchangeObserver, ExchangeOptions, Phase, RenameError,
+    RenameStage,
+};
+
+pub const EXCHANGE_EVENT_TEMP_DIR_CREATED: u32 = 0;
+pub const EXCHANGE_EVENT_RENAMED: u32 = 1;
+pub const EXCHANGE_EVENT_ROLLBACK_STARTED: u32 = 2;
+pub const EXCHANGE_EVENT_ROLLBACK_STEP: u32 = 3;
+pub const EXCHANGE_EVENT_CLEANUP: u32 = 4;
+pub const EXCHANGE_EVENT_HISTORY_FAILED: u32 = 5;
+
+/// One step of an exchange reported to a C observer.
+///
+/// Path buffers are not NUL-terminated and are valid only during the callback. Absent paths are
+/// null with a zero length.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExchangeEvent {
+    /// One of the `EXCHANGE_EVENT_*` values.
+    pub kind: u32,
+    /// `1`, `2` or `3` for renames in execution order, otherwise `0`.
+    pub stage: u32,
+    pub from: *const u8,
+    pub from_len: usize,
+    pub to: *const u8,
+    pub to_len: usize,
+    /// `0` on success, otherwise an error code; the failure code for rollback-started events.
+    pub result: i32,
+}
+
+pub type ExchangeEventCallback = extern "C" fn(event: *const ExchangeEvent, user_data: *mut c_void);
 
 /// Exchanges names using NUL-terminated UTF-8 strings.
 ///
@@ -24,7 +84,11 @@ pub unsafe extern "C" fn exchange(
         let path1 = unsafe { path_from_c_string(path1) }?;
         // SAFETY: Required by this function's contract.
         let path2 = unsafe { path_from_c_string(path2) }?;
-        exchange_rs(&path1, &path2, parse_bool(preserve_ext)?)
+        exchange_with(
+            &path1,
+            &path2,
+            &ExchangeOptions::new().preserve_ext(parse_bool(preserve_ext)?),
+        )
     })
 }
 
@@ -47,19 +111,305 @@ pub unsafe extern "C" fn exchange_n(
         let path1 = unsafe { path_from_bytes(path1, path1_len) }?;
         // SAFETY: Required by this function's contract.
         let path2 = unsafe { path_from_bytes(path2, path2_len) }?;
-        exchange_rs(&path1, &path2, parse_bool(preserve_ext)?)
+        exchange_with(
+            &path1,
+            &path2,
+            &ExchangeOptions::new().preserve_ext(parse_bool(preserve_ext)?),
+        )
+    })
+}
+
+/// Exchanges names using NUL-terminated strings taken as raw bytes.
+///
+/// On Unix the bytes form an opaque path and need not be UTF-8. On other platforms this behaves
+/// like [`exchange`].
+///
+/// # Safety
+///
+/// Pointer requirements match [`exchange`]. `preserve_ext` must be `0` or `1`.
+#[no_mangle]
+pub unsafe extern "C" fn exchange_raw(
+    path1: *const c_char,
+    path2: *const c_char,
+    preserve_ext: u8,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let path1 = unsafe { raw_path_from_c_string(path1) }?;
+        // SAFETY: Required by this function's contract.
+        let path2 = unsafe { raw_path_from_c_string(path2) }?;
+        exchange_with(
+            &path1,
+            &path2,
+            &ExchangeOptions::new().preserve_ext(parse_bool(preserve_ext)?),
+        )
+    })
+}
+
+/// Exchanges names using explicit buffer lengths and raw path bytes.
+///
+/// On Unix the bytes form an opaque path and need not be UTF-8; embedded NUL bytes are still
+/// rejected. On other platforms this behaves like [`exchange_n`].
+///
+/// # Safety
+///
+/// Pointer requirements match [`exchange_n`]. `preserve_ext` must be `0` or `1`.
+#[no_mangle]
+pub unsafe extern "C" fn exchange_raw_n(
+    path1: *const u8,
+    path1_len: usize,
+    path2: *const u8,
+    path2_len: usize,
+    preserve_ext: u8,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let path1 = unsafe { raw_path_from_bytes(path1, path1_len) }?;
+        // SAFETY: Required by this function's contract.
+        let path2 = unsafe { raw_path_from_bytes(path2, path2_len) }?;
+        exchange_with(
+            &path1,
+            &path2,
+            &ExchangeOptions::new().preserve_ext(parse_bool(preserve_ext)?),
+        )
+    })
+}
+
+/// Exchanges names like [`exchange_n`] and reports every step to `callback`.
+///
+/// # Safety
+///
+/// Path requirements match [`exchange_n`]. `callback` may be null; otherwise it is invoked
+/// synchronously on the calling thread with `user_data`, which is passed through unchanged.
+#[no_mangle]
+pub unsafe extern "C" fn exchange_n_observed(
+    path1: *const u8,
+    path1_len: usize,
+    path2: *const u8,
+    path2_len: usize,
+    preserve_ext: u8,
+    callback: Option<ExchangeEventCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let path1 = unsafe { path_from_bytes(path1, path1_len) }?;
+        // SAFETY: Required by this function's contract.
+        let path2 = unsafe { path_from_bytes(path2, path2_len) }?;
+        let mut options = ExchangeOptions::new().preserve_ext(parse_bool(preserve_ext)?);
+        if let Some(callback) = callback {
+            options = options.observer(Arc::new(CallbackObserver {
+                callback,
+                user_data,
+            }));
+        }
+        exchange_with(&path1, &path2, &options)
+    })
+}
+
+struct CallbackObserver {
+    callback: ExchangeEventCallback,
+    user_data: *mut c_void,
+}
+
+// SAFETY: The C contract requires the callback to accept `user_data` on whichever thread runs the
+// exchange; the library never calls it concurrently for one exchange.
+unsafe impl Send for CallbackObserver {}
+// SAFETY: See the `Send` implementation.
+unsafe impl Sync for CallbackObserver {}
+
+impl CallbackObserver {
+    fn emit(&self, kind: u32, stage: u32, from: Option<&Path>, to: Option<&Path>, result: i32) {
+        let (from, from_len) = path_parts(from);
+        let (to, to_len) = path_parts(to);
+        let event = ExchangeEvent {
+            kind,
+            stage,
+            from,
+            from_len,
+            to,
+            to_len,
+            result,
+        };
+        (self.callback)(&raw const event, self.user_data);
+    }
+}
+
+impl ExchangeObserver for CallbackObserver {
+    fn temp_dir_created(&self, path: &Path) {
+        self.emit(EXCHANGE_EVENT_TEMP_DIR_CREATED, 0, Some(path), None, 0);
+    }
+
+    fn renamed(
+        &self,
+        stage: RenameStage,
+        from: &Path,
+        to: &Path,
+        result: Result<(), &ExchangeError>,
+    ) {
+        let stage = match stage {
+            RenameStage::ToTemporary => 1,
+            RenameStage::FirstToTarget => 2,
+            RenameStage::TemporaryToTarget => 3,
+        };
+        self.emit(
+            EXCHANGE_EVENT_RENAMED,
+            stage,
+            Some(from),
+            Some(to),
+            result_code(result),
+        );
+    }
+
+    fn rollback_started(&self, cause: &ExchangeError) {
+        self.emit(
+            EXCHANGE_EVENT_ROLLBACK_STARTED,
+            0,
+            None,
+            None,
+            cause.to_code(),
+        );
+    }
+
+    fn rollback_step(&self, from: &Path, to: &Path, result: Result<(), &ExchangeError>) {
+        self.emit(
+            EXCHANGE_EVENT_ROLLBACK_STEP,
+            0,
+            Some(from),
+            Some(to),
+            result_code(result),
+        );
+    }
+
+    fn cleanup(&self, path: &Path, result: Result<(), &io::Error>) {
+        let result = result.map_or_else(
+            |error| RenameError::from(io::Error::from(error.kind())).to_code(),
+            |()| 0,
+        );
+        self.emit(EXCHANGE_EVENT_CLEANUP, 0, Some(path), None, result);
+    }
+
+    fn history_failed(&self, path: &Path, error: &ExchangeError) {
+        self.emit(
+            EXCHANGE_EVENT_HISTORY_FAILED,
+            0,
+            Some(path),
+            None,
+            error.to_code(),
+        );
+    }
+}
+
+fn path_parts(path: Option<&Path>) -> (*const u8, usize) {
+    path.map_or((ptr::null(), 0), |path| {
+        let bytes = path.as_os_str().as_encoded_bytes();
+        (bytes.as_ptr(), bytes.len())
     })
 }
 
-fn ffi_boundary(operation: impl FnOnce() -> Result<(), RenameError>) -> i32 {
-    match catch_unwind(AssertUnwindSafe(operation)) {
-        Ok(Ok(())) => 0,
-        Ok(Err(error)) => error.to_code(),
-        Err(_) => 255,
+fn result_code(result: Result<(), &ExchangeError>) -> i32 {
+    result.map_or_else(ExchangeError::to_code, |()| 0)
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<LastError>> = const { RefCell::new(None) };
+}
+
+struct LastError {
+    message: String,
+    os_code: Option<i32>,
+}
+
+/// Copies the message of the last failed call on this thread into `buffer`.
+///
+/// Returns the full message length in bytes, excluding the terminator, or `0` when the last call
+/// on this thread succeeded. At most `buffer_len - 1` bytes are written, truncated at a UTF-8
+/// character boundary and always followed by a NUL terminator.
+///
+/// # Safety
+///
+/// `buffer` must be writable for `buffer_len` bytes. It may be null only when `buffer_len` is `0`.
+#[no_mangle]
+pub unsafe extern "C" fn exchange_last_error_message(
+    buffer: *mut c_char,
+    buffer_len: usize,
+) -> usize {
+    LAST_ERROR.with_borrow(|last| {
+        let message = last.as_ref().map_or("", |last| last.message.as_str());
+        // SAFETY: Required by this function's contract.
+        unsafe { copy_out(message.as_bytes(), buffer, buffer_len) }
+    })
+}
+
+/// Copies as much of `value` as fits into `buffer`, NUL-terminated, and returns its full length.
+///
+/// UTF-8 values are truncated at a character boundary.
+unsafe fn copy_out(value: &[u8], buffer: *mut c_char, buffer_len: usize) -> usize {
+    if !buffer.is_null() && buffer_len > 0 {
+        let mut length = value.len().min(buffer_len - 1);
+        if let Ok(text) = str::from_utf8(value) {
+            while !text.is_char_boundary(length) {
+                length -= 1;
+            }
+        }
+        // SAFETY: The caller guarantees `buffer_len` writable bytes, and `length < buffer_len`.
+        unsafe {
+            ptr::copy_nonoverlapping(value.as_ptr(), buffer.cast::<u8>(), length);
+            buffer.add(length).write(0);
+        }
+    }
+    value.len()
+}
+
+/// Returns the OS error code behind the last failed call on this thread, or `0` when there is
+/// none.
+#[no_mangle]
+pub extern "C" fn exchange_last_error_os_code() -> i32 {
+    LAST_ERROR.with_borrow(|last| last.as_ref().and_then(|last| last.os_code).unwrap_or(0))
+}
+
+fn ffi_boundary(operation: impl FnOnce() -> Result<(), ExchangeError>) -> i32 {
+    let (code, last) = match catch_unwind(AssertUnwindSafe(operation)) {
+        Ok(Ok(())) => (0, None),
+        Ok(Err(error)) => (error.to_code(), Some(LastError::from_error(&error))),
+        Err(payload) => (255, Some(LastError::from_panic(payload.as_ref()))),
+    };
+    LAST_ERROR.set(last);
+    code
+}
+
+/// Runs a release function without letting a panic unwind into C.
+///
+/// Unlike [`ffi_boundary`], a release keeps the last error of the call before it, so that it can
+/// still be read after freeing a failed plan; only a panic replaces it.
+fn release(operation: impl FnOnce()) {
+    if let Err(payload) = catch_unwind(AssertUnwindSafe(operation)) {
+        LAST_ERROR.set(Some(LastError::from_panic(payload.as_ref())));
     }
 }
 
-unsafe fn path_from_c_string(pointer: *const c_char) -> Result<PathBuf, RenameError> {
+impl LastError {
+    fn from_error(error: &ExchangeError) -> Self {
+        Self {
+            message: error.message(),
+            os_code: error.raw_os_error(),
+        }
+    }
+
+    fn from_panic(payload: &(dyn Any + Send)) -> Self {
+        let detail = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic payload");
+        Self {
+            message: format!("panic: {detail}"),
+            os_code: None,
+        }
+    }
+}
+
+unsafe fn path_from_c_string(pointer: *const c_char) -> Result<PathBuf, ExchangeError> {
     if pointer.is_null() {
         return Err(invalid("null path pointer"));
     }
@@ -71,7 +421,28 @@ unsafe fn path_from_c_string(pointer: *const c_char) -> Result<PathBuf, RenameEr
     path_from_utf8(value)
 }
 
-unsafe fn path_from_bytes(pointer: *const u8, length: usize) -> Result<PathBuf, RenameError> {
+unsafe fn path_from_bytes(pointer: *const u8, length: usize) -> Result<PathBuf, ExchangeError> {
+    // SAFETY: Required by the caller of this function.
+    let bytes = unsafe { path_buffer(pointer, length) }?;
+    let value =
+        str::from_utf8(bytes).map_err(|error| invalid(format!("path is not UTF-8: {error}")))?;
+    path_from_utf8(value)
+}
+
+unsafe fn raw_path_from_c_string(pointer: *const c_char) -> Result<PathBuf, ExchangeError> {
+    if pointer.is_null() {
+        return Err(invalid("null path pointer"));
+    }
+    // SAFETY: Required by the caller of this function.
+    path_from_raw(unsafe { CStr::from_ptr(pointer) }.to_bytes())
+}
+
+unsafe fn raw_path_from_bytes(pointer: *const u8, length: usize) -> Result<PathBuf, ExchangeError> {
+    // SAFETY: Required by the caller of this function.
+    path_from_raw(unsafe { path_buffer(pointer, length) }?)
+}
+
+unsafe fn path_buffer<'a>(pointer: *const u8, length: usize) -> Result<&'a [u8], ExchangeError> {
     if pointer.is_null() {
         return Err(invalid("null path pointer"));
     }
@@ -80,12 +451,10 @@ unsafe fn path_from_bytes(pointer: *const u8, length: usize) -> Result<PathBuf,
     if bytes.contains(&0) {
         return Err(invalid("path contains an embedded NUL byte"));
     }
-    let value =
-        str::from_utf8(bytes).map_err(|error| invalid(format!("path is not UTF-8: {error}")))?;
-    path_from_utf8(value)
+    Ok(bytes)
 }
 
-fn parse_bool(value: u8) -> Result<bool, RenameError> {
+fn parse_bool(value: u8) -> Result<bool, ExchangeError> {
     match value {
         0 => Ok(false),
         1 => Ok(true),
@@ -93,7 +462,7 @@ fn parse_bool(value: u8) -> Result<bool, RenameError> {
     }
 }
 
-fn path_from_utf8(value: &str) -> Result<PathBuf, RenameError> {
+fn path_from_utf8(value: &str) -> Result<PathBuf, ExchangeError> {
     if value.is_empty() {
         Err(invalid("path is empty"))
     } else {
@@ -101,6 +470,25 @@ fn path_from_utf8(value: &str) -> Result<PathBuf, RenameError> {
     }
 }
 
-fn invalid(message: impl Into<String>) -> RenameError {
-    RenameError::InvalidPath(message.into())
+#[cfg(unix)]
+fn path_from_raw(bytes: &[u8]) -> Result<PathBuf, ExchangeError> {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    if bytes.is_empty() {
+        Err(invalid("path is empty"))
+    } else {
+        Ok(PathBuf::from(OsStr::from_bytes(bytes)))
+    }
+}
+
+/// Only Unix paths are byte strings; elsewhere raw paths must still be UTF-8.
+#[cfg(not(unix))]
+fn path_from_raw(bytes: &[u8]) -> Result<PathBuf, ExchangeError> {
+    let value =
+        str::from_utf8(bytes).map_err(|error| invalid(format!("path is not UTF-8: {error}")))?;
+    path_from_utf8(value)
+}
+
+fn invalid(message: impl Into<String>) -> ExchangeError {
+    ExchangeError::new(RenameError::InvalidPath(message.into()), Phase::Resolve)
 }
@@ -0,0 +1,45 @@
+use std::{ffi::c_int, os::fd::BorrowedFd};
+
+use super::{
+    ffi_boundary, handles::options_or_default, invalid, raw_path_from_bytes, ExchangerOptions,
+};
+
+/// Like [`exchange_raw_n`](super::exchange_raw_n), with each name taken as a single component
+/// inside an open directory, as [`exchange_at`](crate::exchange_at) does.
+///
+/// # Safety
+///
+/// `dirfd1` and `dirfd2` must be open directory descriptors for the duration of the call. Name
+/// requirements match [`exchange_raw_n`](super::exchange_raw_n). `options` must be null, for
+/// default options, or a live pointer from
+/// [`exchanger_options_new`](super::exchanger_options_new).
+#[no_mangle]
+pub unsafe extern "C" fn exchange_at(
+    dirfd1: c_int,
+    name1: *const u8,
+    name1_len: usize,
+    dirfd2: c_int,
+    name2: *const u8,
+    name2_len: usize,
+    options: *const ExchangerOptions,
+) -> i32 {
+    ffi_boundary(|| {
+        if dirfd1 < 0 || dirfd2 < 0 {
+            return Err(invalid("directory descriptors must not be negative"));
+        }
+        // SAFETY: Required by this function's contract.
+        let name1 = unsafe { raw_path_from_bytes(name1, name1_len) }?;
+        // SAFETY: Required by this function's contract.
+        let name2 = unsafe { raw_path_from_bytes(name2, name2_len) }?;
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_or_default(options) };
+        // SAFETY: Both descriptors are non-negative and open for the call, as required.
+        let (dir1, dir2) = unsafe {
+            (
+                BorrowedFd::borrow_raw(dirfd1),
+                BorrowedFd::borrow_raw(dirfd2),
+            )
+        };
+        crate::exchange_at(dir1, &name1, dir2, &name2, &options)
+    })
+}
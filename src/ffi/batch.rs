@@ -0,0 +1,146 @@
+use std::{path::PathBuf, slice};
+
+use super::{
+    ffi_boundary, handles::options_or_default, invalid, path_from_bytes, ExchangerOptions,
+};
+use crate::{exchange_batch, rotate, ExchangeError, RenameError};
+
+/// One pair of UTF-8 paths for [`exchange_batch_n`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExchangePathPair {
+    pub path1: *const u8,
+    pub path1_len: usize,
+    pub path2: *const u8,
+    pub path2_len: usize,
+}
+
+/// One UTF-8 path for [`exchange_rotate_n`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExchangePath {
+    pub path: *const u8,
+    pub len: usize,
+}
+
+/// Exchanges every pair in order as one all-or-nothing operation.
+///
+/// When `results` is not null, it receives one code per pair: `0` after success, the failure
+/// code for the failing pair, [`RenameError::Aborted`] (`17`) for pairs undone or never started,
+/// and `7` for completed pairs that could not be undone. Returns the overall code.
+///
+/// # Safety
+///
+/// `options` must be null, for default options, or a live pointer from
+/// [`exchanger_options_new`](super::exchanger_options_new). `pairs` must point to `count`
+/// readable pairs whose buffers are readable for their lengths, and `results`, when not null, must
+/// be writable for `count` values.
+#[no_mangle]
+pub unsafe extern "C" fn exchange_batch_n(
+    options: *const ExchangerOptions,
+    pairs: *const ExchangePathPair,
+    count: usize,
+    results: *mut i32,
+) -> i32 {
+    ffi_boundary(|| {
+        let mut results = if results.is_null() {
+            None
+        } else {
+            // SAFETY: Required by this function's contract.
+            Some(unsafe { slice::from_raw_parts_mut(results, count) })
+        };
+        if let Some(results) = results.as_deref_mut() {
+            results.fill(RenameError::Aborted.to_code());
+        }
+        let mut record = |index: usize, code: i32| {
+            if let Some(results) = results.as_deref_mut() {
+                results[index] = code;
+            }
+        };
+
+        // SAFETY: Required by this function's contract.
+        let pairs = unsafe { array(pairs, count) }?;
+        let mut paths = Vec::with_capacity(count);
+        for (index, pair) in pairs.iter().enumerate() {
+            // SAFETY: Required by this function's contract.
+            let parsed = unsafe { parse_pair(pair) }.inspect_err(|error| {
+                record(index, error.to_code());
+            })?;
+            paths.push(parsed);
+        }
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_or_default(options) };
+        let borrowed = paths
+            .iter()
+            .map(|(path1, path2)| (path1.as_path(), path2.as_path()))
+            .collect::<Vec<_>>();
+
+        match exchange_batch(&borrowed, &options) {
+            Ok(()) => {
+                (0..count).for_each(|index| record(index, 0));
+                Ok(())
+            }
+            Err(failure) => {
+                if let Some(index) = failure.index() {
+                    let error = failure.error();
+                    let own = if failure.unrestored().is_empty() {
+                        error
+                    } else {
+                        error.operation().unwrap_or(error)
+                    };
+                    record(index, own.to_code());
+                }
+                for &index in failure.unrestored() {
+                    record(index, failure.error().to_code());
+                }
+                Err(failure.into_error())
+            }
+        }
+    })
+}
+
+/// Gives each entry the name of the next one and the last entry the name of the first.
+///
+/// # Safety
+///
+/// `options` must be null, for default options, or a live pointer from
+/// [`exchanger_options_new`](super::exchanger_options_new). `paths` must point to `count`
+/// readable paths whose buffers are readable for their lengths.
+#[no_mangle]
+pub unsafe extern "C" fn exchange_rotate_n(
+    options: *const ExchangerOptions,
+    paths: *const ExchangePath,
+    count: usize,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let paths = unsafe { array(paths, count) }?
+            .iter()
+            // SAFETY: Required by this function's contract.
+            .map(|path| unsafe { path_from_bytes(path.path, path.len) })
+            .collect::<Result<Vec<_>, _>>()?;
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_or_default(options) };
+        let borrowed = paths.iter().map(PathBuf::as_path).collect::<Vec<_>>();
+        rotate(&borrowed, &options)
+    })
+}
+
+unsafe fn parse_pair(pair: &ExchangePathPair) -> Result<(PathBuf, PathBuf), ExchangeError> {
+    // SAFETY: Required by the caller of this function.
+    let path1 = unsafe { path_from_bytes(pair.path1, pair.path1_len) }?;
+    // SAFETY: Required by the caller of this function.
+    let path2 = unsafe { path_from_bytes(pair.path2, pair.path2_len) }?;
+    Ok((path1, path2))
+}
+
+unsafe fn array<'a, T>(pointer: *const T, count: usize) -> Result<&'a [T], ExchangeError> {
+    if count == 0 {
+        return Ok(&[]);
+    }
+    if pointer.is_null() {
+        return Err(invalid("null array pointer"));
+    }
+    // SAFETY: Required by the caller of this function.
+    Ok(unsafe { slice::from_raw_parts(pointer, count) })
+}
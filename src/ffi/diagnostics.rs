@@ -0,0 +1,94 @@
+use std::ffi::{c_char, c_void, CString};
+
+use super::{
+    ffi_boundary, handles::options_or_default, path_from_bytes, path_parts, ExchangerOptions,
+};
+use crate::{diagnose_exchange, Finding, Severity};
+
+/// The finding would make the exchange fail.
+pub const EXCHANGE_SEVERITY_ERROR: u32 = 1;
+/// A check could not be completed.
+pub const EXCHANGE_SEVERITY_WARNING: u32 = 2;
+
+/// One problem reported to an [`ExchangeFindingCallback`]; see [`Finding`].
+///
+/// The path buffer is not NUL-terminated; it is null with a zero length when the finding
+/// concerns no path. All pointers are valid only during the callback.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExchangeFinding {
+    /// One of the `EXCHANGE_SEVERITY_*` values.
+    pub severity: u32,
+    /// The error code the exchange would fail with.
+    pub code: i32,
+    pub path: *const u8,
+    pub path_len: usize,
+    /// NUL-terminated description of the problem and its causes.
+    pub message: *const c_char,
+}
+
+pub type ExchangeFindingCallback =
+    extern "C" fn(finding: *const ExchangeFinding, user_data: *mut c_void);
+
+/// Runs every check an exchange of two UTF-8 paths would make and reports each problem found to
+/// `callback`, in order; see [`diagnose_exchange`].
+///
+/// Returns `0` when no finding is an error, and otherwise the code of the first error finding,
+/// which also becomes the last error.
+///
+/// # Safety
+///
+/// `options` must be null, for default options, or a live pointer from
+/// [`exchanger_options_new`](super::exchanger_options_new). Path requirements match
+/// [`exchange_n`](super::exchange_n). `callback`, if not null, must be safe to call with
+/// `user_data` during this call.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_diagnose(
+    options: *const ExchangerOptions,
+    path1: *const u8,
+    path1_len: usize,
+    path2: *const u8,
+    path2_len: usize,
+    callback: Option<ExchangeFindingCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let path1 = unsafe { path_from_bytes(path1, path1_len) }?;
+        // SAFETY: Required by this function's contract.
+        let path2 = unsafe { path_from_bytes(path2, path2_len) }?;
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_or_default(options) };
+
+        let findings = diagnose_exchange(&path1, &path2, &options);
+        if let Some(callback) = callback {
+            for finding in &findings {
+                report(finding, callback, user_data);
+            }
+        }
+        match findings
+            .into_iter()
+            .find(|finding| finding.severity() == Severity::Error)
+        {
+            Some(finding) => Err(finding.into_error()),
+            None => Ok(()),
+        }
+    })
+}
+
+fn report(finding: &Finding, callback: ExchangeFindingCallback, user_data: *mut c_void) {
+    let (path, path_len) = path_parts(finding.path());
+    // Messages come from paths and OS errors, which hold no NUL bytes.
+    let message = CString::new(finding.error().message()).unwrap_or_default();
+    let finding = ExchangeFinding {
+        severity: match finding.severity() {
+            Severity::Error => EXCHANGE_SEVERITY_ERROR,
+            Severity::Warning => EXCHANGE_SEVERITY_WARNING,
+        },
+        code: finding.error().to_code(),
+        path,
+        path_len,
+        message: message.as_ptr(),
+    };
+    callback(&raw const finding, user_data);
+}
@@ -0,0 +1,574 @@
+use std::{
+    ffi::{c_char, c_void},
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    ptr, slice, str,
+    sync::Arc,
+    time::Duration,
+};
+
+use super::{
+    copy_out, ffi_boundary, invalid, parse_bool, path_from_bytes, raw_path_from_bytes, release,
+    CallbackObserver, ExchangeEventCallback,
+};
+use crate::{
+    plan_exchange, Durability, ExchangeError, ExchangeOptions, History, NoopObserver, Phase,
+    PlannedExchange, PreparedExchange, Recovered, RenameError,
+};
+
+/// [`exchanger_recover`] found a staged exchange and returned it as a prepared plan.
+pub const EXCHANGER_RECOVERED_PREPARED: u8 = 0;
+/// [`exchanger_recover`] found a committed exchange and removed its journal.
+pub const EXCHANGER_RECOVERED_COMMITTED: u8 = 1;
+/// [`exchanger_recover`] found an aborted exchange and removed its journal.
+pub const EXCHANGER_RECOVERED_ABORTED: u8 = 2;
+
+/// [`Durability::None`] for [`exchanger_options_set_durability`].
+pub const EXCHANGE_DURABILITY_NONE: u8 = 0;
+/// [`Durability::Directories`] for [`exchanger_options_set_durability`].
+pub const EXCHANGE_DURABILITY_DIRECTORIES: u8 = 1;
+/// [`Durability::Full`] for [`exchanger_options_set_durability`].
+pub const EXCHANGE_DURABILITY_FULL: u8 = 2;
+
+/// Opaque option set for the handle-based C API.
+#[derive(Debug, Default)]
+pub struct ExchangerOptions {
+    options: ExchangeOptions,
+}
+
+/// Opaque validated exchange for the handle-based C API.
+#[derive(Debug)]
+pub struct ExchangerPlan {
+    steps: Vec<(PathBuf, PathBuf)>,
+    planned: Option<PlannedExchange>,
+    prepared: Option<PreparedExchange>,
+}
+
+/// Creates an option set with default values, or returns null if allocation fails.
+#[no_mangle]
+pub extern "C" fn exchanger_options_new() -> *mut ExchangerOptions {
+    catch_unwind(|| Box::into_raw(Box::default())).unwrap_or(ptr::null_mut())
+}
+
+/// Releases an option set; null is ignored.
+///
+/// # Safety
+///
+/// `options` must be null or a pointer returned by [`exchanger_options_new`] that has not been
+/// freed.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_free(options: *mut ExchangerOptions) {
+    if !options.is_null() {
+        // Dropping runs no callbacks, but a panic must not unwind into C either way.
+        // SAFETY: Required by this function's contract.
+        release(|| drop(unsafe { Box::from_raw(options) }));
+    }
+}
+
+/// Keeps each regular file's extension when `preserve_ext` is `1`.
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by [`exchanger_options_new`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_set_preserve_ext(
+    options: *mut ExchangerOptions,
+    preserve_ext: u8,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        options.preserve_ext = parse_bool(preserve_ext)?;
+        Ok(())
+    })
+}
+
+/// Checks the result of each exchange when `verify` is `1`; see [`ExchangeOptions::verify`].
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by [`exchanger_options_new`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_set_verify(
+    options: *mut ExchangerOptions,
+    verify: u8,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        options.verify = parse_bool(verify)?;
+        Ok(())
+    })
+}
+
+/// Sets how much of each exchange is flushed to storage from an `EXCHANGE_DURABILITY_*` value;
+/// see [`ExchangeOptions::durability`].
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by [`exchanger_options_new`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_set_durability(
+    options: *mut ExchangerOptions,
+    durability: u8,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        options.durability = match durability {
+            EXCHANGE_DURABILITY_NONE => Durability::None,
+            EXCHANGE_DURABILITY_DIRECTORIES => Durability::Directories,
+            EXCHANGE_DURABILITY_FULL => Durability::Full,
+            _ => return Err(invalid("durability must be 0, 1, or 2")),
+        };
+        Ok(())
+    })
+}
+
+/// Limits how long an exchange waits for overlapping exchanges; a negative value waits forever.
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by [`exchanger_options_new`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_set_lock_timeout_ms(
+    options: *mut ExchangerOptions,
+    timeout_ms: i64,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        options.lock_timeout = u64::try_from(timeout_ms).ok().map(Duration::from_millis);
+        Ok(())
+    })
+}
+
+/// Reports each step of exchanges run with these options; a null `callback` removes the observer.
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by [`exchanger_options_new`]. The callback runs
+/// synchronously on the thread executing an exchange and must accept `user_data` there.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_set_observer(
+    options: *mut ExchangerOptions,
+    callback: Option<ExchangeEventCallback>,
+    user_data: *mut c_void,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        options.observer = match callback {
+            Some(callback) => Arc::new(CallbackObserver {
+                callback,
+                user_data,
+            }),
+            None => Arc::new(NoopObserver),
+        };
+        Ok(())
+    })
+}
+
+/// Resolves relative paths against an absolute base directory instead of the working directory.
+///
+/// The path is passed like in [`exchange_raw_n`](super::exchange_raw_n); a null `path` restores
+/// the working directory.
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by [`exchanger_options_new`]. `path` must be null
+/// or readable for `path_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_set_base_dir(
+    options: *mut ExchangerOptions,
+    path: *const u8,
+    path_len: usize,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        options.base_dir = if path.is_null() {
+            None
+        } else {
+            // SAFETY: Required by this function's contract.
+            let path = unsafe { raw_path_from_bytes(path, path_len) }?;
+            if !path.is_absolute() {
+                return Err(invalid("base directory must be absolute"));
+            }
+            Some(path)
+        };
+        Ok(())
+    })
+}
+
+/// Records completed exchanges in the history file at `path` so retries can be recognized.
+///
+/// The path is passed like in [`exchange_raw_n`](super::exchange_raw_n); a null `path` stops
+/// recording.
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by [`exchanger_options_new`]. `path` must be null
+/// or readable for `path_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_set_history(
+    options: *mut ExchangerOptions,
+    path: *const u8,
+    path_len: usize,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        options.history = if path.is_null() {
+            None
+        } else {
+            // SAFETY: Required by this function's contract.
+            Some(History::new(unsafe {
+                raw_path_from_bytes(path, path_len)
+            }?))
+        };
+        Ok(())
+    })
+}
+
+/// Makes repeating an exchange with the same non-empty UTF-8 `operation_id` a no-op.
+///
+/// Requires a history; see [`ExchangeOptions::operation_id`]. A null `operation_id` clears it.
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by [`exchanger_options_new`]. `operation_id` must
+/// be null or readable for `operation_id_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_set_operation_id(
+    options: *mut ExchangerOptions,
+    operation_id: *const u8,
+    operation_id_len: usize,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        options.operation_id = if operation_id.is_null() {
+            None
+        } else {
+            // SAFETY: Required by this function's contract.
+            let bytes = unsafe { slice::from_raw_parts(operation_id, operation_id_len) };
+            let operation_id =
+                str::from_utf8(bytes).map_err(|_| invalid("operation ID is not UTF-8"))?;
+            if operation_id.is_empty() {
+                return Err(ExchangeError::new(
+                    RenameError::InvalidOperationId("must not be empty".to_owned()),
+                    Phase::Resolve,
+                ));
+            }
+            Some(operation_id.to_owned())
+        };
+        Ok(())
+    })
+}
+
+/// Validates an exchange of two UTF-8 paths and stores the result in `*out_plan`.
+///
+/// On failure `*out_plan` is set to null.
+///
+/// # Safety
+///
+/// `options` must be null, for default options, or a live pointer from
+/// [`exchanger_options_new`]. Path requirements match [`exchange_n`](super::exchange_n).
+/// `out_plan` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan(
+    options: *const ExchangerOptions,
+    path1: *const u8,
+    path1_len: usize,
+    path2: *const u8,
+    path2_len: usize,
+    out_plan: *mut *mut ExchangerPlan,
+) -> i32 {
+    ffi_boundary(|| {
+        if out_plan.is_null() {
+            return Err(invalid("null plan output pointer"));
+        }
+        // SAFETY: Required by this function's contract.
+        unsafe { out_plan.write(ptr::null_mut()) };
+        // SAFETY: Required by this function's contract.
+        let path1 = unsafe { path_from_bytes(path1, path1_len) }?;
+        // SAFETY: Required by this function's contract.
+        let path2 = unsafe { path_from_bytes(path2, path2_len) }?;
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_or_default(options) };
+
+        let planned = plan_exchange(&path1, &path2, &options)?;
+        let plan = Box::new(ExchangerPlan {
+            steps: owned_steps(planned.steps()),
+            planned: Some(planned),
+            prepared: None,
+        });
+        // SAFETY: Required by this function's contract.
+        unsafe { out_plan.write(Box::into_raw(plan)) };
+        Ok(())
+    })
+}
+
+/// Returns the number of renames in a plan, or `0` for a null plan.
+///
+/// # Safety
+///
+/// `plan` must be null or a live pointer from [`exchanger_plan`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_step_count(plan: *const ExchangerPlan) -> usize {
+    // SAFETY: Required by this function's contract.
+    unsafe { plan.as_ref() }.map_or(0, |plan| plan.steps.len())
+}
+
+/// Copies the current path of step `index` into `buffer`, like
+/// [`exchange_last_error_message`](super::exchange_last_error_message).
+///
+/// Returns the full path length in bytes, or `0` for a null plan or an out-of-range index.
+///
+/// # Safety
+///
+/// `plan` must be null or a live pointer from [`exchanger_plan`]. `buffer` must be writable for
+/// `buffer_len` bytes, and may be null only when `buffer_len` is `0`.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_source(
+    plan: *const ExchangerPlan,
+    index: usize,
+    buffer: *mut c_char,
+    buffer_len: usize,
+) -> usize {
+    // SAFETY: Required by this function's contract.
+    unsafe { copy_step_path(plan, index, buffer, buffer_len, |(source, _)| source) }
+}
+
+/// Copies the new path of step `index` into `buffer`; see [`exchanger_plan_source`].
+///
+/// # Safety
+///
+/// Requirements match [`exchanger_plan_source`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_target(
+    plan: *const ExchangerPlan,
+    index: usize,
+    buffer: *mut c_char,
+    buffer_len: usize,
+) -> usize {
+    // SAFETY: Required by this function's contract.
+    unsafe { copy_step_path(plan, index, buffer, buffer_len, |(_, target)| target) }
+}
+
+/// Performs a planned exchange. A plan can be executed once; later calls fail with code `5`.
+///
+/// # Safety
+///
+/// `plan` must be a live pointer from [`exchanger_plan`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_execute(plan: *mut ExchangerPlan) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let plan = unsafe { plan.as_mut() }.ok_or_else(|| invalid("null plan handle"))?;
+        plan.planned
+            .take()
+            .ok_or_else(|| invalid("plan has already been executed"))?
+            .execute()
+    })
+}
+
+/// Validates a plan again and moves its second entry into a temporary directory beside it.
+///
+/// The exchange then waits for [`exchanger_plan_commit`] or [`exchanger_plan_abort`]; freeing
+/// the plan first aborts it. A plan can be prepared or executed once; later calls fail with
+/// code `5`.
+///
+/// # Safety
+///
+/// `plan` must be a live pointer from [`exchanger_plan`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_prepare(plan: *mut ExchangerPlan) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let plan = unsafe { plan.as_mut() }.ok_or_else(|| invalid("null plan handle"))?;
+        let planned = plan
+            .planned
+            .take()
+            .ok_or_else(|| invalid("plan has already been executed"))?;
+        plan.prepared = Some(planned.prepare()?);
+        Ok(())
+    })
+}
+
+/// Finishes a prepared exchange. Fails with code `5` unless the plan is prepared.
+///
+/// # Safety
+///
+/// `plan` must be a live pointer from [`exchanger_plan`] or [`exchanger_recover`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_commit(plan: *mut ExchangerPlan) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        unsafe { take_prepared(plan) }?.commit()
+    })
+}
+
+/// Gives the entries of a prepared exchange their original names back. Fails with code `5`
+/// unless the plan is prepared.
+///
+/// # Safety
+///
+/// `plan` must be a live pointer from [`exchanger_plan`] or [`exchanger_recover`].
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_abort(plan: *mut ExchangerPlan) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        unsafe { take_prepared(plan) }?.abort()
+    })
+}
+
+/// Copies the journal directory of a prepared exchange into `buffer`, like
+/// [`exchange_last_error_message`](super::exchange_last_error_message).
+///
+/// Pass the directory to [`exchanger_recover`] after a crash. Returns the full path length in
+/// bytes, or `0` when the plan is null or has nothing staged.
+///
+/// # Safety
+///
+/// `plan` must be null or a live pointer from [`exchanger_plan`] or [`exchanger_recover`].
+/// `buffer` must be writable for `buffer_len` bytes, and may be null only when `buffer_len` is
+/// `0`.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_journal_dir(
+    plan: *const ExchangerPlan,
+    buffer: *mut c_char,
+    buffer_len: usize,
+) -> usize {
+    catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: Required by this function's contract.
+        let Some(dir) = unsafe { plan.as_ref() }
+            .and_then(|plan| plan.prepared.as_ref())
+            .and_then(PreparedExchange::journal_dir)
+        else {
+            return 0;
+        };
+        // SAFETY: Required by this function's contract.
+        unsafe { copy_out(dir.as_os_str().as_encoded_bytes(), buffer, buffer_len) }
+    }))
+    .unwrap_or(0)
+}
+
+/// Resumes the exchange journaled in `dir` by a process that died before committing or
+/// aborting it; see [`PreparedExchange::recover`].
+///
+/// `*out_state` receives an `EXCHANGER_RECOVERED_*` value. For
+/// [`EXCHANGER_RECOVERED_PREPARED`], `*out_plan` receives a prepared plan to commit or abort;
+/// otherwise, and on failure, it is set to null.
+///
+/// # Safety
+///
+/// `options` must be null, for default options, or a live pointer from
+/// [`exchanger_options_new`]. `dir` is passed like in [`exchange_raw_n`](super::exchange_raw_n).
+/// `out_plan` and `out_state` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_recover(
+    options: *const ExchangerOptions,
+    dir: *const u8,
+    dir_len: usize,
+    out_plan: *mut *mut ExchangerPlan,
+    out_state: *mut u8,
+) -> i32 {
+    ffi_boundary(|| {
+        if out_plan.is_null() || out_state.is_null() {
+            return Err(invalid("null recovery output pointer"));
+        }
+        // SAFETY: Required by this function's contract.
+        unsafe { out_plan.write(ptr::null_mut()) };
+        // SAFETY: Required by this function's contract.
+        let dir = unsafe { raw_path_from_bytes(dir, dir_len) }?;
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_or_default(options) };
+
+        let state = match PreparedExchange::recover(&dir, &options)? {
+            Recovered::Prepared(prepared) => {
+                let plan = Box::new(ExchangerPlan {
+                    steps: owned_steps(prepared.steps()),
+                    planned: None,
+                    prepared: Some(*prepared),
+                });
+                // SAFETY: Required by this function's contract.
+                unsafe { out_plan.write(Box::into_raw(plan)) };
+                EXCHANGER_RECOVERED_PREPARED
+            }
+            Recovered::Committed => EXCHANGER_RECOVERED_COMMITTED,
+            Recovered::Aborted => EXCHANGER_RECOVERED_ABORTED,
+        };
+        // SAFETY: Required by this function's contract.
+        unsafe { out_state.write(state) };
+        Ok(())
+    })
+}
+
+/// Releases a plan; null is ignored.
+///
+/// # Safety
+///
+/// `plan` must be null or a pointer from [`exchanger_plan`] or [`exchanger_recover`] that has
+/// not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_plan_free(plan: *mut ExchangerPlan) {
+    if !plan.is_null() {
+        // Dropping a prepared plan aborts it, which renames entries and calls the observer.
+        // SAFETY: Required by this function's contract.
+        release(|| drop(unsafe { Box::from_raw(plan) }));
+    }
+}
+
+/// Copies the options behind a nullable handle, using defaults for null.
+pub(super) unsafe fn options_or_default(options: *const ExchangerOptions) -> ExchangeOptions {
+    // SAFETY: Required by the caller of this function.
+    unsafe { options.as_ref() }
+        .map_or_else(ExchangeOptions::default, |options| options.options.clone())
+}
+
+pub(super) unsafe fn options_mut<'a>(
+    options: *mut ExchangerOptions,
+) -> Result<&'a mut ExchangeOptions, crate::ExchangeError> {
+    // SAFETY: Required by the caller of this function.
+    unsafe { options.as_mut() }
+        .map(|options| &mut options.options)
+        .ok_or_else(|| invalid("null options handle"))
+}
+
+unsafe fn take_prepared(
+    plan: *mut ExchangerPlan,
+) -> Result<PreparedExchange, crate::ExchangeError> {
+    // SAFETY: Required by the caller of this function.
+    unsafe { plan.as_mut() }
+        .ok_or_else(|| invalid("null plan handle"))?
+        .prepared
+        .take()
+        .ok_or_else(|| invalid("plan is not prepared"))
+}
+
+fn owned_steps<'a>(steps: impl Iterator<Item = (&'a Path, &'a Path)>) -> Vec<(PathBuf, PathBuf)> {
+    steps
+        .map(|(source, target)| (source.to_path_buf(), target.to_path_buf()))
+        .collect()
+}
+
+unsafe fn copy_step_path(
+    plan: *const ExchangerPlan,
+    index: usize,
+    buffer: *mut c_char,
+    buffer_len: usize,
+    select: impl FnOnce(&(PathBuf, PathBuf)) -> &PathBuf,
+) -> usize {
+    catch_unwind(AssertUnwindSafe(|| {
+        // SAFETY: Required by the caller of this function.
+        let Some(step) = unsafe { plan.as_ref() }.and_then(|plan| plan.steps.get(index)) else {
+            return 0;
+        };
+        let path = select(step).as_os_str().as_encoded_bytes();
+        // SAFETY: Required by the caller of this function.
+        unsafe { copy_out(path, buffer, buffer_len) }
+    }))
+    .unwrap_or(0)
+}
@@ -0,0 +1,86 @@
+// Bit 0 is reserved and never set.
+
+/// `exchange_batch_n` and `exchange_rotate_n` are available.
+pub const EXCHANGE_CAP_BATCH: u64 = 1 << 1;
+/// The `exchanger_options_*` and `exchanger_plan_*` handles are available.
+pub const EXCHANGE_CAP_HANDLES: u64 = 1 << 2;
+/// `exchange_raw` and `exchange_raw_n` accept paths that are not UTF-8.
+pub const EXCHANGE_CAP_RAW_PATHS: u64 = 1 << 3;
+/// Built with the `tokio` feature.
+pub const EXCHANGE_CAP_ASYNC: u64 = 1 << 4;
+/// Built with the `tracing` feature.
+pub const EXCHANGE_CAP_TRACING: u64 = 1 << 5;
+/// `exchange_at` can exchange names relative to directory descriptors.
+pub const EXCHANGE_CAP_DIRECTORY_HANDLES: u64 = 1 << 6;
+/// `exchanger_plan_prepare`, `exchanger_plan_commit`, `exchanger_plan_abort`, and
+/// `exchanger_recover` are available.
+pub const EXCHANGE_CAP_TWO_PHASE: u64 = 1 << 7;
+/// Entries on different file systems can be exchanged, since each is renamed within its own
+/// directory.
+pub const EXCHANGE_CAP_CROSS_DEVICE: u64 = 1 << 8;
+/// `exchanger_options_set_durability` is available.
+pub const EXCHANGE_CAP_DURABILITY: u64 = 1 << 9;
+/// Exchanges can be verified with `exchanger_options_set_verify`.
+pub const EXCHANGE_CAP_VERIFY: u64 = 1 << 10;
+/// `exchanger_options_expect` is available.
+pub const EXCHANGE_CAP_PRECONDITIONS: u64 = 1 << 11;
+/// `exchanger_diagnose` is available.
+pub const EXCHANGE_CAP_DIAGNOSTICS: u64 = 1 << 12;
+/// Exchanges can be recorded with `exchanger_options_set_history` and given operation IDs.
+pub const EXCHANGE_CAP_HISTORY: u64 = 1 << 13;
+
+/// Version of the loaded library.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExchangeLibVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+/// Returns the version of the loaded library.
+#[no_mangle]
+pub extern "C" fn exchange_lib_version() -> ExchangeLibVersion {
+    ExchangeLibVersion {
+        major: parse_version(env!("CARGO_PKG_VERSION_MAJOR")),
+        minor: parse_version(env!("CARGO_PKG_VERSION_MINOR")),
+        patch: parse_version(env!("CARGO_PKG_VERSION_PATCH")),
+    }
+}
+
+/// Returns the `EXCHANGE_CAP_*` bits supported by this build on the running system.
+#[no_mangle]
+pub extern "C" fn exchange_lib_capabilities() -> u64 {
+    let mut capabilities = EXCHANGE_CAP_BATCH
+        | EXCHANGE_CAP_HANDLES
+        | EXCHANGE_CAP_TWO_PHASE
+        | EXCHANGE_CAP_CROSS_DEVICE
+        | EXCHANGE_CAP_DURABILITY
+        | EXCHANGE_CAP_PRECONDITIONS
+        | EXCHANGE_CAP_DIAGNOSTICS;
+    if cfg!(unix) {
+        // Verification and history compare device and inode numbers.
+        capabilities |= EXCHANGE_CAP_RAW_PATHS
+            | EXCHANGE_CAP_VERIFY
+            | EXCHANGE_CAP_HISTORY
+            | EXCHANGE_CAP_DIRECTORY_HANDLES;
+    }
+    if cfg!(feature = "tokio") {
+        capabilities |= EXCHANGE_CAP_ASYNC;
+    }
+    if cfg!(feature = "tracing") {
+        capabilities |= EXCHANGE_CAP_TRACING;
+    }
+    capabilities
+}
+
+const fn parse_version(value: &str) -> u32 {
+    let bytes = value.as_bytes();
+    let mut result = 0_u32;
+    let mut index = 0;
+    while index < bytes.len() {
+        result = result * 10 + (bytes[index] - b'0') as u32;
+        index += 1;
+    }
+    result
+}
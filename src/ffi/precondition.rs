@@ -0,0 +1,112 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use super::{ffi_boundary, handles::options_mut, invalid, raw_path_from_bytes, ExchangerOptions};
+use crate::{ExchangeError, Precondition};
+
+/// [`ExchangePrecondition::dev`] and [`ExchangePrecondition::ino`] are set.
+pub const EXCHANGE_EXPECT_IDENTITY: u32 = 1 << 0;
+/// [`ExchangePrecondition::size`] is set.
+pub const EXCHANGE_EXPECT_SIZE: u32 = 1 << 1;
+/// [`ExchangePrecondition::modified_sec`] and [`ExchangePrecondition::modified_nsec`] are set.
+pub const EXCHANGE_EXPECT_MODIFIED: u32 = 1 << 2;
+/// [`ExchangePrecondition::sha256`] is set.
+pub const EXCHANGE_EXPECT_SHA256: u32 = 1 << 3;
+
+const KNOWN_FIELDS: u32 = EXCHANGE_EXPECT_IDENTITY
+    | EXCHANGE_EXPECT_SIZE
+    | EXCHANGE_EXPECT_MODIFIED
+    | EXCHANGE_EXPECT_SHA256;
+
+/// Expected state of one entry for [`exchanger_options_expect`]; see [`Precondition`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExchangePrecondition {
+    /// `EXCHANGE_EXPECT_*` bits naming the fields to check.
+    pub fields: u32,
+    pub dev: u64,
+    pub ino: u64,
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch, negative before it.
+    pub modified_sec: i64,
+    /// Nanoseconds added to `modified_sec`, below 1 000 000 000.
+    pub modified_nsec: u32,
+    pub sha256: [u8; 32],
+}
+
+impl TryFrom<&ExchangePrecondition> for Precondition {
+    type Error = ExchangeError;
+
+    fn try_from(value: &ExchangePrecondition) -> Result<Self, Self::Error> {
+        if value.fields & !KNOWN_FIELDS != 0 {
+            return Err(invalid("unknown precondition fields"));
+        }
+        let mut precondition = Self::new();
+        if value.fields & EXCHANGE_EXPECT_IDENTITY != 0 {
+            precondition = precondition.identity(value.dev, value.ino);
+        }
+        if value.fields & EXCHANGE_EXPECT_SIZE != 0 {
+            precondition = precondition.size(value.size);
+        }
+        if value.fields & EXCHANGE_EXPECT_MODIFIED != 0 {
+            precondition =
+                precondition.modified(modified(value.modified_sec, value.modified_nsec)?);
+        }
+        if value.fields & EXCHANGE_EXPECT_SHA256 != 0 {
+            precondition = precondition.sha256(value.sha256);
+        }
+        Ok(precondition)
+    }
+}
+
+/// Exchanges the entry at `path` only while it still matches `*precondition`; see
+/// [`ExchangeOptions::expect`](crate::ExchangeOptions::expect).
+///
+/// The path is passed like in [`exchange_raw_n`](super::exchange_raw_n). A null `path` removes
+/// every precondition and ignores `precondition`.
+///
+/// # Safety
+///
+/// `options` must be a live pointer returned by
+/// [`exchanger_options_new`](super::exchanger_options_new). `path` must be null or readable for
+/// `path_len` bytes, and `precondition` must be null or readable.
+#[no_mangle]
+pub unsafe extern "C" fn exchanger_options_expect(
+    options: *mut ExchangerOptions,
+    path: *const u8,
+    path_len: usize,
+    precondition: *const ExchangePrecondition,
+) -> i32 {
+    ffi_boundary(|| {
+        // SAFETY: Required by this function's contract.
+        let options = unsafe { options_mut(options) }?;
+        if path.is_null() {
+            options.preconditions.clear();
+            return Ok(());
+        }
+        // SAFETY: Required by this function's contract.
+        let path: PathBuf = unsafe { raw_path_from_bytes(path, path_len) }?;
+        // SAFETY: Required by this function's contract.
+        let precondition = unsafe { precondition.as_ref() }
+            .ok_or_else(|| invalid("null precondition"))?
+            .try_into()?;
+        options.preconditions.push((path, precondition));
+        Ok(())
+    })
+}
+
+fn modified(seconds: i64, nanoseconds: u32) -> Result<SystemTime, ExchangeError> {
+    if nanoseconds >= 1_000_000_000 {
+        return Err(invalid("modification time nanoseconds out of range"));
+    }
+    let time = if seconds >= 0 {
+        SystemTime::UNIX_EPOCH.checked_add(Duration::new(seconds.unsigned_abs(), nanoseconds))
+    } else {
+        SystemTime::UNIX_EPOCH
+            .checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+            .and_then(|time| time.checked_add(Duration::from_nanos(nanoseconds.into())))
+    };
+    time.ok_or_else(|| invalid("modification time out of range"))
+}
@@ -0,0 +1,540 @@
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Read as _, Write as _},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    encoding::{decode_field, encode_field, path_bytes, path_from_bytes},
+    identity::Identity,
+    plan::{ExchangePlan, RenameStep, ResolvedPair},
+    transaction, ExchangeError, ExchangeObserver, ExchangeOptions, Phase, RenameError,
+};
+
+/// Append-only log of attempted exchanges, from which completed ones can be undone.
+///
+/// Each line records one exchange, that one was undone, or that it failed and never ran. Failed
+/// exchanges stay in the file, marked aborted, but are not listed.
+/// Exchanges are recorded before their first rename, so a recording failure renames nothing.
+/// Processes sharing the file serialize their appends with an advisory lock, and each append is
+/// flushed to storage. Recording reads device and inode numbers, so it only succeeds on Unix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    path: PathBuf,
+}
+
+/// A recorded exchange.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    id: u64,
+    timestamp: SystemTime,
+    preserve_ext: bool,
+    first: Moved,
+    second: Moved,
+    operation_id: Option<String>,
+    undone: bool,
+    /// Recorded ahead of an exchange that then failed; hidden from callers.
+    aborted: bool,
+}
+
+/// One renamed entry and the identity it had under its new name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Moved {
+    identity: Identity,
+    source: PathBuf,
+    target: PathBuf,
+}
+
+impl History {
+    /// Uses the history file at `path`, which is created by the first record.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Returns `exchange-name/history` under `$XDG_STATE_HOME`, or under `~/.local/state` when
+    /// that is unset, or `None` when neither variable holds an absolute path.
+    #[must_use]
+    pub fn default_path() -> Option<PathBuf> {
+        let absolute = |variable| {
+            env::var_os(variable)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+        };
+        let state = absolute("XDG_STATE_HOME")
+            .or_else(|| absolute("HOME").map(|home| home.join(".local").join("state")))?;
+        Some(state.join("exchange-name").join("history"))
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns every recorded exchange, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExchangeError`] with [`Phase::History`] when the file cannot be read or parsed.
+    pub fn entries(&self) -> Result<Vec<HistoryEntry>, ExchangeError> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(self.io_error(error)),
+        };
+        file.lock_shared().map_err(|error| self.io_error(error))?;
+        let mut entries = self.read(&mut file)?;
+        entries.retain(|entry| !entry.aborted);
+        Ok(entries)
+    }
+
+    /// Undoes the most recent exchange that has not been undone yet.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`undo`](Self::undo), or with [`RenameError::InvalidPath`] when every recorded
+    /// exchange has been undone.
+    pub fn undo_last(&self, options: &ExchangeOptions) -> Result<HistoryEntry, ExchangeError> {
+        let entry = self
+            .entries()?
+            .into_iter()
+            .rev()
+            .find(|entry| !entry.undone)
+            .ok_or_else(|| self.invalid("no exchange left to undo".to_owned()))?;
+        self.undo_entry(entry, options)
+    }
+
+    /// Gives both entries of exchange `id` their original names back.
+    ///
+    /// The entries must still be the same files, by device and inode, at the names the exchange
+    /// gave them. `options` supply the lock timeout and observer; names are computed as the
+    /// exchange computed them, and undoing is not recorded as a new exchange. Once the entries
+    /// are renamed, failing to mark the exchange as undone is only reported to the observer's
+    /// [`history_failed`](ExchangeObserver::history_failed).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RenameError::InvalidPath`] when `id` is unknown or already undone,
+    /// [`RenameError::Changed`] when an entry was renamed or replaced since, and otherwise fails
+    /// like [`exchange_with`](crate::exchange_with).
+    pub fn undo(&self, id: u64, options: &ExchangeOptions) -> Result<HistoryEntry, ExchangeError> {
+        let entry = self
+            .entries()?
+            .into_iter()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| self.invalid(format!("no exchange {id} in the history")))?;
+        if entry.undone {
+            return Err(self.invalid(format!("exchange {id} was already undone")));
+        }
+        self.undo_entry(entry, options)
+    }
+
+    fn undo_entry(
+        &self,
+        mut entry: HistoryEntry,
+        options: &ExchangeOptions,
+    ) -> Result<HistoryEntry, ExchangeError> {
+        let inverse = entry.plan().inverse();
+        let pair = inverse.pair();
+        let _lock = pair.lock(options)?;
+        entry.first.verify()?;
+        entry.second.verify()?;
+        let plan = inverse.revalidate(pair, &options.clone().preserve_ext(entry.preserve_ext))?;
+        transaction::execute(
+            &plan,
+            options.observer.as_ref(),
+            None,
+            options.verify,
+            options.durability,
+        )?;
+
+        self.mark("undo", [entry.id], options.observer.as_ref());
+        entry.undone = true;
+        Ok(entry)
+    }
+
+    /// Appends one `kind` line for each exchange in `ids` after its renames ran or were undone,
+    /// when a failure can no longer change the outcome, so it is only reported to `observer`.
+    fn mark(
+        &self,
+        kind: &str,
+        ids: impl IntoIterator<Item = u64>,
+        observer: &dyn ExchangeObserver,
+    ) {
+        let lines = marks(kind, ids);
+        if lines.is_empty() {
+            return;
+        }
+        if let Err(error) = self.append(|_| lines) {
+            observer.history_failed(&self.path, &error);
+        }
+    }
+
+    /// Appends the bytes `lines` produces from the next free exchange id, which it returns.
+    fn append(&self, lines: impl FnOnce(u64) -> Vec<u8>) -> Result<u64, ExchangeError> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|error| ExchangeError::io(Phase::History, parent, error))?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .map_err(|error| self.io_error(error))?;
+        file.lock().map_err(|error| self.io_error(error))?;
+        let mut contents = self.contents(&mut file)?;
+        // An interrupted append leaves a partial line that the next line would be joined to.
+        let complete = contents
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |end| end + 1);
+        if complete < contents.len() {
+            contents.truncate(complete);
+            file.set_len(complete as u64)
+                .map_err(|error| self.io_error(error))?;
+        }
+        let next_id = self
+            .parse(&contents)?
+            .last()
+            .map_or(1, |entry| entry.id + 1);
+        file.write_all(&lines(next_id))
+            .and_then(|()| file.sync_data())
+            .map_err(|error| self.io_error(error))?;
+        Ok(next_id)
+    }
+
+    fn read(&self, file: &mut File) -> Result<Vec<HistoryEntry>, ExchangeError> {
+        let contents = self.contents(file)?;
+        self.parse(&contents)
+    }
+
+    fn contents(&self, file: &mut File) -> Result<Vec<u8>, ExchangeError> {
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)
+            .map_err(|error| self.io_error(error))?;
+        Ok(contents)
+    }
+
+    fn parse(&self, contents: &[u8]) -> Result<Vec<HistoryEntry>, ExchangeError> {
+        parse(contents).map_err(|line| {
+            ExchangeError::new(
+                RenameError::Unknown(format!("malformed history line {line}")),
+                Phase::History,
+            )
+            .with_path(&self.path)
+        })
+    }
+
+    fn io_error(&self, error: io::Error) -> ExchangeError {
+        ExchangeError::io(Phase::History, &self.path, error)
+    }
+
+    fn invalid(&self, message: String) -> ExchangeError {
+        ExchangeError::new(RenameError::InvalidPath(message), Phase::History).with_path(&self.path)
+    }
+}
+
+impl HistoryEntry {
+    #[must_use]
+    pub const fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns when the exchange was recorded, just before its first rename, to the second.
+    #[must_use]
+    pub const fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// Returns each entry's original path and the path the exchange gave it.
+    #[must_use]
+    pub fn steps(&self) -> impl ExactSizeIterator<Item = (&Path, &Path)> {
+        [&self.first, &self.second]
+            .into_iter()
+            .map(|moved| (moved.source.as_path(), moved.target.as_path()))
+    }
+
+    /// Returns the caller's operation ID, if the exchange was given one.
+    #[must_use]
+    pub fn operation_id(&self) -> Option<&str> {
+        self.operation_id.as_deref()
+    }
+
+    #[must_use]
+    pub const fn is_undone(&self) -> bool {
+        self.undone
+    }
+
+    fn plan(&self) -> ExchangePlan {
+        ExchangePlan {
+            first: self.first.step(),
+            second: self.second.step(),
+        }
+    }
+
+    fn encode(&self, id: u64, out: &mut Vec<u8>) {
+        out.extend_from_slice(
+            format!(
+                "exchange\t{id}\t{}\t{}",
+                seconds(self.timestamp),
+                u8::from(self.preserve_ext)
+            )
+            .as_bytes(),
+        );
+        for moved in [&self.first, &self.second] {
+            let Identity { dev, ino } = moved.identity;
+            out.extend_from_slice(format!("\t{dev}\t{ino}\t").as_bytes());
+            encode_field(&path_bytes(&moved.source), out);
+            out.push(b'\t');
+            encode_field(&path_bytes(&moved.target), out);
+        }
+        if let Some(operation_id) = &self.operation_id {
+            out.push(b'\t');
+            encode_field(operation_id.as_bytes(), out);
+        }
+        out.push(b'\n');
+    }
+}
+
+impl Moved {
+    /// Reads the identity of a planned step's entry, which renaming it does not change.
+    fn planned(step: &RenameStep) -> Result<Self, ExchangeError> {
+        Ok(Self {
+            identity: Identity::of(&step.source)
+                .map_err(|error| ExchangeError::io(Phase::History, &step.source, error))?,
+            source: step.source.clone(),
+            target: step.target.clone(),
+        })
+    }
+
+    fn step(&self) -> RenameStep {
+        RenameStep {
+            source: self.source.clone(),
+            target: self.target.clone(),
+            expected: None,
+        }
+    }
+
+    /// Checks that the entry at the recorded new name is still the renamed one.
+    fn verify(&self) -> Result<(), ExchangeError> {
+        match Identity::of(&self.target) {
+            Ok(identity) if identity == self.identity => Ok(()),
+            Err(error) if error.kind() != io::ErrorKind::NotFound => {
+                Err(ExchangeError::io(Phase::Inspect, &self.target, error))
+            }
+            _ => Err(ExchangeError::new(RenameError::Changed, Phase::Plan).with_path(&self.target)),
+        }
+    }
+
+    /// Returns whether the entry still has its original name, as when the exchange never ran.
+    fn unmoved(&self) -> bool {
+        Identity::of(&self.source).is_ok_and(|identity| identity == self.identity)
+    }
+}
+
+/// Exchanges recorded in a history before they run.
+#[derive(Debug, Default)]
+pub(crate) struct Pending {
+    history: Option<History>,
+    ids: Vec<u64>,
+}
+
+impl Pending {
+    /// Marks the recorded exchanges as never run, after they failed and were undone.
+    ///
+    /// A failure is only reported to `observer`, as the exchange has already failed.
+    pub(crate) fn abort(self, observer: &dyn ExchangeObserver) {
+        if let Some(history) = &self.history {
+            history.mark("abort", self.ids, observer);
+        }
+    }
+
+    /// Combines the records of several exchanges so that they are aborted together.
+    pub(crate) fn merge(all: impl IntoIterator<Item = Self>) -> Self {
+        all.into_iter()
+            .fold(Self::default(), |mut merged, pending| {
+                merged.history = merged.history.or(pending.history);
+                merged.ids.extend(pending.ids);
+                merged
+            })
+    }
+}
+
+/// Records `plan` in the history of `options`, if they keep one, before anything is renamed.
+///
+/// Callers abort the returned record if executing the plan fails.
+pub(crate) fn begin(
+    options: &ExchangeOptions,
+    plan: &ExchangePlan,
+) -> Result<Pending, ExchangeError> {
+    let Some(history) = &options.history else {
+        return Ok(Pending::default());
+    };
+    let entry = HistoryEntry {
+        id: 0,
+        timestamp: SystemTime::now(),
+        preserve_ext: options.preserve_ext,
+        first: Moved::planned(&plan.first)?,
+        second: Moved::planned(&plan.second)?,
+        operation_id: options.operation_id.clone(),
+        undone: false,
+        aborted: false,
+    };
+    let id = history.append(|id| {
+        let mut line = Vec::new();
+        entry.encode(id, &mut line);
+        line
+    })?;
+    Ok(Pending {
+        history: Some(history.clone()),
+        ids: vec![id],
+    })
+}
+
+/// Runs `execute` on `plan`, recorded ahead in the history of `options`.
+pub(crate) fn recorded(
+    options: &ExchangeOptions,
+    plan: &ExchangePlan,
+    execute: impl FnOnce() -> Result<(), ExchangeError>,
+) -> Result<(), ExchangeError> {
+    let pending = begin(options, plan)?;
+    execute().inspect_err(|_| pending.abort(options.observer.as_ref()))
+}
+
+/// Returns whether the operation ID of `options` already completed for the same `pairs`.
+///
+/// The entries of a completed operation must still be at the names it gave them. Callers hold
+/// the path lock of every pair.
+pub(crate) fn completed<'a>(
+    options: &ExchangeOptions,
+    pairs: impl IntoIterator<Item = &'a ResolvedPair>,
+) -> Result<bool, ExchangeError> {
+    let Some(operation_id) = &options.operation_id else {
+        return Ok(false);
+    };
+    let invalid = |message: String| {
+        ExchangeError::new(RenameError::InvalidOperationId(message), Phase::History)
+    };
+    if operation_id.is_empty() {
+        return Err(invalid("must not be empty".to_owned()));
+    }
+    let history = options
+        .history
+        .as_ref()
+        .ok_or_else(|| invalid("requires a history".to_owned()))?;
+    let entries = history
+        .entries()?
+        .into_iter()
+        .filter(|entry| !entry.undone && entry.operation_id.as_ref() == Some(operation_id))
+        .collect::<Vec<_>>();
+    // Exchanges recorded by a process that died before renaming anything never ran.
+    if entries
+        .iter()
+        .all(|entry| entry.first.unmoved() && entry.second.unmoved())
+    {
+        if !entries.is_empty() {
+            history.append(|_| marks("abort", entries.iter().map(|entry| entry.id)))?;
+        }
+        return Ok(false);
+    }
+    let recorded = entries
+        .iter()
+        .map(|entry| (&entry.first.source, &entry.second.source));
+    if !recorded.eq(pairs.into_iter().map(|pair| (&pair.first, &pair.second))) {
+        return Err(
+            invalid(format!("'{operation_id}' was used for other paths")).with_path(history.path()),
+        );
+    }
+    // Later pairs of a batch may move entries again; only the last entry given a name stays there.
+    let mut expected = Vec::<&Moved>::new();
+    for moved in entries
+        .iter()
+        .flat_map(|entry| [&entry.first, &entry.second])
+    {
+        expected.retain(|earlier| earlier.target != moved.target);
+        expected.push(moved);
+    }
+    for moved in expected {
+        moved.verify()?;
+    }
+    Ok(true)
+}
+
+/// Returns one `kind` line for each exchange in `ids`.
+fn marks(kind: &str, ids: impl IntoIterator<Item = u64>) -> Vec<u8> {
+    let timestamp = seconds(SystemTime::now());
+    let mut lines = Vec::new();
+    for id in ids {
+        lines.extend_from_slice(format!("{kind}\t{id}\t{timestamp}\n").as_bytes());
+    }
+    lines
+}
+
+/// Parses the history, returning the number of the first malformed line on failure.
+fn parse(contents: &[u8]) -> Result<Vec<HistoryEntry>, usize> {
+    let mut entries = Vec::new();
+    let mut lines = contents.split(|&byte| byte == b'\n').collect::<Vec<_>>();
+    // Text after the last newline is empty, or an append that was interrupted.
+    lines.pop();
+    for (index, line) in lines.into_iter().enumerate() {
+        parse_line(line, &mut entries).ok_or(index + 1)?;
+    }
+    Ok(entries)
+}
+
+fn parse_line(line: &[u8], entries: &mut Vec<HistoryEntry>) -> Option<()> {
+    let fields = line.split(|&byte| byte == b'\t').collect::<Vec<_>>();
+    match fields.as_slice() {
+        [b"exchange", id, timestamp, preserve_ext, rest @ ..]
+            if rest.len() == 8 || rest.len() == 9 =>
+        {
+            let moved = |dev, ino, source, target| {
+                Some(Moved {
+                    identity: Identity {
+                        dev: number(dev)?,
+                        ino: number(ino)?,
+                    },
+                    source: path_from_bytes(decode_field(source)?),
+                    target: path_from_bytes(decode_field(target)?),
+                })
+            };
+            entries.push(HistoryEntry {
+                id: number(id)?,
+                timestamp: UNIX_EPOCH + Duration::from_secs(number(timestamp)?),
+                preserve_ext: number::<u8>(preserve_ext)? != 0,
+                first: moved(rest[0], rest[1], rest[2], rest[3])?,
+                second: moved(rest[4], rest[5], rest[6], rest[7])?,
+                operation_id: match rest.get(8) {
+                    Some(field) => Some(String::from_utf8(decode_field(field)?).ok()?),
+                    None => None,
+                },
+                undone: false,
+                aborted: false,
+            });
+        }
+        [b"undo", id, _timestamp] => {
+            let id = number::<u64>(id)?;
+            entries.iter_mut().find(|entry| entry.id == id)?.undone = true;
+        }
+        [b"abort", id, _timestamp] => {
+            let id = number::<u64>(id)?;
+            entries.iter_mut().find(|entry| entry.id == id)?.aborted = true;
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+fn number<T: FromStr>(field: &[u8]) -> Option<T> {
+    std::str::from_utf8(field).ok()?.parse().ok()
+}
+
+fn seconds(timestamp: SystemTime) -> u64 {
+    timestamp
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
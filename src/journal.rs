@@ -0,0 +1,134 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    encoding::{decode_field, encode_field, path_bytes, path_from_bytes},
+    plan::{ExchangePlan, RenameStep},
+    ExchangeError, Phase, RenameError,
+};
+
+pub(crate) const FILE_NAME: &str = "journal";
+const HEADER: &[u8] = b"name-exchange journal 1";
+
+/// Outcome a prepared exchange was heading for when its journal was last written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Decision {
+    Commit,
+    Abort,
+}
+
+/// Durably records `plan` in `dir` before any of its entries is moved.
+pub(crate) fn write(dir: &Path, plan: &ExchangePlan) -> Result<(), ExchangeError> {
+    let path = dir.join(FILE_NAME);
+    let mut contents = HEADER.to_vec();
+    for step in [&plan.first, &plan.second] {
+        contents.push(b'\n');
+        encode_field(&path_bytes(&step.source), &mut contents);
+        contents.push(b'\t');
+        encode_field(&path_bytes(&step.target), &mut contents);
+    }
+    contents.push(b'\n');
+    File::create_new(&path)
+        .and_then(|mut file| {
+            file.write_all(&contents)?;
+            file.sync_all()
+        })
+        .and_then(|()| sync_dir(dir))
+        .map_err(|error| ExchangeError::io(Phase::Rename, &path, error))
+}
+
+/// Durably appends `decision`; the last decision in the journal wins.
+pub(crate) fn mark(dir: &Path, decision: Decision) -> Result<(), ExchangeError> {
+    let path = dir.join(FILE_NAME);
+    let line: &[u8] = match decision {
+        Decision::Commit => b"commit\n",
+        Decision::Abort => b"abort\n",
+    };
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| {
+            file.write_all(line)?;
+            file.sync_all()
+        })
+        .map_err(|error| ExchangeError::io(Phase::Rename, &path, error))
+}
+
+/// Reads the plan and the last decision recorded in `dir`.
+pub(crate) fn read(dir: &Path) -> Result<(ExchangePlan, Option<Decision>), ExchangeError> {
+    let path = dir.join(FILE_NAME);
+    let contents =
+        fs::read(&path).map_err(|error| ExchangeError::io(Phase::Inspect, &path, error))?;
+    parse(&contents).ok_or_else(|| {
+        ExchangeError::new(
+            RenameError::Unknown("malformed exchange journal".to_owned()),
+            Phase::Inspect,
+        )
+        .with_path(&path)
+    })
+}
+
+pub(crate) fn remove(dir: &Path) -> io::Result<()> {
+    match fs::remove_file(dir.join(FILE_NAME)) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Returns the journal directories of prepared exchanges left in `dir`.
+pub(crate) fn find(dir: &Path, prefix: &str) -> io::Result<Vec<PathBuf>> {
+    let mut found = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with(prefix)
+            && entry.path().join(FILE_NAME).is_file()
+        {
+            found.push(entry.path());
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+fn parse(contents: &[u8]) -> Option<(ExchangePlan, Option<Decision>)> {
+    let mut lines = contents.strip_suffix(b"\n")?.split(|&byte| byte == b'\n');
+    if lines.next()? != HEADER {
+        return None;
+    }
+    let first = parse_step(lines.next()?)?;
+    let second = parse_step(lines.next()?)?;
+    let mut decision = None;
+    for line in lines {
+        decision = Some(match line {
+            b"commit" => Decision::Commit,
+            b"abort" => Decision::Abort,
+            _ => return None,
+        });
+    }
+    Some((ExchangePlan { first, second }, decision))
+}
+
+fn parse_step(line: &[u8]) -> Option<RenameStep> {
+    let mut fields = line.split(|&byte| byte == b'\t');
+    let (Some(source), Some(target), None) = (fields.next(), fields.next(), fields.next()) else {
+        return None;
+    };
+    Some(RenameStep {
+        source: path_from_bytes(decode_field(source)?),
+        target: path_from_bytes(decode_field(target)?),
+        expected: None,
+    })
+}
+
+/// Makes a new directory entry in `dir` durable; directories cannot be opened for syncing on
+/// every platform.
+pub(crate) fn sync_dir(dir: &Path) -> io::Result<()> {
+    if cfg!(unix) {
+        File::open(dir)?.sync_all()
+    } else {
+        Ok(())
+    }
+}
@@ -2,15 +2,71 @@
 
 use std::path::{Path, PathBuf};
 
+#[cfg(feature = "tokio")]
+mod async_api;
+#[cfg(unix)]
+mod at;
+mod batch;
+mod diagnostics;
+mod encoding;
 mod entry;
 mod error;
 mod ffi;
+mod history;
+mod identity;
+mod journal;
+mod lock;
+mod mount;
+mod observer;
+mod options;
 mod plan;
+mod precondition;
+mod prepared;
+#[cfg(feature = "serde")]
+mod report;
 mod resolver;
+#[cfg(unix)]
+mod sys;
 mod transaction;
 
-pub use error::RenameError;
-pub use ffi::{exchange, exchange_n};
+#[cfg(feature = "tokio")]
+pub use async_api::{
+    exchange_async, exchange_async_with, exchange_batch_async, plan_exchange_async, rotate_async,
+};
+pub use diagnostics::{Finding, Severity};
+pub use error::{BatchError, ExchangeError, Phase, RenameError};
+pub use ffi::{
+    exchange, exchange_batch_n, exchange_last_error_message, exchange_last_error_os_code,
+    exchange_lib_capabilities, exchange_lib_version, exchange_n, exchange_n_observed, exchange_raw,
+    exchange_raw_n, exchange_rotate_n, exchanger_diagnose, exchanger_options_expect,
+    exchanger_options_free, exchanger_options_new, exchanger_options_set_base_dir,
+    exchanger_options_set_durability, exchanger_options_set_history,
+    exchanger_options_set_lock_timeout_ms, exchanger_options_set_observer,
+    exchanger_options_set_operation_id, exchanger_options_set_preserve_ext,
+    exchanger_options_set_verify, exchanger_plan, exchanger_plan_abort, exchanger_plan_commit,
+    exchanger_plan_execute, exchanger_plan_free, exchanger_plan_journal_dir,
+    exchanger_plan_prepare, exchanger_plan_source, exchanger_plan_step_count,
+    exchanger_plan_target, exchanger_recover, ExchangeEvent, ExchangeEventCallback,
+    ExchangeFinding, ExchangeFindingCallback, ExchangeLibVersion, ExchangePath, ExchangePathPair,
+    ExchangePrecondition, ExchangerOptions, ExchangerPlan,
+};
+pub use history::{History, HistoryEntry};
+pub use observer::{ExchangeObserver, NoopObserver, RenameStage};
+pub use options::{Durability, ExchangeOptions};
+pub use plan::PlannedExchange;
+pub use precondition::Precondition;
+pub use prepared::{PreparedExchange, Recovered};
+#[cfg(feature = "serde")]
+pub use report::{
+    DiagnosticsReport, ErrorReport, EventReport, FindingReport, OutcomeReport, PlanReport,
+    ReportStatus, StepReport,
+};
+
+/// C ABI functions whose symbols share their names with Rust functions of this crate.
+#[cfg(unix)]
+pub mod c_abi {
+    pub use crate::ffi::exchange_at;
+}
 
 /// Swaps names of two files, directories, or symbolic links.
 ///
@@ -20,8 +76,152 @@ pub use ffi::{exchange, exchange_n};
 ///
 /// Returns [`RenameError`] when validation, renaming, or rollback fails.
 pub fn exchange_rs(path1: &Path, path2: &Path, preserve_ext: bool) -> Result<(), RenameError> {
-    let plan = plan::ExchangePlan::build(path1, path2, preserve_ext)?;
-    transaction::execute(&plan)
+    exchange_with(
+        path1,
+        path2,
+        &ExchangeOptions::new().preserve_ext(preserve_ext),
+    )
+    .map_err(RenameError::from)
+}
+
+/// Swaps names of two files, directories, or symbolic links using explicit options.
+///
+/// # Errors
+///
+/// Returns [`ExchangeError`] when validation, renaming, or rollback fails.
+pub fn exchange_with(
+    path1: &Path,
+    path2: &Path,
+    options: &ExchangeOptions,
+) -> Result<(), ExchangeError> {
+    exchange_pair(plan::ResolvedPair::resolve(path1, path2, options)?, options)
+}
+
+/// Swaps names of two entries named relative to open directories.
+///
+/// Each name must be a single component inside its directory. Entries are inspected and renamed
+/// through the descriptors with `fstatat` and `renameat`, so neither the working directory nor
+/// the directories' current paths are consulted, and the exchange keeps working after a
+/// directory is moved. The temporary directory is created inside `dir2`, and observers and
+/// errors report names relative to the directories.
+///
+/// Before renaming, the descriptors get the checks path-based exchanges run: name length,
+/// read-only file systems, write and search access, immutable and append-only flags, sticky
+/// directories, and mount points. Mount points are recognized by their device numbers only, so
+/// bind mounts within one file system are left to the renames.
+///
+/// Exchanges in this process that rename inside the same directories, through handles or paths,
+/// are serialized. Options that name paths or need them, namely a history, an operation
+/// ID, preconditions, verification, and durability, are rejected.
+///
+/// # Errors
+///
+/// Returns [`ExchangeError`] when a descriptor is not a directory, a name has more than one
+/// component, an option is not supported, or validation, renaming, or rollback fails.
+#[cfg(unix)]
+pub fn exchange_at(
+    dir1: std::os::fd::BorrowedFd<'_>,
+    name1: &Path,
+    dir2: std::os::fd::BorrowedFd<'_>,
+    name2: &Path,
+    options: &ExchangeOptions,
+) -> Result<(), ExchangeError> {
+    at::exchange(dir1, name1, dir2, name2, options)
+}
+
+/// Exchanges each pair in order as one all-or-nothing operation.
+///
+/// Pairs may share entries; each pair sees the names produced by the pairs before it. When a pair
+/// fails, the pairs already exchanged are exchanged back in reverse order.
+///
+/// # Errors
+///
+/// Returns [`BatchError`] naming the failing pair when resolution, validation, renaming, or
+/// undoing fails.
+pub fn exchange_batch(
+    pairs: &[(&Path, &Path)],
+    options: &ExchangeOptions,
+) -> Result<(), BatchError> {
+    let pairs = batch::resolve(pairs, options)?;
+    let _lock = plan::lock_entries(
+        pairs.iter().flat_map(|pair| [&pair.first, &pair.second]),
+        options,
+    )
+    .map_err(|error| BatchError::new(None, error))?;
+    if history::completed(options, &pairs).map_err(|error| BatchError::new(None, error))? {
+        return Ok(());
+    }
+    batch::execute(pairs, options, None)
+}
+
+/// Gives each entry the name of the next one and the last entry the name of the first.
+///
+/// With two paths this is an exchange. Names follow the same rules as [`exchange_with`], and a
+/// failure undoes every completed rename.
+///
+/// # Errors
+///
+/// Returns [`ExchangeError`] when fewer than two paths are given, or when validation, renaming,
+/// or rollback fails.
+pub fn rotate(paths: &[&Path], options: &ExchangeOptions) -> Result<(), ExchangeError> {
+    let paths = resolve_all(paths, options)?;
+    let _lock = plan::lock_entries(&paths, options)?;
+    let plan = plan::RotationPlan::build(paths, options, &mount::MountTable::default())?;
+    transaction::execute_rotation(&plan, options.observer.as_ref(), None)
+}
+
+fn resolve_all(paths: &[&Path], options: &ExchangeOptions) -> Result<Vec<PathBuf>, ExchangeError> {
+    let base_dir = resolver::base_dir(options)?;
+    paths
+        .iter()
+        .map(|path| resolver::resolve(path, &base_dir).map(resolver::ResolvedPath::into_path))
+        .collect()
+}
+
+/// Validates an exchange and computes the new names without renaming anything.
+///
+/// # Errors
+///
+/// Returns [`ExchangeError`] when resolution or validation fails.
+pub fn plan_exchange(
+    path1: &Path,
+    path2: &Path,
+    options: &ExchangeOptions,
+) -> Result<PlannedExchange, ExchangeError> {
+    let pair = plan::ResolvedPair::resolve(path1, path2, options)?;
+    let _lock = pair.lock(options)?;
+    Ok(PlannedExchange {
+        plan: plan::ExchangePlan::build(pair, options, &mount::MountTable::default())?,
+        options: options.clone(),
+    })
+}
+
+/// Runs every check an exchange of `path1` and `path2` would make and returns each problem found,
+/// instead of stopping at the first.
+///
+/// Checks resolution, existence, distinct and non-nested entries, both targets, preconditions,
+/// name lengths, and the pre-flight permission checks. Nothing is renamed; an empty list means
+/// the exchange is expected to succeed.
+#[must_use]
+pub fn diagnose_exchange(path1: &Path, path2: &Path, options: &ExchangeOptions) -> Vec<Finding> {
+    diagnostics::diagnose(path1, path2, options)
+}
+
+fn exchange_pair(pair: plan::ResolvedPair, options: &ExchangeOptions) -> Result<(), ExchangeError> {
+    let _lock = pair.lock(options)?;
+    if history::completed(options, [&pair])? {
+        return Ok(());
+    }
+    let plan = plan::ExchangePlan::build(pair, options, &mount::MountTable::default())?;
+    history::recorded(options, &plan, || {
+        transaction::execute(
+            &plan,
+            options.observer.as_ref(),
+            None,
+            options.verify,
+            options.durability,
+        )
+    })
 }
 
 /// Resolves a path without dereferencing its final symbolic-link component.
@@ -30,5 +230,7 @@ pub fn exchange_rs(path1: &Path, path2: &Path, preserve_ext: bool) -> Result<(),
 ///
 /// Returns [`RenameError`] when expansion, normalization, or metadata access fails.
 pub fn resolve_path_rs(path: &Path, base_dir: &Path) -> Result<(bool, PathBuf), RenameError> {
-    resolver::resolve(path, base_dir).map(resolver::ResolvedPath::into_legacy_tuple)
+    resolver::resolve(path, base_dir)
+        .map(resolver::ResolvedPath::into_legacy_tuple)
+        .map_err(RenameError::from)
 }
@@ -0,0 +1,174 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    path::PathBuf,
+    sync::{Condvar, Mutex, MutexGuard, OnceLock, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::identity::Identity;
+
+static LOCK_TABLE: OnceLock<LockTable> = OnceLock::new();
+
+/// How a key is locked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Access {
+    /// Held alongside other shared holders, such as ancestors of the directories an exchange
+    /// renames in, which must not be renamed meanwhile.
+    Shared,
+    /// Held alone, for the entries an exchange renames and their directories.
+    Exclusive,
+}
+
+/// Returns the key of the directory with `identity`.
+///
+/// Exchanges through directory handles lock it for each directory, and path-based exchanges for
+/// each parent, so that both serialize. Relative keys never collide with locked absolute paths.
+pub(crate) fn directory_key(identity: Identity) -> PathBuf {
+    PathBuf::from(format!("directory {}:{}", identity.dev, identity.ino))
+}
+
+#[derive(Debug)]
+enum Holders {
+    Exclusive,
+    Shared(usize),
+}
+
+#[derive(Default)]
+struct LockTable {
+    held: Mutex<BTreeMap<PathBuf, Holders>>,
+    released: Condvar,
+    #[cfg(feature = "tokio")]
+    released_async: tokio::sync::Notify,
+}
+
+impl LockTable {
+    fn held(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Holders>> {
+        self.held.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn release<'a>(
+        &self,
+        mut held: MutexGuard<'_, BTreeMap<PathBuf, Holders>>,
+        keys: impl IntoIterator<Item = &'a PathBuf>,
+    ) {
+        for key in keys {
+            if let Entry::Occupied(mut entry) = held.entry(key.clone()) {
+                match entry.get_mut() {
+                    Holders::Shared(count) if *count > 1 => *count -= 1,
+                    _ => {
+                        entry.remove();
+                    }
+                }
+            }
+        }
+        drop(held);
+        self.released.notify_all();
+        #[cfg(feature = "tokio")]
+        self.released_async.notify_waiters();
+    }
+}
+
+/// Releases every key of one exchange when dropped.
+#[derive(Debug)]
+pub(crate) struct PathLock {
+    keys: Vec<PathBuf>,
+}
+
+impl Drop for PathLock {
+    fn drop(&mut self) {
+        let table = table();
+        table.release(table.held(), &self.keys);
+    }
+}
+
+/// Takes `key` unless a holder conflicts with `access`.
+fn take(held: &mut BTreeMap<PathBuf, Holders>, key: &PathBuf, access: Access) -> bool {
+    match (held.get_mut(key), access) {
+        (None, Access::Exclusive) => {
+            held.insert(key.clone(), Holders::Exclusive);
+        }
+        (None, Access::Shared) => {
+            held.insert(key.clone(), Holders::Shared(1));
+        }
+        (Some(Holders::Shared(count)), Access::Shared) => *count += 1,
+        (Some(_), _) => return false,
+    }
+    true
+}
+
+/// Combines requests for the same key, keeping the strongest access, in sorted order.
+fn sorted(keys: impl IntoIterator<Item = (PathBuf, Access)>) -> BTreeMap<PathBuf, Access> {
+    let mut sorted = BTreeMap::new();
+    for (key, access) in keys {
+        let current = sorted.entry(key).or_insert(access);
+        *current = (*current).max(access);
+    }
+    sorted
+}
+
+/// Locks keys one by one in sorted order, so overlapping callers cannot deadlock.
+///
+/// Returns `None`, holding nothing, when `timeout` elapses first.
+pub(crate) fn lock_paths(
+    keys: impl IntoIterator<Item = (PathBuf, Access)>,
+    timeout: Option<Duration>,
+) -> Option<PathLock> {
+    let keys = sorted(keys);
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    let table = table();
+    let mut held = table.held();
+    for (index, (key, &access)) in keys.iter().enumerate() {
+        while !take(&mut held, key, access) {
+            let Some(deadline) = deadline else {
+                held = table
+                    .released
+                    .wait(held)
+                    .unwrap_or_else(PoisonError::into_inner);
+                continue;
+            };
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                table.release(held, keys.keys().take(index));
+                return None;
+            }
+            held = table
+                .released
+                .wait_timeout(held, remaining)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+    Some(PathLock {
+        keys: keys.into_keys().collect(),
+    })
+}
+
+/// Async counterpart of [`lock_paths`]; dropping the future releases keys taken so far.
+#[cfg(feature = "tokio")]
+pub(crate) async fn lock_paths_async(
+    keys: impl IntoIterator<Item = (PathBuf, Access)>,
+) -> PathLock {
+    let keys = sorted(keys);
+    let table = table();
+    let mut lock = PathLock {
+        keys: Vec::with_capacity(keys.len()),
+    };
+    for (key, access) in keys {
+        loop {
+            // Registering before the check ensures a release in between is not missed.
+            let released = table.released_async.notified();
+            let mut released = std::pin::pin!(released);
+            released.as_mut().enable();
+            if take(&mut table.held(), &key, access) {
+                break;
+            }
+            released.await;
+        }
+        lock.keys.push(key);
+    }
+    lock
+}
+
+fn table() -> &'static LockTable {
+    LOCK_TABLE.get_or_init(LockTable::default)
+}
@@ -0,0 +1,79 @@
+#[cfg(unix)]
+use std::{
+    fs, io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Mount points of the calling process, read at most once per exchange, batch, or rotation.
+///
+/// A mount point usually has a different device number than its parent directory, but a bind
+/// mount of a directory on the same file system does not, so the mount table is read as well,
+/// and only when the device numbers do not already tell.
+#[derive(Debug, Default)]
+pub(crate) struct MountTable {
+    #[cfg(unix)]
+    points: OnceLock<io::Result<Vec<PathBuf>>>,
+}
+
+impl MountTable {
+    /// Returns whether `path` is a mount point: it lies on another device than `parent`, which
+    /// also catches subvolumes, or the mount table lists it.
+    #[cfg(unix)]
+    pub(crate) fn is_mount_point(&self, path: &Path, parent: &Path) -> io::Result<bool> {
+        let device = fs::symlink_metadata(path)?.dev();
+        if device != fs::metadata(parent)?.dev() {
+            return Ok(true);
+        }
+        let points = self.points.get_or_init(|| {
+            #[cfg(any(target_os = "linux", target_os = "android"))]
+            {
+                read_points()
+            }
+            #[cfg(not(any(target_os = "linux", target_os = "android")))]
+            {
+                Ok(Vec::new())
+            }
+        });
+        match points {
+            Ok(points) => Ok(points.iter().any(|point| point == path)),
+            Err(error) => Err(io::Error::new(error.kind(), error.to_string())),
+        }
+    }
+}
+
+/// Reads `/proc/self/mountinfo`; without `/proc`, only device boundaries are detected.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn read_points() -> io::Result<Vec<PathBuf>> {
+    match fs::read("/proc/self/mountinfo") {
+        Ok(contents) => Ok(contents
+            .split(|&byte| byte == b'\n')
+            .filter_map(|line| line.split(|&byte| byte == b' ').nth(4))
+            .map(unescape)
+            .collect()),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(error) => Err(error),
+    }
+}
+
+/// Decodes the octal escapes `mountinfo` uses for spaces, tabs, newlines and backslashes.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn unescape(field: &[u8]) -> PathBuf {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut rest = field;
+    while let Some((&byte, tail)) = rest.split_first() {
+        if let (b'\\', [a @ b'0'..=b'3', b @ b'0'..=b'7', c @ b'0'..=b'7', tail @ ..]) =
+            (byte, tail)
+        {
+            bytes.push((a - b'0') << 6 | (b - b'0') << 3 | (c - b'0'));
+            rest = tail;
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    PathBuf::from(OsStr::from_bytes(&bytes))
+}
@@ -0,0 +1,61 @@
+use std::{io, path::Path};
+
+use crate::ExchangeError;
+
+/// Rename performed by an exchange, in execution order.
+///
+/// A [`rotate`](crate::rotate) reports the same stages for each entry it moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum RenameStage {
+    /// Moves the second entry into the temporary directory.
+    ToTemporary,
+    /// Moves the first entry to its new name.
+    FirstToTarget,
+    /// Moves the second entry from the temporary directory to its new name.
+    TemporaryToTarget,
+}
+
+/// Receives every step of an exchange as it happens.
+///
+/// All methods default to doing nothing. Callbacks run synchronously on the thread performing
+/// the exchange while its path lock is held, so they should return quickly.
+pub trait ExchangeObserver: Send + Sync {
+    /// The temporary directory used to stage the second entry was created.
+    fn temp_dir_created(&self, _path: &Path) {}
+
+    /// A rename of the exchange finished.
+    fn renamed(
+        &self,
+        _stage: RenameStage,
+        _from: &Path,
+        _to: &Path,
+        _result: Result<(), &ExchangeError>,
+    ) {
+    }
+
+    /// A rename failed and the completed renames are about to be undone.
+    fn rollback_started(&self, _cause: &ExchangeError) {}
+
+    /// One rename undoing an earlier step finished.
+    fn rollback_step(&self, _from: &Path, _to: &Path, _result: Result<(), &ExchangeError>) {}
+
+    /// The temporary directory was removed, or kept because an entry is still stranded in it.
+    fn cleanup(&self, _path: &Path, _result: Result<(), &io::Error>) {}
+
+    /// The history at `path` could not be updated after the entries were renamed or restored.
+    ///
+    /// The outcome of the operation stands; the history may still list an undone exchange, or
+    /// one that failed, whose undo then fails with [`RenameError::Changed`](crate::RenameError).
+    fn history_failed(&self, _path: &Path, _error: &ExchangeError) {}
+}
+
+/// Observer that ignores every event.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl ExchangeObserver for NoopObserver {}
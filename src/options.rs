@@ -0,0 +1,170 @@
+use std::{fmt, path::PathBuf, sync::Arc, time::Duration};
+
+use crate::{ExchangeObserver, History, NoopObserver, Precondition};
+
+/// Settings for a single exchange.
+#[derive(Clone)]
+pub struct ExchangeOptions {
+    pub(crate) preserve_ext: bool,
+    pub(crate) observer: Arc<dyn ExchangeObserver>,
+    pub(crate) lock_timeout: Option<Duration>,
+    pub(crate) base_dir: Option<PathBuf>,
+    pub(crate) history: Option<History>,
+    pub(crate) operation_id: Option<String>,
+    pub(crate) preconditions: Vec<(PathBuf, Precondition)>,
+    pub(crate) verify: bool,
+    pub(crate) durability: Durability,
+}
+
+/// How much of an exchange is flushed to storage before it reports success.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Durability {
+    /// Leaves flushing to the operating system; a power loss may lose any of the renames.
+    #[default]
+    None,
+    /// Journals the exchange and syncs each directory a rename changed, so that after a power
+    /// loss the entries have either their old or their new names, or a journal that
+    /// [`PreparedExchange::recover`](crate::PreparedExchange::recover) finishes or undoes.
+    Directories,
+    /// Like [`Directories`](Self::Directories), and first syncs each exchanged file, or a
+    /// directory's own listing, so that the contents are on storage before the names change.
+    Full,
+}
+
+impl ExchangeOptions {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps each regular file's extension and exchanges only the file stems.
+    #[must_use]
+    pub fn preserve_ext(mut self, preserve_ext: bool) -> Self {
+        self.preserve_ext = preserve_ext;
+        self
+    }
+
+    /// Reports each step of the exchange to `observer`.
+    #[must_use]
+    pub fn observer(mut self, observer: Arc<dyn ExchangeObserver>) -> Self {
+        self.observer = observer;
+        self
+    }
+
+    /// Fails with [`RenameError::LockTimeout`](crate::RenameError::LockTimeout) instead of
+    /// waiting longer than `timeout` for overlapping exchanges in this process.
+    #[must_use]
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    /// Resolves relative paths against `base_dir` instead of the process working directory.
+    ///
+    /// `base_dir` must be absolute; otherwise resolution fails with
+    /// [`RenameError::InvalidPath`](crate::RenameError::InvalidPath).
+    #[must_use]
+    pub fn base_dir(mut self, base_dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(base_dir.into());
+        self
+    }
+
+    /// Records each exchange in `history` so that it can be undone.
+    ///
+    /// Exchanges are recorded before their first rename; a recording failure is reported with
+    /// [`Phase::History`](crate::Phase::History) and renames nothing. Once entries were renamed,
+    /// history failures only reach the observer's
+    /// [`history_failed`](crate::ExchangeObserver::history_failed). Rotations are not recorded.
+    #[must_use]
+    pub fn history(mut self, history: History) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Makes repeating an exchange with the same `operation_id` a successful no-op.
+    ///
+    /// The ID is recorded in the [`history`](Self::history) with the exchanged entries. A later
+    /// exchange of the same paths with this ID renames nothing while those entries are still at
+    /// the names it gave them, and fails with [`RenameError::Changed`](crate::RenameError::Changed)
+    /// otherwise. An empty ID, an ID used for other paths, or one without a history fails with
+    /// [`RenameError::InvalidOperationId`](crate::RenameError::InvalidOperationId) before
+    /// anything is renamed. Undone operations run again.
+    #[must_use]
+    pub fn operation_id(mut self, operation_id: impl Into<String>) -> Self {
+        self.operation_id = Some(operation_id.into());
+        self
+    }
+
+    /// Checks after each exchange that every new name holds the entry that had the old one.
+    ///
+    /// The device and inode numbers of both entries are read just before the first rename and
+    /// compared with those found at the new names.
+    /// [`RenameError::VerificationFailed`](crate::RenameError::VerificationFailed) describes a
+    /// mismatch. The entries are only exchanged back while both new names hold the entries the
+    /// exchange gave them, so another writer's entries never move; otherwise the error is
+    /// [`RenameError::RollbackFailed`](crate::RenameError::RollbackFailed) with the mismatch as
+    /// its operation. A temporary directory left behind is only reported to the observer.
+    /// Rotations are not verified. Needs Unix; elsewhere exchanges fail before renaming.
+    #[must_use]
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Flushes the exchange to storage as described by `durability` before returning.
+    ///
+    /// Durable exchanges are journaled like [`PlannedExchange::prepare`](crate::PlannedExchange::prepare)
+    /// followed by a commit, and look for leftovers with
+    /// [`PreparedExchange::journals_in`](crate::PreparedExchange::journals_in) after a crash.
+    /// A failed sync fails the exchange in [`Phase::Rename`](crate::Phase::Rename) and restores
+    /// the original names. Rotations are not synced.
+    #[must_use]
+    pub fn durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+
+    /// Exchanges the entry at `path` only while it still matches `precondition`.
+    ///
+    /// `path` is resolved like the exchanged paths and applies to whichever entry holds it when
+    /// an exchange or rotation is planned. The entry is checked while planning and again just
+    /// before the first rename; a mismatch fails with
+    /// [`RenameError::Changed`](crate::RenameError::Changed) and renames nothing. A later
+    /// precondition for the same path replaces an earlier one.
+    #[must_use]
+    pub fn expect(mut self, path: impl Into<PathBuf>, precondition: Precondition) -> Self {
+        self.preconditions.push((path.into(), precondition));
+        self
+    }
+}
+
+impl Default for ExchangeOptions {
+    fn default() -> Self {
+        Self {
+            preserve_ext: false,
+            observer: Arc::new(NoopObserver),
+            lock_timeout: None,
+            base_dir: None,
+            history: None,
+            operation_id: None,
+            preconditions: Vec::new(),
+            verify: false,
+            durability: Durability::None,
+        }
+    }
+}
+
+impl fmt::Debug for ExchangeOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExchangeOptions")
+            .field("preserve_ext", &self.preserve_ext)
+            .field("lock_timeout", &self.lock_timeout)
+            .field("base_dir", &self.base_dir)
+            .field("history", &self.history)
+            .field("operation_id", &self.operation_id)
+            .field("preconditions", &self.preconditions)
+            .field("verify", &self.verify)
+            .field("durability", &self.durability)
+            .finish_non_exhaustive()
+    }
+}
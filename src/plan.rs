@@ -1,80 +1,332 @@
-use std::{fs, path::PathBuf};
+use std::{
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+};
 
 use same_file::is_same_file;
 
 use crate::{
+    diagnostics::{Finding, Severity},
     entry::{compose_file_name, Entry, EntryKind},
-    resolver::{current_base_dir, resolve},
-    RenameError,
+    history,
+    identity::Identity,
+    lock::{directory_key, lock_paths, Access, PathLock},
+    mount::MountTable,
+    precondition::{self, Precondition},
+    resolver::{base_dir, resolve},
+    transaction, ExchangeError, ExchangeOptions, Phase, RenameError,
 };
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub(crate) struct RenameStep {
     pub(crate) source: PathBuf,
     pub(crate) target: PathBuf,
+    /// Checked against the source again just before the first rename.
+    pub(crate) expected: Option<Precondition>,
 }
 
-#[derive(Debug)]
+impl RenameStep {
+    /// Checks the source against its precondition and attaches it to the step.
+    fn new(
+        source: PathBuf,
+        target: PathBuf,
+        options: &ExchangeOptions,
+    ) -> Result<Self, ExchangeError> {
+        let expected = precondition::lookup(options, &source)?;
+        if let Some(expected) = &expected {
+            expected.verify(&source, Phase::Plan)?;
+        }
+        Ok(Self {
+            source,
+            target,
+            expected,
+        })
+    }
+
+    fn inverse(&self) -> Self {
+        Self {
+            source: self.target.clone(),
+            target: self.source.clone(),
+            expected: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
 pub(crate) struct ExchangePlan {
     pub(crate) first: RenameStep,
     pub(crate) second: RenameStep,
 }
 
+/// A validated exchange that has not been performed yet.
+///
+/// Created by [`plan_exchange`](crate::plan_exchange). Nothing is locked between planning and
+/// [`execute`](Self::execute), which therefore validates the entries again.
+#[derive(Debug)]
+pub struct PlannedExchange {
+    pub(crate) plan: ExchangePlan,
+    pub(crate) options: ExchangeOptions,
+}
+
+impl PlannedExchange {
+    /// Returns each entry's current path and the path it will be renamed to.
+    #[must_use]
+    pub fn steps(&self) -> impl ExactSizeIterator<Item = (&Path, &Path)> {
+        [&self.plan.first, &self.plan.second]
+            .into_iter()
+            .map(|step| (step.source.as_path(), step.target.as_path()))
+    }
+
+    /// Performs the planned exchange.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RenameError::Changed`] when validating the entries again yields different
+    /// targets, and otherwise fails like [`exchange_with`](crate::exchange_with).
+    pub fn execute(self) -> Result<(), ExchangeError> {
+        let pair = self.plan.pair();
+        let _lock = pair.lock(&self.options)?;
+        if history::completed(&self.options, [&pair])? {
+            return Ok(());
+        }
+        let plan = self.plan.revalidate(pair, &self.options)?;
+        history::recorded(&self.options, &plan, || {
+            transaction::execute(
+                &plan,
+                self.options.observer.as_ref(),
+                None,
+                self.options.verify,
+                self.options.durability,
+            )
+        })
+    }
+}
+
+/// Resolved paths of the two entries; existence is checked again once the pair is locked.
+#[derive(Debug)]
+pub(crate) struct ResolvedPair {
+    pub(crate) first: PathBuf,
+    pub(crate) second: PathBuf,
+}
+
+impl ResolvedPair {
+    pub(crate) fn resolve(
+        first_path: &Path,
+        second_path: &Path,
+        options: &ExchangeOptions,
+    ) -> Result<Self, ExchangeError> {
+        let base_dir = base_dir(options)?;
+        Self::resolve_in(first_path, &base_dir, second_path, &base_dir)
+    }
+
+    /// Resolves each relative path against its own absolute base directory.
+    pub(crate) fn resolve_in(
+        first_path: &Path,
+        first_base: &Path,
+        second_path: &Path,
+        second_base: &Path,
+    ) -> Result<Self, ExchangeError> {
+        Ok(Self {
+            first: resolve(first_path, first_base)?.into_path(),
+            second: resolve(second_path, second_base)?.into_path(),
+        })
+    }
+
+    #[cfg(feature = "tokio")]
+    pub(crate) fn lock_keys(&self) -> Vec<(PathBuf, Access)> {
+        lock_keys([&self.first, &self.second])
+    }
+
+    pub(crate) fn lock(&self, options: &ExchangeOptions) -> Result<PathLock, ExchangeError> {
+        lock_entries([&self.first, &self.second], options)
+    }
+}
+
+/// Returns the entries that renaming `paths` touches and their parent directories, which are
+/// locked exclusively by path and by identity, and every directory above those, which must not be
+/// renamed meanwhile.
+pub(crate) fn lock_keys<'a>(
+    paths: impl IntoIterator<Item = &'a PathBuf>,
+) -> Vec<(PathBuf, Access)> {
+    let mut keys = Vec::new();
+    for path in paths {
+        let mut ancestors = path.ancestors().map(Path::to_path_buf);
+        keys.extend(
+            ancestors
+                .by_ref()
+                .take(2)
+                .map(|key| (key, Access::Exclusive)),
+        );
+        keys.extend(ancestors.map(|key| (key, Access::Shared)));
+        // Exchanges through a handle of the parent know it only by its identity.
+        if let Some(identity) = path.parent().and_then(|parent| Identity::of(parent).ok()) {
+            keys.push((directory_key(identity), Access::Exclusive));
+        }
+    }
+    keys
+}
+
+pub(crate) fn lock_entries<'a>(
+    paths: impl IntoIterator<Item = &'a PathBuf>,
+    options: &ExchangeOptions,
+) -> Result<PathLock, ExchangeError> {
+    lock_paths(lock_keys(paths), options.lock_timeout)
+        .ok_or_else(|| ExchangeError::new(RenameError::LockTimeout, Phase::Lock))
+}
+
 impl ExchangePlan {
-    pub(crate) fn build(
-        first_path: &std::path::Path,
-        second_path: &std::path::Path,
-        preserve_ext: bool,
-    ) -> Result<Self, RenameError> {
-        let base_dir = current_base_dir()?;
-        let first_path = resolve(first_path, &base_dir)?.into_existing()?;
-        let second_path = resolve(second_path, &base_dir)?.into_existing()?;
+    /// Returns the plan that moves both entries back to their original names.
+    pub(crate) fn inverse(&self) -> Self {
+        Self {
+            first: self.first.inverse(),
+            second: self.second.inverse(),
+        }
+    }
 
-        if first_path == second_path
-            || is_same_file(&first_path, &second_path).map_err(RenameError::from)?
-        {
-            return Err(RenameError::SamePath);
+    pub(crate) fn pair(&self) -> ResolvedPair {
+        ResolvedPair {
+            first: self.first.source.clone(),
+            second: self.second.source.clone(),
         }
+    }
+
+    /// Validates a locked pair again and checks that nothing changed since this plan was made.
+    pub(crate) fn revalidate(
+        &self,
+        pair: ResolvedPair,
+        options: &ExchangeOptions,
+    ) -> Result<Self, ExchangeError> {
+        let current = Self::build(pair, options, &MountTable::default())?;
+        let renames = |plan: &Self| {
+            [&plan.first, &plan.second].map(|step| (step.source.clone(), step.target.clone()))
+        };
+        if renames(&current) == renames(self) {
+            Ok(current)
+        } else {
+            Err(ExchangeError::new(RenameError::Changed, Phase::Plan))
+        }
+    }
+
+    /// Validates the pair; callers hold the pair's path lock so the checks stay meaningful.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", ret, err))]
+    pub(crate) fn build(
+        pair: ResolvedPair,
+        options: &ExchangeOptions,
+        mounts: &MountTable,
+    ) -> Result<Self, ExchangeError> {
+        let preserve_ext = options.preserve_ext;
+        let ResolvedPair {
+            first: first_path,
+            second: second_path,
+        } = pair;
 
         let first = Entry::inspect(first_path)?;
         let second = Entry::inspect(second_path)?;
+        reject_same_entry(&first, &second)?;
         reject_nested_directories(&first, &second)?;
 
         let first_target = target_for(&first, &second, preserve_ext);
         let second_target = target_for(&second, &first, preserve_ext);
         if first_target == second_target {
-            return Err(RenameError::AlreadyExists);
+            return Err(ExchangeError::new(RenameError::AlreadyExists, Phase::Plan)
+                .with_path(&first_target));
         }
 
-        ensure_available(&first_target, &first, &second)?;
-        ensure_available(&second_target, &first, &second)?;
+        ensure_available(&first_target, &[&first, &second])?;
+        ensure_available(&second_target, &[&first, &second])?;
+        check_name_length(&first_target)?;
+        check_name_length(&second_target)?;
+        preflight(&[&first, &second], mounts)?;
 
         Ok(Self {
-            first: RenameStep {
-                source: first.path,
-                target: first_target,
-            },
-            second: RenameStep {
-                source: second.path,
-                target: second_target,
-            },
+            first: RenameStep::new(first.path, first_target, options)?,
+            second: RenameStep::new(second.path, second_target, options)?,
         })
     }
 }
 
-fn reject_nested_directories(first: &Entry, second: &Entry) -> Result<(), RenameError> {
-    let nested = (first.is_directory() && second.path.starts_with(&first.path))
-        || (second.is_directory() && first.path.starts_with(&second.path));
-    if nested {
-        Err(RenameError::InvalidPath(
-            "ancestor/descendant paths cannot be exchanged safely".to_owned(),
-        ))
-    } else {
-        Ok(())
+/// Renames that give each entry the name of the next one, and the last the name of the first.
+#[derive(Debug)]
+pub(crate) struct RotationPlan {
+    pub(crate) steps: Vec<RenameStep>,
+}
+
+impl RotationPlan {
+    /// Validates the entries; callers hold their path lock so the checks stay meaningful.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", ret, err))]
+    pub(crate) fn build(
+        paths: Vec<PathBuf>,
+        options: &ExchangeOptions,
+        mounts: &MountTable,
+    ) -> Result<Self, ExchangeError> {
+        let preserve_ext = options.preserve_ext;
+        if paths.len() < 2 {
+            return Err(ExchangeError::new(
+                RenameError::InvalidPath("a rotation needs at least two paths".to_owned()),
+                Phase::Plan,
+            ));
+        }
+        let entries = paths
+            .into_iter()
+            .map(Entry::inspect)
+            .collect::<Result<Vec<_>, _>>()?;
+        for (index, first) in entries.iter().enumerate() {
+            for second in &entries[index + 1..] {
+                reject_same_entry(first, second)?;
+                reject_nested_directories(first, second)?;
+            }
+        }
+
+        let targets = entries
+            .iter()
+            .zip(entries.iter().cycle().skip(1))
+            .map(|(entry, next)| target_for(entry, next, preserve_ext))
+            .collect::<Vec<_>>();
+        let all = entries.iter().collect::<Vec<_>>();
+        for (index, target) in targets.iter().enumerate() {
+            if targets[..index].contains(target) {
+                return Err(
+                    ExchangeError::new(RenameError::AlreadyExists, Phase::Plan).with_path(target)
+                );
+            }
+            ensure_available(target, &all)?;
+            check_name_length(target)?;
+        }
+        preflight(&all, mounts)?;
+
+        Ok(Self {
+            steps: entries
+                .into_iter()
+                .zip(targets)
+                .map(|(entry, target)| RenameStep::new(entry.path, target, options))
+                .collect::<Result<_, _>>()?,
+        })
     }
 }
 
-fn target_for(entry: &Entry, other: &Entry, preserve_ext: bool) -> PathBuf {
+fn reject_same_entry(first: &Entry, second: &Entry) -> Result<(), ExchangeError> {
+    if first.path == second.path
+        || is_same_file(&first.path, &second.path)
+            .map_err(|error| ExchangeError::io(Phase::Plan, &first.path, error))?
+    {
+        return Err(ExchangeError::new(RenameError::SamePath, Phase::Plan).with_path(&second.path));
+    }
+    Ok(())
+}
+
+fn reject_nested_directories(first: &Entry, second: &Entry) -> Result<(), ExchangeError> {
+    let ancestor = if first.is_directory() && second.path.starts_with(&first.path) {
+        first
+    } else if second.is_directory() && first.path.starts_with(&second.path) {
+        second
+    } else {
+        return Ok(());
+    };
+    Err(ExchangeError::new(RenameError::NestedDirectories, Phase::Plan).with_path(&ancestor.path))
+}
+
+pub(crate) fn target_for(entry: &Entry, other: &Entry, preserve_ext: bool) -> PathBuf {
     let name = if entry.kind == EntryKind::File && other.kind == EntryKind::File {
         let extension = if preserve_ext {
             entry.extension.as_deref()
@@ -88,17 +340,207 @@ fn target_for(entry: &Entry, other: &Entry, preserve_ext: bool) -> PathBuf {
     entry.parent.join(name)
 }
 
-fn ensure_available(
-    target: &std::path::Path,
-    first: &Entry,
-    second: &Entry,
-) -> Result<(), RenameError> {
-    if target == first.path || target == second.path {
+/// Checks that `target` is free or belongs to one of the participating `entries`.
+fn ensure_available(target: &Path, entries: &[&Entry]) -> Result<(), ExchangeError> {
+    if entries.iter().any(|entry| entry.path == target) {
         return Ok(());
     }
     match fs::symlink_metadata(target) {
-        Ok(_) => Err(RenameError::AlreadyExists),
+        Ok(_) => Err(ExchangeError::new(RenameError::AlreadyExists, Phase::Plan).with_path(target)),
         Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
-        Err(error) => Err(error.into()),
+        Err(error) => Err(ExchangeError::io(Phase::Plan, target, error)),
+    }
+}
+
+/// Fails with [`RenameError::NameTooLong`] when the file system cannot hold the name of `target`.
+fn check_name_length(target: &Path) -> Result<(), ExchangeError> {
+    let (Some(parent), Some(name)) = (target.parent(), target.file_name()) else {
+        return Ok(());
+    };
+    if name_too_long(parent, name) {
+        return Err(ExchangeError::new(RenameError::NameTooLong, Phase::Plan).with_path(target));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn name_too_long(parent: &Path, name: &OsStr) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    crate::sys::name_max(parent).is_some_and(|max| name.as_bytes().len() > max)
+}
+
+#[cfg(windows)]
+fn name_too_long(_parent: &Path, name: &OsStr) -> bool {
+    use std::os::windows::ffi::OsStrExt;
+
+    // `MAX_PATH` limits each component to 255 UTF-16 code units.
+    name.encode_wide().count() > 255
+}
+
+#[cfg(not(any(unix, windows)))]
+fn name_too_long(_parent: &Path, _name: &OsStr) -> bool {
+    false
+}
+
+/// Runs every check of [`ExchangePlan::build`] that applies to the inspected `entries`, recording
+/// each problem in `findings` instead of stopping at the first.
+pub(crate) fn diagnose(
+    entries: &[Entry],
+    options: &ExchangeOptions,
+    mounts: &MountTable,
+    findings: &mut Vec<Finding>,
+) {
+    let mut check = |result: Result<(), ExchangeError>| {
+        if let Err(error) = result {
+            findings.push(Finding::new(Severity::Error, error));
+        }
+    };
+    for entry in entries {
+        check(
+            precondition::lookup(options, &entry.path).and_then(|expected| {
+                expected.map_or(Ok(()), |expected| expected.verify(&entry.path, Phase::Plan))
+            }),
+        );
+    }
+    if let [first, second] = entries {
+        let same = reject_same_entry(first, second);
+        let is_same = same.is_err();
+        check(same);
+        if !is_same {
+            check(reject_nested_directories(first, second));
+            let first_target = target_for(first, second, options.preserve_ext);
+            let second_target = target_for(second, first, options.preserve_ext);
+            if first_target == second_target {
+                check(Err(ExchangeError::new(
+                    RenameError::AlreadyExists,
+                    Phase::Plan,
+                )
+                .with_path(&first_target)));
+            }
+            for target in [&first_target, &second_target] {
+                // Looking up a name that is too long fails with the same problem.
+                check(
+                    check_name_length(target)
+                        .and_then(|()| ensure_available(target, &[first, second])),
+                );
+            }
+        }
+    }
+    findings.extend(preflight_findings(
+        &entries.iter().collect::<Vec<_>>(),
+        mounts,
+    ));
+}
+
+/// Fails with every error [`preflight_findings`] reports, the first carrying the others.
+fn preflight(entries: &[&Entry], mounts: &MountTable) -> Result<(), ExchangeError> {
+    let mut problems = preflight_findings(entries, mounts)
+        .into_iter()
+        .filter(|finding| finding.severity() == Severity::Error)
+        .map(Finding::into_error);
+    match problems.next() {
+        Some(first) => Err(first.with_more(problems.collect())),
+        None => Ok(()),
     }
 }
+
+/// Finds every reason the system would refuse to rename `entries` before any of them is renamed.
+///
+/// Targets and temporary directories are created beside the entries, so only their parent
+/// directories need write and search access, a writable file system, and no immutable or
+/// append-only flag. An entry must carry neither flag nor be a mount point, and a sticky parent
+/// must belong to the effective user, or the entry must. Queries that fail are reported as
+/// warnings.
+#[cfg(unix)]
+fn preflight_findings(entries: &[&Entry], mounts: &MountTable) -> Vec<Finding> {
+    use std::os::unix::fs::MetadataExt;
+
+    use crate::sys::{check_access, effective_uid, inode_flags, is_read_only_mount};
+
+    const S_ISVTX: u32 = 0o1000;
+
+    let found = |severity, path: &Path, error| {
+        Finding::new(severity, ExchangeError::io(Phase::Preflight, path, error))
+    };
+    let refused = |path: &Path, kind, reason: &str| {
+        found(Severity::Error, path, io::Error::new(kind, reason))
+    };
+    let flagged = |path: &Path, what: &str| match inode_flags(path) {
+        Ok(flags) if flags.immutable => Some(refused(
+            path,
+            io::ErrorKind::PermissionDenied,
+            &format!("{what} is immutable"),
+        )),
+        Ok(flags) if flags.append_only => Some(refused(
+            path,
+            io::ErrorKind::PermissionDenied,
+            &format!("{what} is append-only"),
+        )),
+        Ok(_) => None,
+        Err(error) => Some(found(Severity::Warning, path, error)),
+    };
+    let mut findings = Vec::new();
+
+    let mut parents = Vec::<&Path>::new();
+    for entry in entries {
+        if !parents.contains(&entry.parent.as_path()) {
+            parents.push(&entry.parent);
+        }
+    }
+    for &parent in &parents {
+        match is_read_only_mount(parent) {
+            Ok(true) => {
+                findings.push(refused(
+                    parent,
+                    io::ErrorKind::ReadOnlyFilesystem,
+                    "file system is mounted read-only",
+                ));
+                continue;
+            }
+            Ok(false) => {}
+            Err(error) => findings.push(found(Severity::Warning, parent, error)),
+        }
+        if let Err(error) = check_access(parent, true) {
+            findings.push(found(Severity::Error, parent, error));
+        }
+        findings.extend(flagged(parent, "directory"));
+    }
+
+    let user = effective_uid();
+    for entry in entries {
+        // The system refuses to rename a mount point, possibly after the other entry was renamed.
+        match mounts.is_mount_point(&entry.path, &entry.parent) {
+            Ok(true) => findings.push(Finding::new(
+                Severity::Error,
+                ExchangeError::new(RenameError::MountPoint, Phase::Preflight)
+                    .with_path(&entry.path),
+            )),
+            Ok(false) => {}
+            Err(error) => findings.push(found(Severity::Warning, &entry.path, error)),
+        }
+        // Symbolic links carry no flags of their own.
+        if entry.kind != EntryKind::Symlink {
+            findings.extend(flagged(&entry.path, "entry"));
+        }
+        let owners = fs::metadata(&entry.parent).and_then(|parent| {
+            let metadata = fs::symlink_metadata(&entry.path)?;
+            Ok((parent.mode() & S_ISVTX != 0, [parent.uid(), metadata.uid()]))
+        });
+        match owners {
+            Ok((true, owners)) if user != 0 && !owners.contains(&user) => findings.push(refused(
+                &entry.path,
+                io::ErrorKind::PermissionDenied,
+                "sticky directory only lets the owner of the entry or directory rename it",
+            )),
+            Ok(_) => {}
+            Err(error) => findings.push(found(Severity::Warning, &entry.path, error)),
+        }
+    }
+    findings
+}
+
+#[cfg(not(unix))]
+fn preflight_findings(_entries: &[&Entry], _mounts: &MountTable) -> Vec<Finding> {
+    Vec::new()
+}
@@ -0,0 +1,134 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::Path,
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    identity::Identity,
+    resolver::{base_dir, resolve},
+    ExchangeError, ExchangeOptions, Phase, RenameError,
+};
+
+/// State an entry must still be in when it is exchanged.
+///
+/// Register one per path with [`ExchangeOptions::expect`]. Unset properties are not checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Precondition {
+    identity: Option<Identity>,
+    size: Option<u64>,
+    modified: Option<SystemTime>,
+    sha256: Option<[u8; 32]>,
+}
+
+impl Precondition {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Captures the device and inode numbers (on Unix), size, and modification time of `path`
+    /// without following a final symbolic link.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of reading the entry's metadata.
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let metadata = fs::symlink_metadata(path)?;
+        Ok(Self {
+            identity: Identity::of(path).ok(),
+            size: Some(metadata.len()),
+            modified: Some(metadata.modified()?),
+            sha256: None,
+        })
+    }
+
+    /// Expects the entry to have these device and inode numbers; only checkable on Unix.
+    #[must_use]
+    pub fn identity(mut self, dev: u64, ino: u64) -> Self {
+        self.identity = Some(Identity { dev, ino });
+        self
+    }
+
+    /// Expects the entry's size in bytes, as reported by its metadata.
+    #[must_use]
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Expects the entry's modification time.
+    #[must_use]
+    pub fn modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// Expects a regular file whose contents have this SHA-256 digest.
+    #[must_use]
+    pub fn sha256(mut self, digest: [u8; 32]) -> Self {
+        self.sha256 = Some(digest);
+        self
+    }
+
+    /// Returns the SHA-256 digest of the file at `path`, for [`sha256`](Self::sha256).
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of opening or reading the file.
+    pub fn file_sha256(path: &Path) -> io::Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(path)?, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Fails with [`RenameError::Changed`] in `phase` unless `path` matches every expectation.
+    pub(crate) fn verify(&self, path: &Path, phase: Phase) -> Result<(), ExchangeError> {
+        let changed = || ExchangeError::new(RenameError::Changed, phase).with_path(path);
+        let inspect = |error| ExchangeError::io(Phase::Inspect, path, error);
+        let metadata = match fs::symlink_metadata(path) {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(changed()),
+            Err(error) => return Err(inspect(error)),
+        };
+        if let Some(identity) = self.identity {
+            if Identity::of(path).map_err(inspect)? != identity {
+                return Err(changed());
+            }
+        }
+        if self.size.is_some_and(|size| size != metadata.len()) {
+            return Err(changed());
+        }
+        if let Some(modified) = self.modified {
+            if metadata.modified().map_err(inspect)? != modified {
+                return Err(changed());
+            }
+        }
+        if let Some(digest) = self.sha256 {
+            if !metadata.is_file() || Self::file_sha256(path).map_err(inspect)? != digest {
+                return Err(changed());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the precondition registered for the resolved `path`; later registrations win.
+pub(crate) fn lookup(
+    options: &ExchangeOptions,
+    path: &Path,
+) -> Result<Option<Precondition>, ExchangeError> {
+    if options.preconditions.is_empty() {
+        return Ok(None);
+    }
+    let base_dir = base_dir(options)?;
+    for (expected, precondition) in options.preconditions.iter().rev() {
+        if resolve(expected, &base_dir)?.into_path() == path {
+            return Ok(Some(precondition.clone()));
+        }
+    }
+    Ok(None)
+}
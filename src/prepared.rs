@@ -0,0 +1,197 @@
+use std::{
+    mem,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    history, journal,
+    lock::PathLock,
+    plan::{ExchangePlan, PlannedExchange},
+    transaction::{self, Recovery, Staged, TEMP_DIR_PREFIX},
+    ExchangeError, ExchangeOptions, Phase,
+};
+
+/// An exchange whose second entry waits in a temporary directory for [`commit`](Self::commit)
+/// or [`abort`](Self::abort).
+///
+/// Created by [`PlannedExchange::prepare`]. The pair stays locked until the exchange is
+/// committed, aborted, or dropped; dropping it aborts. A journal beside the staged entry lets
+/// [`recover`](Self::recover) finish or undo the exchange if the process dies in between.
+#[derive(Debug)]
+pub struct PreparedExchange {
+    plan: ExchangePlan,
+    options: ExchangeOptions,
+    /// `None` when the operation ID shows the exchange already completed.
+    staged: Option<Staged>,
+    /// The history record written when the exchange was prepared; recovered exchanges have none.
+    pending: history::Pending,
+    _lock: PathLock,
+}
+
+/// What [`PreparedExchange::recover`] found in a journal directory.
+#[derive(Debug)]
+pub enum Recovered {
+    /// The exchange is staged again and waits for a decision.
+    Prepared(Box<PreparedExchange>),
+    /// The exchange had been committed; its leftovers were removed.
+    Committed,
+    /// The exchange had been aborted or never staged; its leftovers were removed.
+    Aborted,
+}
+
+impl PlannedExchange {
+    /// Validates the entries again and moves the second one into a temporary directory.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`execute`](Self::execute) before its first rename, or with the error of
+    /// writing the journal or staging the entry, in which case nothing was renamed.
+    pub fn prepare(self) -> Result<PreparedExchange, ExchangeError> {
+        let pair = self.plan.pair();
+        let lock = pair.lock(&self.options)?;
+        if history::completed(&self.options, [&pair])? {
+            return Ok(PreparedExchange {
+                plan: self.plan,
+                options: self.options,
+                staged: None,
+                pending: history::Pending::default(),
+                _lock: lock,
+            });
+        }
+        let plan = self.plan.revalidate(pair, &self.options)?;
+        let pending = history::begin(&self.options, &plan)?;
+        let observer = self.options.observer.as_ref();
+        let staged = match transaction::prepare(
+            &plan,
+            observer,
+            self.options.verify,
+            self.options.durability,
+        ) {
+            Ok(staged) => staged,
+            Err(error) => {
+                pending.abort(observer);
+                return Err(error);
+            }
+        };
+        Ok(PreparedExchange {
+            plan,
+            options: self.options,
+            staged: Some(staged),
+            pending,
+            _lock: lock,
+        })
+    }
+}
+
+impl PreparedExchange {
+    /// Returns each entry's original path and the path it is renamed to on commit.
+    #[must_use]
+    pub fn steps(&self) -> impl ExactSizeIterator<Item = (&Path, &Path)> {
+        [&self.plan.first, &self.plan.second]
+            .into_iter()
+            .map(|step| (step.source.as_path(), step.target.as_path()))
+    }
+
+    /// Returns the temporary directory holding the staged entry and its journal, which
+    /// [`recover`](Self::recover) takes after a crash.
+    #[must_use]
+    pub fn journal_dir(&self) -> Option<&Path> {
+        self.staged.as_ref().map(|staged| staged.temp_dir.as_path())
+    }
+
+    /// Finishes the exchange, which [`prepare`](PlannedExchange::prepare) recorded in the
+    /// configured history.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`exchange_with`](crate::exchange_with) once its first rename succeeded; the
+    /// original names are restored unless [`RenameError::RollbackFailed`](crate::RenameError)
+    /// is returned.
+    pub fn commit(mut self) -> Result<(), ExchangeError> {
+        let Some(staged) = self.staged.take() else {
+            return Ok(());
+        };
+        let observer = self.options.observer.as_ref();
+        transaction::commit(&self.plan, &staged, observer)
+            .inspect_err(|_| mem::take(&mut self.pending).abort(observer))
+    }
+
+    /// Moves the staged entry back, leaving both entries under their original names.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first rename that failed; the entry then stays in the journal directory.
+    pub fn abort(mut self) -> Result<(), ExchangeError> {
+        match self.staged.take() {
+            Some(staged) => self.abort_staged(&staged),
+            None => Ok(()),
+        }
+    }
+
+    /// Moves the staged entry back and, once it is back, marks the recorded exchange as never
+    /// run.
+    fn abort_staged(&mut self, staged: &Staged) -> Result<(), ExchangeError> {
+        let observer = self.options.observer.as_ref();
+        transaction::abort(&self.plan, staged, observer)?;
+        mem::take(&mut self.pending).abort(observer);
+        Ok(())
+    }
+
+    /// Resumes the exchange journaled in `journal_dir` by a process that died before it was
+    /// committed or aborted.
+    ///
+    /// A staged exchange is returned for the caller to commit or abort; a commit that was
+    /// interrupted part-way continues where it stopped. Otherwise the journal directory is
+    /// removed.
+    ///
+    /// # Errors
+    ///
+    /// Fails in [`Phase::Inspect`] when the journal cannot be read, or like locking the pair.
+    pub fn recover(
+        journal_dir: &Path,
+        options: &ExchangeOptions,
+    ) -> Result<Recovered, ExchangeError> {
+        let (plan, _) = transaction::recover(journal_dir, options.durability)?;
+        let lock = plan.pair().lock(options)?;
+        // Read again under the lock, in case another caller recovered it meanwhile.
+        let (plan, recovery) = transaction::recover(journal_dir, options.durability)?;
+        Ok(match recovery {
+            Recovery::Staged(staged) => Recovered::Prepared(Box::new(Self {
+                plan,
+                options: options.clone(),
+                staged: Some(staged),
+                pending: history::Pending::default(),
+                _lock: lock,
+            })),
+            Recovery::Committed => {
+                transaction::discard(journal_dir, options.observer.as_ref());
+                Recovered::Committed
+            }
+            Recovery::Aborted => {
+                transaction::discard(journal_dir, options.observer.as_ref());
+                Recovered::Aborted
+            }
+        })
+    }
+
+    /// Returns the journal directories of prepared exchanges left directly inside `dir`.
+    ///
+    /// Journals are written beside the second entry of each exchange.
+    ///
+    /// # Errors
+    ///
+    /// Fails in [`Phase::Inspect`] when `dir` cannot be listed.
+    pub fn journals_in(dir: &Path) -> Result<Vec<PathBuf>, ExchangeError> {
+        journal::find(dir, TEMP_DIR_PREFIX)
+            .map_err(|error| ExchangeError::io(Phase::Inspect, dir, error))
+    }
+}
+
+impl Drop for PreparedExchange {
+    fn drop(&mut self) {
+        if let Some(staged) = self.staged.take() {
+            // Best effort: a failed abort leaves the journal behind for `recover`.
+            let _ = self.abort_staged(&staged);
+        }
+    }
+}
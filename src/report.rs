@@ -0,0 +1,201 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{ExchangeError, Finding, Phase, PlannedExchange, RenameStage, Severity};
+
+/// One rename: an entry's current path and the path it is renamed to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StepReport {
+    pub from: String,
+    pub to: String,
+}
+
+/// The renames of a planned exchange, with resolved source paths and final names.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlanReport {
+    pub steps: Vec<StepReport>,
+}
+
+/// Result of planning and possibly executing an exchange.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutcomeReport {
+    pub status: ReportStatus,
+    pub code: i32,
+    /// Whether the renames were performed rather than only planned.
+    pub executed: bool,
+    /// Planned renames; empty when planning failed.
+    pub steps: Vec<StepReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorReport>,
+}
+
+/// Findings of [`diagnose_exchange`](crate::diagnose_exchange).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiagnosticsReport {
+    /// [`ReportStatus::Error`] when any finding is an error.
+    pub status: ReportStatus,
+    /// Code of the first error finding, or 0.
+    pub code: i32,
+    pub findings: Vec<FindingReport>,
+}
+
+/// Serializable form of a [`Finding`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FindingReport {
+    pub severity: Severity,
+    #[serde(flatten)]
+    pub error: ErrorReport,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    Ok,
+    Error,
+}
+
+/// Serializable form of an [`ExchangeError`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorReport {
+    /// Same value as [`ExchangeError::to_code`].
+    pub code: i32,
+    pub phase: Phase,
+    /// Description of the [`RenameError`](crate::RenameError) kind.
+    pub kind: String,
+    pub path: Option<String>,
+    pub os_error: Option<i32>,
+    /// The error followed by its chain of causes.
+    pub message: String,
+    /// For a failed rollback, the failure that triggered it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation: Option<Box<ErrorReport>>,
+    /// The other problems the pre-flight checks found; see [`ExchangeError::problems`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub problems: Vec<ErrorReport>,
+}
+
+/// Serializable form of one [`ExchangeObserver`](crate::ExchangeObserver) callback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventReport {
+    TempDirCreated {
+        path: String,
+    },
+    Renamed {
+        stage: RenameStage,
+        from: String,
+        to: String,
+        error: Option<ErrorReport>,
+    },
+    RollbackStarted {
+        cause: ErrorReport,
+    },
+    RollbackStep {
+        from: String,
+        to: String,
+        error: Option<ErrorReport>,
+    },
+    Cleanup {
+        path: String,
+        error: Option<String>,
+    },
+    HistoryFailed {
+        path: String,
+        error: ErrorReport,
+    },
+}
+
+impl StepReport {
+    #[must_use]
+    pub fn new(from: &Path, to: &Path) -> Self {
+        Self {
+            from: lossy(from),
+            to: lossy(to),
+        }
+    }
+}
+
+impl From<&PlannedExchange> for PlanReport {
+    fn from(plan: &PlannedExchange) -> Self {
+        Self {
+            steps: plan
+                .steps()
+                .map(|(from, to)| StepReport::new(from, to))
+                .collect(),
+        }
+    }
+}
+
+impl OutcomeReport {
+    #[must_use]
+    pub fn success(plan: PlanReport, executed: bool) -> Self {
+        Self {
+            status: ReportStatus::Ok,
+            code: 0,
+            executed,
+            steps: plan.steps,
+            error: None,
+        }
+    }
+
+    /// Reports a failure; `plan` is the plan that failed to execute, if planning succeeded.
+    #[must_use]
+    pub fn failure(plan: Option<PlanReport>, error: &ExchangeError) -> Self {
+        Self {
+            status: ReportStatus::Error,
+            code: error.to_code(),
+            executed: false,
+            steps: plan.map(|plan| plan.steps).unwrap_or_default(),
+            error: Some(error.into()),
+        }
+    }
+}
+
+impl From<&[Finding]> for DiagnosticsReport {
+    fn from(findings: &[Finding]) -> Self {
+        let code = findings
+            .iter()
+            .find(|finding| finding.severity() == Severity::Error)
+            .map_or(0, |finding| finding.error().to_code());
+        Self {
+            status: if code == 0 {
+                ReportStatus::Ok
+            } else {
+                ReportStatus::Error
+            },
+            code,
+            findings: findings.iter().map(FindingReport::from).collect(),
+        }
+    }
+}
+
+impl From<&Finding> for FindingReport {
+    fn from(finding: &Finding) -> Self {
+        Self {
+            severity: finding.severity(),
+            error: finding.error().into(),
+        }
+    }
+}
+
+impl From<&ExchangeError> for ErrorReport {
+    fn from(error: &ExchangeError) -> Self {
+        Self {
+            code: error.to_code(),
+            phase: error.phase(),
+            kind: error.kind().to_string(),
+            path: error.path().map(lossy),
+            os_error: error.raw_os_error(),
+            message: error.message(),
+            operation: error
+                .operation()
+                .map(|operation| Box::new(operation.into())),
+            problems: error.problems().skip(1).map(Into::into).collect(),
+        }
+    }
+}
+
+fn lossy(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
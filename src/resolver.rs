@@ -5,18 +5,18 @@ use std::{
     path::{Component, Path, PathBuf},
 };
 
-use crate::RenameError;
+use crate::{ExchangeError, ExchangeOptions, Phase, RenameError};
 
+#[derive(Debug)]
 pub(crate) enum ResolvedPath {
     Existing(PathBuf),
     Missing(PathBuf),
 }
 
 impl ResolvedPath {
-    pub(crate) fn into_existing(self) -> Result<PathBuf, RenameError> {
+    pub(crate) fn into_path(self) -> PathBuf {
         match self {
-            Self::Existing(path) => Ok(path),
-            Self::Missing(_) => Err(RenameError::NotExists),
+            Self::Existing(path) | Self::Missing(path) => path,
         }
     }
 
@@ -28,17 +28,31 @@ impl ResolvedPath {
     }
 }
 
-pub(crate) fn current_base_dir() -> Result<PathBuf, RenameError> {
-    env::current_dir().map_err(RenameError::from)
+fn current_base_dir() -> Result<PathBuf, ExchangeError> {
+    env::current_dir().map_err(|error| ExchangeError::io(Phase::Resolve, Path::new("."), error))
+}
+
+/// Returns the configured base directory, falling back to the working directory.
+pub(crate) fn base_dir(options: &ExchangeOptions) -> Result<PathBuf, ExchangeError> {
+    match &options.base_dir {
+        Some(base_dir) if base_dir.is_absolute() => Ok(base_dir.clone()),
+        Some(base_dir) => Err(ExchangeError::new(
+            RenameError::InvalidPath("base directory must be absolute".to_owned()),
+            Phase::Resolve,
+        )
+        .with_path(base_dir)),
+        None => current_base_dir(),
+    }
 }
 
 /// Resolves parents while preserving a symbolic link in the final component.
-pub(crate) fn resolve(path: &Path, base_dir: &Path) -> Result<ResolvedPath, RenameError> {
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", ret, err))]
+pub(crate) fn resolve(path: &Path, base_dir: &Path) -> Result<ResolvedPath, ExchangeError> {
     if path.as_os_str().is_empty() {
         return Ok(ResolvedPath::Missing(path.to_path_buf()));
     }
 
-    let expanded = expand_home(path)?;
+    let expanded = expand_home(path).map_err(|error| error.with_path(path))?;
     let absolute = if expanded.is_absolute() {
         expanded
     } else {
@@ -52,22 +66,22 @@ pub(crate) fn resolve(path: &Path, base_dir: &Path) -> Result<ResolvedPath, Rena
         Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
             Ok(ResolvedPath::Missing(resolved))
         }
-        Err(error) => Err(error.into()),
+        Err(error) => Err(ExchangeError::io(Phase::Resolve, &resolved, error)),
     }
 }
 
-fn resolve_parent(path: PathBuf) -> Result<PathBuf, RenameError> {
+fn resolve_parent(path: PathBuf) -> Result<PathBuf, ExchangeError> {
     let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
         return Ok(path);
     };
     match parent.canonicalize() {
         Ok(parent) => Ok(parent.join(name)),
         Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(path),
-        Err(error) => Err(error.into()),
+        Err(error) => Err(ExchangeError::io(Phase::Resolve, parent, error)),
     }
 }
 
-fn expand_home(path: &Path) -> Result<PathBuf, RenameError> {
+fn expand_home(path: &Path) -> Result<PathBuf, ExchangeError> {
     let mut components = path.components();
     if !matches!(components.next(), Some(Component::Normal(part)) if part == OsStr::new("~")) {
         return Ok(path.to_path_buf());
@@ -75,22 +89,26 @@ fn expand_home(path: &Path) -> Result<PathBuf, RenameError> {
 
     let variable = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
     let home = env::var_os(variable).ok_or_else(|| {
-        RenameError::InvalidPath(format!("{variable} is not set; cannot expand '~'"))
+        ExchangeError::new(
+            RenameError::InvalidPath(format!("{variable} is not set; cannot expand '~'")),
+            Phase::Resolve,
+        )
     })?;
     let mut expanded = PathBuf::from(home);
     expanded.extend(components);
     Ok(expanded)
 }
 
-fn normalize_lexically(path: &Path) -> Result<PathBuf, RenameError> {
+fn normalize_lexically(path: &Path) -> Result<PathBuf, ExchangeError> {
     let mut result = PathBuf::new();
     for component in path.components() {
         match component {
             Component::ParentDir if !result.pop() => {
-                return Err(RenameError::InvalidPath(format!(
-                    "path escapes its root: {}",
-                    path.display()
-                )));
+                return Err(ExchangeError::new(
+                    RenameError::InvalidPath(format!("path escapes its root: {}", path.display())),
+                    Phase::Resolve,
+                )
+                .with_path(path));
             }
             Component::CurDir | Component::ParentDir => {}
             other => result.push(other.as_os_str()),
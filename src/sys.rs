@@ -0,0 +1,307 @@
+use std::{
+    ffi::{CString, OsStr},
+    io,
+    mem::MaybeUninit,
+    os::{
+        fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+        unix::ffi::OsStrExt,
+    },
+    path::Path,
+};
+
+use crate::{entry::EntryKind, identity::Identity};
+
+/// Inode flags that stop an entry from being renamed or a directory's entries from changing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct InodeFlags {
+    pub(crate) immutable: bool,
+    pub(crate) append_only: bool,
+}
+
+/// Checks with the effective user and group IDs that `path` grants write permission, and also
+/// search permission when `search` is set.
+pub(crate) fn check_access(path: &Path, search: bool) -> io::Result<()> {
+    let path = c_path(path)?;
+    let mode = if search {
+        libc::W_OK | libc::X_OK
+    } else {
+        libc::W_OK
+    };
+    #[cfg(not(target_os = "android"))]
+    // SAFETY: `path` is NUL-terminated and outlives the call.
+    let result = unsafe { libc::faccessat(libc::AT_FDCWD, path.as_ptr(), mode, libc::AT_EACCESS) };
+    // Android's C library lacks `AT_EACCESS`; apps there do not change their user IDs.
+    #[cfg(target_os = "android")]
+    // SAFETY: `path` is NUL-terminated and outlives the call.
+    let result = unsafe { libc::access(path.as_ptr(), mode) };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Checks with the effective user and group IDs that the open directory `dir` grants write and
+/// search permission.
+pub(crate) fn check_access_at(dir: BorrowedFd<'_>) -> io::Result<()> {
+    let mode = libc::W_OK | libc::X_OK;
+    #[cfg(not(target_os = "android"))]
+    let flags = libc::AT_EACCESS;
+    // See `check_access`.
+    #[cfg(target_os = "android")]
+    let flags = 0;
+    // SAFETY: The name is NUL-terminated and `dir` is open for the call.
+    if unsafe { libc::faccessat(dir.as_raw_fd(), c".".as_ptr(), mode, flags) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns whether the file system holding `path` is mounted read-only.
+pub(crate) fn is_read_only_mount(path: &Path) -> io::Result<bool> {
+    let path = c_path(path)?;
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `path` is NUL-terminated and `stats` is valid for writing one `statvfs`.
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `statvfs` succeeded, so it initialized `stats`.
+    let stats = unsafe { stats.assume_init() };
+    Ok(stats.f_flag & libc::ST_RDONLY != 0)
+}
+
+/// Returns whether the file system holding the open directory `dir` is mounted read-only.
+pub(crate) fn is_read_only_mount_at(dir: BorrowedFd<'_>) -> io::Result<bool> {
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: `dir` is open for the call and `stats` is valid for writing one `statvfs`.
+    if unsafe { libc::fstatvfs(dir.as_raw_fd(), stats.as_mut_ptr()) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fstatvfs` succeeded, so it initialized `stats`.
+    let stats = unsafe { stats.assume_init() };
+    Ok(stats.f_flag & libc::ST_RDONLY != 0)
+}
+
+/// Like [`name_max`], for the file system holding the open directory `dir`.
+pub(crate) fn name_max_at(dir: BorrowedFd<'_>) -> Option<usize> {
+    // SAFETY: `dir` is open for the call.
+    let max = unsafe { libc::fpathconf(dir.as_raw_fd(), libc::_PC_NAME_MAX) };
+    usize::try_from(max).ok()
+}
+
+/// Returns the longest file name the file system holding `dir` accepts, or `None` when it has
+/// no limit or the limit cannot be determined.
+pub(crate) fn name_max(dir: &Path) -> Option<usize> {
+    let dir = c_path(dir).ok()?;
+    // SAFETY: `dir` is NUL-terminated and outlives the call.
+    let max = unsafe { libc::pathconf(dir.as_ptr(), libc::_PC_NAME_MAX) };
+    usize::try_from(max).ok()
+}
+
+pub(crate) fn effective_uid() -> u32 {
+    // SAFETY: `geteuid` has no preconditions and cannot fail.
+    unsafe { libc::geteuid() }
+}
+
+/// Reads the immutable and append-only flags of `path` without following a final symbolic
+/// link; file systems without such flags report neither.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn inode_flags(path: &Path) -> io::Result<InodeFlags> {
+    use std::{
+        fs::OpenOptions,
+        os::{fd::AsFd, unix::fs::OpenOptionsExt},
+    };
+
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW)
+        .open(path)?;
+    descriptor_flags(file.as_fd())
+}
+
+/// Like [`inode_flags`], for `name` inside the open directory `dir`.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn inode_flags_at(dir: BorrowedFd<'_>, name: &OsStr) -> io::Result<InodeFlags> {
+    use std::os::fd::AsFd;
+
+    descriptor_flags(open_at(dir, name, libc::O_NONBLOCK | libc::O_NOFOLLOW)?.as_fd())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn descriptor_flags(fd: BorrowedFd<'_>) -> io::Result<InodeFlags> {
+    // From `linux/fs.h`.
+    const FS_IMMUTABLE_FL: libc::c_int = 0x10;
+    const FS_APPEND_FL: libc::c_int = 0x20;
+
+    let mut flags: libc::c_int = 0;
+    // SAFETY: `FS_IOC_GETFLAGS` writes one `int` through the pointer, which stays valid for the
+    // call, and `fd` is open for the call.
+    if unsafe { libc::ioctl(fd.as_raw_fd(), libc::FS_IOC_GETFLAGS, &raw mut flags) } == -1 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::ENOTTY | libc::EOPNOTSUPP | libc::EINVAL) => Ok(InodeFlags::default()),
+            _ => Err(error),
+        };
+    }
+    Ok(InodeFlags {
+        immutable: flags & FS_IMMUTABLE_FL != 0,
+        append_only: flags & FS_APPEND_FL != 0,
+    })
+}
+
+/// Reads the immutable and append-only flags of `path` without following a final symbolic
+/// link.
+#[cfg(target_os = "macos")]
+pub(crate) fn inode_flags(path: &Path) -> io::Result<InodeFlags> {
+    use std::os::macos::fs::MetadataExt;
+
+    Ok(file_flags(std::fs::symlink_metadata(path)?.st_flags()))
+}
+
+/// Like [`inode_flags`], for `name` inside the open directory `dir`.
+#[cfg(target_os = "macos")]
+pub(crate) fn inode_flags_at(dir: BorrowedFd<'_>, name: &OsStr) -> io::Result<InodeFlags> {
+    use std::os::macos::fs::MetadataExt;
+
+    let file = std::fs::File::from(open_at(dir, name, libc::O_NONBLOCK | libc::O_NOFOLLOW)?);
+    Ok(file_flags(file.metadata()?.st_flags()))
+}
+
+#[cfg(target_os = "macos")]
+fn file_flags(flags: u32) -> InodeFlags {
+    InodeFlags {
+        immutable: flags & (libc::UF_IMMUTABLE | libc::SF_IMMUTABLE) != 0,
+        append_only: flags & (libc::UF_APPEND | libc::SF_APPEND) != 0,
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+pub(crate) fn inode_flags(_path: &Path) -> io::Result<InodeFlags> {
+    Ok(InodeFlags::default())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+pub(crate) fn inode_flags_at(_dir: BorrowedFd<'_>, _name: &OsStr) -> io::Result<InodeFlags> {
+    Ok(InodeFlags::default())
+}
+
+/// What [`stat_at`] reads about an entry.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EntryStat {
+    /// `None` for entries that cannot be exchanged.
+    pub(crate) kind: Option<EntryKind>,
+    pub(crate) identity: Identity,
+    pub(crate) uid: u32,
+}
+
+/// Reads `name` inside `dir` without following a final symbolic link.
+pub(crate) fn stat_at(dir: BorrowedFd<'_>, name: &OsStr) -> io::Result<EntryStat> {
+    let name = c_path(Path::new(name))?;
+    let mut stat = MaybeUninit::<libc::stat>::uninit();
+    // SAFETY: `name` is NUL-terminated, `dir` is open for the call, and `stat` is valid for
+    // writing one `stat`.
+    let result = unsafe {
+        libc::fstatat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            stat.as_mut_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fstatat` succeeded, so it initialized `stat`.
+    let stat = unsafe { stat.assume_init() };
+    let kind = match stat.st_mode & libc::S_IFMT {
+        libc::S_IFREG => Some(EntryKind::File),
+        libc::S_IFDIR => Some(EntryKind::Directory),
+        libc::S_IFLNK => Some(EntryKind::Symlink),
+        _ => None,
+    };
+    Ok(EntryStat {
+        kind,
+        identity: Identity {
+            dev: widen(stat.st_dev)?,
+            ino: widen(stat.st_ino)?,
+        },
+        uid: stat.st_uid,
+    })
+}
+
+/// Renames `from` inside `from_dir` to `to` inside `to_dir`, replacing an existing `to`.
+pub(crate) fn rename_at(
+    from_dir: BorrowedFd<'_>,
+    from: &Path,
+    to_dir: BorrowedFd<'_>,
+    to: &Path,
+) -> io::Result<()> {
+    let (from, to) = (c_path(from)?, c_path(to)?);
+    // SAFETY: Both names are NUL-terminated and both descriptors are open for the call.
+    let result = unsafe {
+        libc::renameat(
+            from_dir.as_raw_fd(),
+            from.as_ptr(),
+            to_dir.as_raw_fd(),
+            to.as_ptr(),
+        )
+    };
+    if result == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Creates the directory `name` inside `dir`, accessible only to its owner.
+pub(crate) fn create_dir_at(dir: BorrowedFd<'_>, name: &OsStr) -> io::Result<()> {
+    let name = c_path(Path::new(name))?;
+    // SAFETY: `name` is NUL-terminated and `dir` is open for the call.
+    if unsafe { libc::mkdirat(dir.as_raw_fd(), name.as_ptr(), 0o700) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Removes the empty directory `name` inside `dir`.
+pub(crate) fn remove_dir_at(dir: BorrowedFd<'_>, name: &OsStr) -> io::Result<()> {
+    let name = c_path(Path::new(name))?;
+    // SAFETY: `name` is NUL-terminated and `dir` is open for the call.
+    if unsafe { libc::unlinkat(dir.as_raw_fd(), name.as_ptr(), libc::AT_REMOVEDIR) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Opens the directory `name` inside `dir` without following a symbolic link.
+pub(crate) fn open_dir_at(dir: BorrowedFd<'_>, name: &OsStr) -> io::Result<OwnedFd> {
+    open_at(dir, name, libc::O_DIRECTORY | libc::O_NOFOLLOW)
+}
+
+/// Opens `name` inside `dir` for reading, with `flags` added.
+fn open_at(dir: BorrowedFd<'_>, name: &OsStr, flags: libc::c_int) -> io::Result<OwnedFd> {
+    let name = c_path(Path::new(name))?;
+    // SAFETY: `name` is NUL-terminated and `dir` is open for the call.
+    let fd = unsafe {
+        libc::openat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            libc::O_RDONLY | libc::O_CLOEXEC | flags,
+        )
+    };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `openat` succeeded, so `fd` is a new descriptor owned by nothing else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Converts a `stat` field, whose type differs between platforms, to 64 bits.
+fn widen<T: TryInto<u64>>(value: T) -> io::Result<u64> {
+    value
+        .try_into()
+        .map_err(|_| io::Error::other("stat field does not fit in 64 bits"))
+}
+
+fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
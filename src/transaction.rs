@@ -1,75 +1,647 @@
 use std::{
-    fs,
-    sync::{Mutex, MutexGuard, OnceLock},
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
 };
 
-use tempfile::Builder;
+use tempfile::{Builder, TempDir};
 
-use crate::{plan::ExchangePlan, RenameError};
+use crate::{
+    identity::Identity,
+    journal::{self, Decision},
+    plan::{ExchangePlan, RenameStep, RotationPlan},
+    Durability, ExchangeError, ExchangeObserver, Phase, Precondition, RenameError, RenameStage,
+};
+
+pub(crate) const TEMP_DIR_PREFIX: &str = ".name-exchange-";
+pub(crate) const STAGED_NAME: &str = "entry";
+
+/// A prepared exchange: the second entry waits in a temporary directory beside its journal.
+#[derive(Debug)]
+pub(crate) struct Staged {
+    pub(crate) temp_dir: PathBuf,
+    /// Whether a commit interrupted by a crash had already moved the first entry.
+    first_moved: bool,
+    /// Identities to verify after committing; recovered exchanges are not verified.
+    snapshot: Option<Snapshot>,
+    durability: Durability,
+}
+
+/// Device and inode numbers of both entries, read just before the first rename.
+#[derive(Debug, Clone, Copy)]
+struct Snapshot {
+    first: Identity,
+    second: Identity,
+}
+
+/// State of a journaled exchange found in a temporary directory.
+#[derive(Debug)]
+pub(crate) enum Recovery {
+    Staged(Staged),
+    Committed,
+    Aborted,
+}
+
+/// Performs the plan; a set `cancelled` flag is honoured up to the first rename.
+///
+/// With `verify`, the result is checked and the entries are exchanged back on a mismatch. A
+/// durable exchange is journaled, prepared, and committed so that a crash can be recovered.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        level = "debug",
+        skip_all,
+        fields(first = ?plan.first.source, second = ?plan.second.source),
+        err
+    )
+)]
+pub(crate) fn execute(
+    plan: &ExchangePlan,
+    observer: &dyn ExchangeObserver,
+    cancelled: Option<&AtomicBool>,
+    verify: bool,
+    durability: Durability,
+) -> Result<(), ExchangeError> {
+    let is_cancelled = || cancelled.is_some_and(|cancelled| cancelled.load(Ordering::Acquire));
+    let cancel = || ExchangeError::new(RenameError::Cancelled, Phase::Rename);
+    if durability != Durability::None {
+        if is_cancelled() {
+            return Err(cancel());
+        }
+        let staged = prepare(plan, observer, verify, durability)?;
+        return commit(plan, &staged, observer);
+    }
 
-static OPERATION_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    let temp_dir = temp_dir_beside(&plan.second.source, observer)?;
+    let temporary = temp_dir.path().to_path_buf();
 
-pub(crate) fn execute(plan: &ExchangePlan) -> Result<(), RenameError> {
-    let _guard = lock_operations();
-    let temp_parent = plan.second.source.parent().ok_or_else(|| {
-        RenameError::InvalidPath(format!(
-            "path has no parent: {}",
-            plan.second.source.display()
-        ))
+    let result = if is_cancelled() {
+        Err(cancel())
+    } else {
+        check_preconditions([&plan.first, &plan.second])
+            .and_then(|()| Snapshot::take(plan, verify))
+            .and_then(|snapshot| {
+                swap(plan, &temporary.join(STAGED_NAME), observer)?;
+                Ok(snapshot)
+            })
+    };
+    cleanup(temp_dir, observer);
+    match result? {
+        Some(snapshot) => snapshot.verify(plan, durability, observer),
+        None => Ok(()),
+    }
+}
+
+/// Journals the plan, then moves the second entry into a temporary directory beside it.
+pub(crate) fn prepare(
+    plan: &ExchangePlan,
+    observer: &dyn ExchangeObserver,
+    verify: bool,
+    durability: Durability,
+) -> Result<Staged, ExchangeError> {
+    let temp_dir = temp_dir_beside(&plan.second.source, observer)?;
+    let temporary = temp_dir.path().join(STAGED_NAME);
+    let result = check_preconditions([&plan.first, &plan.second])
+        .and_then(|()| Snapshot::take(plan, verify))
+        .and_then(|snapshot| {
+            if durability == Durability::Full {
+                sync_entry(&plan.first.source)?;
+                sync_entry(&plan.second.source)?;
+            }
+            journal::write(temp_dir.path(), plan)?;
+            // The journal is only found if the directory holding it survives too.
+            sync(durability, [parent(&plan.second.source)])?;
+            step(
+                observer,
+                RenameStage::ToTemporary,
+                &plan.second.source,
+                &temporary,
+            )?;
+            if let Err(operation) = sync(durability, [parent(&plan.second.source), temp_dir.path()])
+            {
+                observer.rollback_started(&operation);
+                let rollback = undo(observer, &temporary, &plan.second.source);
+                return Err(rolled_back(
+                    operation,
+                    rollback.map_err(|error| vec![error]),
+                ));
+            }
+            Ok(snapshot)
+        });
+    match result {
+        Ok(snapshot) => Ok(Staged {
+            temp_dir: temp_dir.keep(),
+            first_moved: false,
+            snapshot,
+            durability,
+        }),
+        Err(error) => {
+            cleanup(temp_dir, observer);
+            Err(error)
+        }
+    }
+}
+
+/// Finishes a prepared exchange, restoring the original names if a rename fails.
+pub(crate) fn commit(
+    plan: &ExchangePlan,
+    staged: &Staged,
+    observer: &dyn ExchangeObserver,
+) -> Result<(), ExchangeError> {
+    let journal = staged.temp_dir.as_path();
+    let temporary = journal.join(STAGED_NAME);
+    let result = match journal::mark(journal, Decision::Commit) {
+        Ok(()) => complete(
+            plan,
+            &temporary,
+            staged.first_moved,
+            Some(journal),
+            staged.durability,
+            observer,
+        ),
+        Err(operation) => {
+            observer.rollback_started(&operation);
+            let rollback = restore(
+                plan,
+                &temporary,
+                staged.first_moved,
+                Some(journal),
+                staged.durability,
+                observer,
+            );
+            Err(rolled_back(operation, rollback))
+        }
+    };
+    cleanup_dir(journal, observer);
+    result?;
+    match staged.snapshot {
+        Some(snapshot) => snapshot.verify(plan, staged.durability, observer),
+        None => Ok(()),
+    }
+}
+
+/// Gives a prepared exchange's entries their original names back.
+///
+/// Every rename is attempted; the first failure is returned and all are reported to `observer`.
+pub(crate) fn abort(
+    plan: &ExchangePlan,
+    staged: &Staged,
+    observer: &dyn ExchangeObserver,
+) -> Result<(), ExchangeError> {
+    let journal = staged.temp_dir.as_path();
+    let result = restore(
+        plan,
+        &journal.join(STAGED_NAME),
+        staged.first_moved,
+        Some(journal),
+        staged.durability,
+        observer,
+    );
+    cleanup_dir(journal, observer);
+    result.map_err(|failures| {
+        failures
+            .into_iter()
+            .next()
+            .expect("restore reports failures")
+    })
+}
+
+/// Reads the journal in `temp_dir` and works out how far its exchange got.
+///
+/// Entries only leave the temporary directory after a decision was journaled, and the first
+/// entry only moves after a commit was, so the decision and the staged entry tell the state.
+pub(crate) fn recover(
+    temp_dir: &Path,
+    durability: Durability,
+) -> Result<(ExchangePlan, Recovery), ExchangeError> {
+    let (plan, decision) = journal::read(temp_dir)?;
+    let recovery = if exists(&temp_dir.join(STAGED_NAME))? {
+        Recovery::Staged(Staged {
+            temp_dir: temp_dir.to_path_buf(),
+            first_moved: decision.is_some() && !exists(&plan.first.source)?,
+            snapshot: None,
+            durability,
+        })
+    } else if decision == Some(Decision::Commit) {
+        Recovery::Committed
+    } else {
+        Recovery::Aborted
+    };
+    Ok((plan, recovery))
+}
+
+/// Removes the journal and temporary directory of a recovered exchange that is no longer staged.
+pub(crate) fn discard(temp_dir: &Path, observer: &dyn ExchangeObserver) {
+    cleanup_dir(temp_dir, observer);
+}
+
+/// Performs a rotation, renaming entries whose target is free first and stashing an entry in a
+/// temporary directory beside it only to break a cycle. A set `cancelled` flag is honoured up to
+/// the first rename.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, fields(entries = plan.steps.len()), err)
+)]
+pub(crate) fn execute_rotation(
+    plan: &RotationPlan,
+    observer: &dyn ExchangeObserver,
+    cancelled: Option<&AtomicBool>,
+) -> Result<(), ExchangeError> {
+    if cancelled.is_some_and(|cancelled| cancelled.load(Ordering::Acquire)) {
+        return Err(ExchangeError::new(RenameError::Cancelled, Phase::Rename));
+    }
+    check_preconditions(&plan.steps)?;
+    let mut temp_dirs = Vec::new();
+    let mut completed = Vec::new();
+    let result = rotate(plan, &mut temp_dirs, &mut completed, observer).map_err(|operation| {
+        observer.rollback_started(&operation);
+        let failures = completed
+            .iter()
+            .rev()
+            .filter_map(|(from, to): &(PathBuf, PathBuf)| undo(observer, to, from).err())
+            .collect::<Vec<_>>();
+        if failures.is_empty() {
+            operation
+        } else {
+            ExchangeError::rollback_failed(operation, failures)
+        }
+    });
+    for temp_dir in temp_dirs {
+        cleanup(temp_dir, observer);
+    }
+    result
+}
+
+fn rotate(
+    plan: &RotationPlan,
+    temp_dirs: &mut Vec<TempDir>,
+    completed: &mut Vec<(PathBuf, PathBuf)>,
+    observer: &dyn ExchangeObserver,
+) -> Result<(), ExchangeError> {
+    let mut pending = plan
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(index, rename)| (index, rename.source.clone()))
+        .collect::<Vec<_>>();
+    while !pending.is_empty() {
+        let ready = pending.iter().position(|&(index, _)| {
+            !pending
+                .iter()
+                .any(|(other, current)| *other != index && *current == plan.steps[index].target)
+        });
+        if let Some(position) = ready {
+            let (index, current) = pending.remove(position);
+            let rename = &plan.steps[index];
+            let stage = if current == rename.source {
+                RenameStage::FirstToTarget
+            } else {
+                RenameStage::TemporaryToTarget
+            };
+            step(observer, stage, &current, &rename.target)?;
+            completed.push((current, rename.target.clone()));
+        } else {
+            // Every remaining target is still occupied by another remaining entry: a cycle.
+            let (index, current) = &mut pending[0];
+            let temporary = stash_slot(temp_dirs, &plan.steps[*index].source, *index, observer)?;
+            step(observer, RenameStage::ToTemporary, current, &temporary)?;
+            completed.push((current.clone(), temporary.clone()));
+            *current = temporary;
+        }
+    }
+    Ok(())
+}
+
+/// Returns an unused path in a temporary directory beside `source`, creating the directory once.
+fn stash_slot(
+    temp_dirs: &mut Vec<TempDir>,
+    source: &Path,
+    index: usize,
+    observer: &dyn ExchangeObserver,
+) -> Result<PathBuf, ExchangeError> {
+    let parent = source.parent();
+    let existing = temp_dirs
+        .iter()
+        .position(|temp_dir| temp_dir.path().parent() == parent);
+    let position = if let Some(position) = existing {
+        position
+    } else {
+        temp_dirs.push(temp_dir_beside(source, observer)?);
+        temp_dirs.len() - 1
+    };
+    Ok(temp_dirs[position].path().join(format!("entry-{index}")))
+}
+
+fn temp_dir_beside(path: &Path, observer: &dyn ExchangeObserver) -> Result<TempDir, ExchangeError> {
+    let temp_parent = path.parent().ok_or_else(|| {
+        ExchangeError::new(
+            RenameError::InvalidPath(format!("path has no parent: {}", path.display())),
+            Phase::Rename,
+        )
+        .with_path(path)
     })?;
     let temp_dir = Builder::new()
-        .prefix(".name-exchange-")
+        .prefix(TEMP_DIR_PREFIX)
         .tempdir_in(temp_parent)
-        .map_err(RenameError::from)?;
-    let temporary = temp_dir.path().join("entry");
-
-    rename(&plan.second.source, &temporary)?;
-    if let Err(operation) = rename(&plan.first.source, &plan.first.target) {
-        return match rename(&temporary, &plan.second.source) {
-            Ok(()) => Err(operation),
-            Err(rollback) => Err(rollback_failed(&operation, &rollback)),
+        .map_err(|error| ExchangeError::io(Phase::Rename, temp_parent, error))?;
+    observer.temp_dir_created(temp_dir.path());
+    Ok(temp_dir)
+}
+
+fn swap(
+    plan: &ExchangePlan,
+    temporary: &Path,
+    observer: &dyn ExchangeObserver,
+) -> Result<(), ExchangeError> {
+    step(
+        observer,
+        RenameStage::ToTemporary,
+        &plan.second.source,
+        temporary,
+    )?;
+    complete(plan, temporary, false, None, Durability::None, observer)
+}
+
+/// Moves the first entry to its target, unless `first_moved`, then the staged second entry to
+/// its target, restoring the original names if either rename or sync fails.
+fn complete(
+    plan: &ExchangePlan,
+    temporary: &Path,
+    first_moved: bool,
+    journal: Option<&Path>,
+    durability: Durability,
+    observer: &dyn ExchangeObserver,
+) -> Result<(), ExchangeError> {
+    let fail = |operation: ExchangeError, first_moved: bool| {
+        observer.rollback_started(&operation);
+        let rollback = restore(plan, temporary, first_moved, journal, durability, observer);
+        Err(rolled_back(operation, rollback))
+    };
+    if !first_moved {
+        if let Err(operation) = step(
+            observer,
+            RenameStage::FirstToTarget,
+            &plan.first.source,
+            &plan.first.target,
+        ) {
+            return fail(operation, false);
+        }
+        if let Err(operation) = sync(
+            durability,
+            [parent(&plan.first.source), parent(&plan.first.target)],
+        ) {
+            return fail(operation, true);
+        }
+    }
+
+    if let Err(operation) = step(
+        observer,
+        RenameStage::TemporaryToTarget,
+        temporary,
+        &plan.second.target,
+    ) {
+        return fail(operation, true);
+    }
+    if let Err(operation) = sync(durability, [parent(temporary), parent(&plan.second.target)]) {
+        observer.rollback_started(&operation);
+        // Put the second entry back where `restore` expects it.
+        if let Err(error) = undo(observer, &plan.second.target, temporary) {
+            return Err(ExchangeError::rollback_failed(operation, vec![error]));
+        }
+        let rollback = restore(plan, temporary, true, journal, durability, observer);
+        return Err(rolled_back(operation, rollback));
+    }
+    Ok(())
+}
+
+/// Moves the first entry back if it was moved, then the staged second entry.
+fn restore(
+    plan: &ExchangePlan,
+    temporary: &Path,
+    first_moved: bool,
+    journal: Option<&Path>,
+    durability: Durability,
+    observer: &dyn ExchangeObserver,
+) -> Result<(), Vec<ExchangeError>> {
+    if let Some(journal) = journal {
+        // Renaming back matters more than the marker, which only matters if this process dies
+        // between the last rename and removing the journal.
+        let _ = journal::mark(journal, Decision::Abort);
+    }
+    let first_rollback = if first_moved {
+        undo(observer, &plan.first.target, &plan.first.source)
+    } else {
+        Ok(())
+    };
+    let second_rollback = undo(observer, temporary, &plan.second.source);
+    let failures = [first_rollback.err(), second_rollback.err()]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    if failures.is_empty() {
+        // The names are back either way; a failed sync leaves the abort journaled for recovery.
+        let _ = sync(
+            durability,
+            [
+                parent(&plan.first.source),
+                parent(&plan.first.target),
+                parent(temporary),
+                parent(&plan.second.source),
+            ],
+        );
+        Ok(())
+    } else {
+        Err(failures)
+    }
+}
+
+fn rolled_back(
+    operation: ExchangeError,
+    rollback: Result<(), Vec<ExchangeError>>,
+) -> ExchangeError {
+    match rollback {
+        Ok(()) => operation,
+        Err(failures) => ExchangeError::rollback_failed(operation, failures),
+    }
+}
+
+impl Snapshot {
+    fn take(plan: &ExchangePlan, verify: bool) -> Result<Option<Self>, ExchangeError> {
+        if !verify {
+            return Ok(None);
+        }
+        let identity = |path: &Path| {
+            Identity::of(path).map_err(|error| ExchangeError::io(Phase::Verify, path, error))
         };
+        Ok(Some(Self {
+            first: identity(&plan.first.source)?,
+            second: identity(&plan.second.source)?,
+        }))
     }
 
-    if let Err(operation) = rename(&temporary, &plan.second.target) {
-        let first_rollback = rename(&plan.first.target, &plan.first.source);
-        let second_rollback = rename(&temporary, &plan.second.source);
-        return match (first_rollback, second_rollback) {
-            (Ok(()), Ok(())) => Err(operation),
-            (first_result, second_result) => {
-                let rollback = [first_result.err(), second_result.err()]
-                    .into_iter()
-                    .flatten()
-                    .map(|error| error.to_string())
-                    .collect::<Vec<_>>()
-                    .join("; ");
-                Err(RenameError::RollbackFailed {
-                    operation: operation.to_string(),
-                    rollback,
-                })
-            }
+    /// Checks that each target holds the entry its source held, exchanging the entries back
+    /// otherwise, but only while each still holds the entry the exchange gave it.
+    fn verify(
+        self,
+        plan: &ExchangePlan,
+        durability: Durability,
+        observer: &dyn ExchangeObserver,
+    ) -> Result<(), ExchangeError> {
+        let mut mismatches = Vec::new();
+        for (step, expected) in [(&plan.first, self.first), (&plan.second, self.second)] {
+            let mismatch = match Identity::of(&step.target) {
+                Ok(identity) if identity == expected => continue,
+                Ok(_) => format!(
+                    "'{}' does not hold the entry from '{}'",
+                    step.target.display(),
+                    step.source.display()
+                ),
+                Err(error) => format!("'{}' cannot be checked: {error}", step.target.display()),
+            };
+            mismatches.push((step.target.as_path(), mismatch));
+        }
+        let Some(&(path, _)) = mismatches.first() else {
+            return Ok(());
         };
+        let detail = mismatches
+            .iter()
+            .map(|(_, mismatch)| mismatch.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+        let error = ExchangeError::new(RenameError::VerificationFailed(detail), Phase::Verify)
+            .with_path(path);
+
+        // Names another writer took over keep their entries; the exchange back fails instead.
+        let mut inverse = plan.inverse();
+        for (step, identity) in [
+            (&mut inverse.first, self.first),
+            (&mut inverse.second, self.second),
+        ] {
+            step.expected = Some(Precondition::new().identity(identity.dev, identity.ino));
+        }
+        observer.rollback_started(&error);
+        match execute(&inverse, observer, None, false, durability) {
+            Ok(()) => Err(error),
+            Err(rollback) => Err(ExchangeError::rollback_failed(error, vec![rollback])),
+        }
     }
+}
 
-    // Retrying after a cleanup-only failure would reverse an already successful exchange.
-    let _ = temp_dir.close();
+/// Checks each source against its precondition once more before anything is renamed.
+fn check_preconditions<'a>(
+    steps: impl IntoIterator<Item = &'a RenameStep>,
+) -> Result<(), ExchangeError> {
+    steps.into_iter().try_for_each(|step| match &step.expected {
+        Some(expected) => expected.verify(&step.source, Phase::Rename),
+        None => Ok(()),
+    })
+}
+
+/// Flushes each distinct directory in `dirs` to storage, unless durability is off.
+fn sync<'a>(
+    durability: Durability,
+    dirs: impl IntoIterator<Item = &'a Path>,
+) -> Result<(), ExchangeError> {
+    if durability == Durability::None {
+        return Ok(());
+    }
+    let mut synced = Vec::new();
+    for dir in dirs {
+        if !synced.contains(&dir) {
+            journal::sync_dir(dir).map_err(|error| ExchangeError::io(Phase::Rename, dir, error))?;
+            synced.push(dir);
+        }
+    }
     Ok(())
 }
 
-fn lock_operations() -> MutexGuard<'static, ()> {
-    OPERATION_LOCK
-        .get_or_init(|| Mutex::new(()))
-        .lock()
-        .unwrap_or_else(std::sync::PoisonError::into_inner)
+/// Flushes a regular file's contents or a directory's listing; symbolic links have nothing to
+/// flush that their parent directory does not hold.
+fn sync_entry(path: &Path) -> Result<(), ExchangeError> {
+    let io_error = |error| ExchangeError::io(Phase::Rename, path, error);
+    let file_type = fs::symlink_metadata(path).map_err(io_error)?.file_type();
+    if file_type.is_file() {
+        File::open(path)
+            .and_then(|file| file.sync_all())
+            .map_err(io_error)
+    } else if file_type.is_dir() {
+        journal::sync_dir(path).map_err(io_error)
+    } else {
+        Ok(())
+    }
 }
 
-fn rename(from: &std::path::Path, to: &std::path::Path) -> Result<(), RenameError> {
-    fs::rename(from, to).map_err(RenameError::from)
+/// Returns the directory holding `path`; planned paths are absolute and always have one.
+fn parent(path: &Path) -> &Path {
+    path.parent().unwrap_or(path)
 }
 
-fn rollback_failed(operation: &RenameError, rollback: &RenameError) -> RenameError {
-    RenameError::RollbackFailed {
-        operation: operation.to_string(),
-        rollback: rollback.to_string(),
+fn exists(path: &Path) -> Result<bool, ExchangeError> {
+    match fs::symlink_metadata(path) {
+        Ok(_) => Ok(true),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(ExchangeError::io(Phase::Inspect, path, error)),
     }
 }
+
+/// Removes the temporary directory unless a failed rollback stranded an entry inside it.
+fn cleanup(temp_dir: TempDir, observer: &dyn ExchangeObserver) {
+    cleanup_dir(&temp_dir.keep(), observer);
+}
+
+/// Removes a kept temporary directory and its journal unless an entry was stranded inside it.
+fn cleanup_dir(path: &Path, observer: &dyn ExchangeObserver) {
+    let stranded = fs::read_dir(path).map_or(true, |mut entries| {
+        entries.any(|entry| entry.map_or(true, |entry| entry.file_name() != journal::FILE_NAME))
+    });
+    if stranded {
+        let error = io::Error::new(
+            io::ErrorKind::DirectoryNotEmpty,
+            "temporary directory still holds an entry and was kept for manual recovery",
+        );
+        observer.cleanup(path, Err(&error));
+        return;
+    }
+
+    // Retrying after a cleanup-only failure would reverse an already successful exchange.
+    let result = journal::remove(path).and_then(|()| fs::remove_dir(path));
+    observer.cleanup(path, result.as_ref().map(|&()| ()));
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "rename", level = "debug", skip(observer), err)
+)]
+fn step(
+    observer: &dyn ExchangeObserver,
+    stage: RenameStage,
+    from: &Path,
+    to: &Path,
+) -> Result<(), ExchangeError> {
+    let result = rename(Phase::Rename, from, to);
+    observer.renamed(stage, from, to, result.as_ref().map(|&()| ()));
+    result
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(name = "rollback", level = "debug", skip(observer), err)
+)]
+fn undo(observer: &dyn ExchangeObserver, from: &Path, to: &Path) -> Result<(), ExchangeError> {
+    let result = rename(Phase::Rollback, from, to);
+    observer.rollback_step(from, to, result.as_ref().map(|&()| ()));
+    result
+}
+
+fn rename(phase: Phase, from: &Path, to: &Path) -> Result<(), ExchangeError> {
+    fs::rename(from, to).map_err(|error| {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            io_error_kind = ?error.kind(),
+            os_error = error.raw_os_error(),
+            "rename failed"
+        );
+        ExchangeError::io(phase, from, error)
+    })
+}
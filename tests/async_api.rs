@@ -0,0 +1,162 @@
+#![cfg(feature = "tokio")]
+
+use std::{fs, path::Path};
+
+use exchange_name_lib::{
+    exchange_async, exchange_batch_async, plan_exchange_async, rotate_async, ExchangeOptions,
+    RenameError,
+};
+use tempfile::TempDir;
+use tokio::{runtime::Runtime, task::JoinSet};
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("build runtime")
+}
+
+fn write(path: &Path, value: &str) {
+    fs::write(path, value).expect("write test file");
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).expect("read test file")
+}
+
+#[test]
+fn exchanges_files_on_blocking_pool() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.txt");
+    let second = dir.path().join("beta.log");
+    write(&first, "A");
+    write(&second, "B");
+
+    runtime()
+        .block_on(exchange_async(&first, &second, true))
+        .expect("exchange files");
+
+    assert_eq!(read(&dir.path().join("beta.txt")), "A");
+    assert_eq!(read(&dir.path().join("alpha.log")), "B");
+}
+
+#[test]
+fn planned_exchange_executes_async() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.txt");
+    let second = dir.path().join("beta.log");
+    write(&first, "A");
+    write(&second, "B");
+
+    runtime()
+        .block_on(async {
+            let plan = plan_exchange_async(&first, &second, &ExchangeOptions::new()).await?;
+            assert_eq!(plan.steps().len(), 2);
+            plan.execute_async().await
+        })
+        .expect("exchange files");
+
+    assert_eq!(read(&first), "B");
+    assert_eq!(read(&second), "A");
+}
+
+#[test]
+fn overlapping_tasks_are_serialized() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.txt");
+    let second = dir.path().join("beta.txt");
+    write(&first, "A");
+    write(&second, "B");
+
+    runtime().block_on(async {
+        let mut tasks = JoinSet::new();
+        for _ in 0..8 {
+            let (first, second) = (first.clone(), second.clone());
+            tasks.spawn(async move { exchange_async(&first, &second, false).await });
+        }
+        while let Some(result) = tasks.join_next().await {
+            result.expect("join task").expect("exchange files");
+        }
+    });
+
+    assert_eq!(read(&first), "A");
+    assert_eq!(read(&second), "B");
+}
+
+#[test]
+fn dropped_future_does_not_rename() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.txt");
+    let second = dir.path().join("beta.txt");
+    write(&first, "A");
+    write(&second, "B");
+
+    drop(exchange_async(&first, &second, false));
+
+    assert_eq!(read(&first), "A");
+    assert_eq!(read(&second), "B");
+}
+
+#[test]
+fn reports_missing_paths() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.txt");
+    write(&first, "A");
+
+    let result = runtime().block_on(exchange_async(&first, &dir.path().join("missing"), false));
+
+    assert_eq!(result, Err(RenameError::NotExists));
+}
+
+#[test]
+fn batches_and_rotations_run_async() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c] = ["a", "b", "c"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    write(&c, "C");
+    let options = ExchangeOptions::new();
+
+    runtime()
+        .block_on(exchange_batch_async(&[(&a, &b), (&b, &c)], &options))
+        .expect("exchange batch");
+    assert_eq!([read(&a), read(&b), read(&c)], ["B", "C", "A"]);
+
+    runtime()
+        .block_on(rotate_async(&[&a, &b, &c], &options))
+        .expect("rotate");
+    assert_eq!([read(&a), read(&b), read(&c)], ["A", "B", "C"]);
+}
+
+#[test]
+fn failed_async_batch_restores_completed_pairs() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c] = ["a", "b", "c"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+
+    let error = runtime()
+        .block_on(exchange_batch_async(
+            &[(&a, &b), (&b, &c)],
+            &ExchangeOptions::new(),
+        ))
+        .expect_err("missing entry");
+
+    assert_eq!(error.index(), Some(1));
+    assert_eq!(error.error().kind(), &RenameError::NotExists);
+    assert_eq!([read(&a), read(&b)], ["A", "B"]);
+}
+
+#[test]
+fn dropped_batch_and_rotation_futures_do_not_rename() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c] = ["a", "b", "c"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    write(&c, "C");
+    let options = ExchangeOptions::new();
+
+    drop(exchange_batch_async(&[(&a, &b)], &options));
+    drop(rotate_async(&[&a, &b, &c], &options));
+
+    assert_eq!([read(&a), read(&b), read(&c)], ["A", "B", "C"]);
+}
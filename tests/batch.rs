@@ -0,0 +1,112 @@
+use std::{fs, path::Path};
+
+use exchange_name_lib::{exchange_batch, rotate, ExchangeOptions, RenameError};
+use tempfile::TempDir;
+
+fn write(path: &Path, value: &str) {
+    fs::write(path, value).expect("write test file");
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).expect("read test file")
+}
+
+#[test]
+fn batch_applies_pairs_in_order() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c] = ["a", "b", "c"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    write(&c, "C");
+
+    exchange_batch(&[(&a, &b), (&b, &c)], &ExchangeOptions::new()).expect("exchange batch");
+
+    assert_eq!(read(&a), "B");
+    assert_eq!(read(&b), "C");
+    assert_eq!(read(&c), "A");
+}
+
+#[test]
+fn failed_batch_restores_completed_pairs() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c, missing] = ["a", "b", "c", "missing"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    write(&c, "C");
+
+    let error = exchange_batch(&[(&a, &b), (&c, &missing)], &ExchangeOptions::new())
+        .expect_err("second pair is missing");
+
+    assert_eq!(error.index(), Some(1));
+    assert_eq!(error.error().kind(), &RenameError::NotExists);
+    assert!(error.unrestored().is_empty());
+    assert_eq!(read(&a), "A");
+    assert_eq!(read(&b), "B");
+    assert_eq!(fs::read_dir(dir.path()).expect("list dir").count(), 3);
+}
+
+#[test]
+fn rotates_names_within_directory() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c] = ["a.txt", "b.txt", "c.txt"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    write(&c, "C");
+
+    rotate(&[&a, &b, &c], &ExchangeOptions::new()).expect("rotate");
+
+    assert_eq!(read(&b), "A");
+    assert_eq!(read(&c), "B");
+    assert_eq!(read(&a), "C");
+    assert_eq!(fs::read_dir(dir.path()).expect("list dir").count(), 3);
+}
+
+#[test]
+fn rotation_across_directories_never_overwrites() {
+    let dir = TempDir::new().expect("create temp dir");
+    let other = dir.path().join("other");
+    fs::create_dir(&other).expect("create other dir");
+    let first = dir.path().join("x");
+    let second = dir.path().join("z");
+    let third = other.join("x");
+    write(&first, "1");
+    write(&second, "2");
+    write(&third, "3");
+
+    rotate(&[&first, &second, &third], &ExchangeOptions::new()).expect("rotate");
+
+    // Each entry stays in its directory and takes the next entry's name.
+    assert_eq!(read(&second), "1");
+    assert_eq!(read(&first), "2");
+    assert_eq!(read(&other.join("x")), "3");
+}
+
+#[test]
+fn rotation_preserves_extensions() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c] = ["a.1", "b.2", "c.3"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    write(&c, "C");
+
+    rotate(&[&a, &b, &c], &ExchangeOptions::new().preserve_ext(true)).expect("rotate");
+
+    assert_eq!(read(&dir.path().join("b.1")), "A");
+    assert_eq!(read(&dir.path().join("c.2")), "B");
+    assert_eq!(read(&dir.path().join("a.3")), "C");
+}
+
+#[test]
+fn rotation_rejects_duplicates_and_single_paths() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    let options = ExchangeOptions::new();
+
+    let error = rotate(&[&a, &b, &a], &options).expect_err("duplicate path");
+    assert_eq!(error.kind(), &RenameError::SamePath);
+    let error = rotate(&[&a], &options).expect_err("single path");
+    assert_eq!(error.to_code(), 5);
+    assert_eq!(read(&a), "A");
+}
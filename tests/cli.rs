@@ -0,0 +1,303 @@
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+use serde_json::Value;
+use tempfile::TempDir;
+
+fn exchange_name(args: &[&str], dir: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_exchange-name"))
+        .args(args)
+        .current_dir(dir)
+        .env("XDG_STATE_HOME", dir.join(".state"))
+        .output()
+        .expect("run exchange-name")
+}
+
+#[test]
+fn exchanges_and_reports_renames() {
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("alpha.txt"), "A").expect("write first");
+    fs::write(dir.path().join("beta.log"), "B").expect("write second");
+
+    let output = exchange_name(&["--preserve-ext", "alpha.txt", "beta.log"], dir.path());
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).lines().count(), 2);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("beta.txt")).expect("read"),
+        "A"
+    );
+}
+
+#[test]
+fn dry_run_prints_json_without_renaming() {
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("a"), "A").expect("write first");
+    fs::write(dir.path().join("b"), "B").expect("write second");
+
+    let output = exchange_name(&["--dry-run", "--json", "a", "b"], dir.path());
+
+    assert!(output.status.success());
+    let report: Value = serde_json::from_slice(&output.stdout).expect("JSON output");
+    assert_eq!(report["status"], "ok");
+    assert_eq!(report["executed"], false);
+    assert_eq!(report["steps"].as_array().map(Vec::len), Some(2));
+    assert_eq!(fs::read_to_string(dir.path().join("a")).expect("read"), "A");
+}
+
+#[test]
+fn jsonl_streams_one_event_per_step() {
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("a"), "A").expect("write first");
+    fs::write(dir.path().join("b"), "B").expect("write second");
+
+    let output = exchange_name(&["--jsonl", "a", "b"], dir.path());
+
+    assert!(output.status.success());
+    let events = String::from_utf8(output.stdout)
+        .expect("UTF-8 output")
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).expect("JSON line"))
+        .collect::<Vec<_>>();
+    let kinds = events
+        .iter()
+        .map(|event| event["event"].as_str().expect("event tag"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            "temp_dir_created",
+            "renamed",
+            "renamed",
+            "renamed",
+            "cleanup",
+            "outcome"
+        ]
+    );
+    assert_eq!(events[1]["stage"], "to_temporary");
+    assert_eq!(events[5]["executed"], true);
+    assert_eq!(fs::read_to_string(dir.path().join("a")).expect("read"), "B");
+
+    let output = exchange_name(&["--json", "--jsonl", "a", "b"], dir.path());
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[cfg(unix)]
+#[test]
+fn undo_restores_recorded_exchanges() {
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("a"), "A").expect("write first");
+    fs::write(dir.path().join("b"), "B").expect("write second");
+
+    assert!(exchange_name(&["--verify", "a", "b"], dir.path())
+        .status
+        .success());
+    assert!(exchange_name(&["--no-history", "a", "b"], dir.path())
+        .status
+        .success());
+    assert!(exchange_name(&["a", "b"], dir.path()).status.success());
+    assert_eq!(fs::read_to_string(dir.path().join("a")).expect("read"), "B");
+
+    let output = exchange_name(&["--json", "--verify", "--durable", "undo"], dir.path());
+    assert!(output.status.success());
+    let report: Value = serde_json::from_slice(&output.stdout).expect("JSON output");
+    assert_eq!(report["executed"], true);
+    assert_eq!(fs::read_to_string(dir.path().join("a")).expect("read"), "A");
+
+    // The unrecorded exchange in between changed which entry holds each name.
+    let output = exchange_name(&["undo", "1"], dir.path());
+    assert_eq!(output.status.code(), Some(16));
+
+    let output = exchange_name(&["undo", "b"], dir.path());
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[cfg(unix)]
+#[test]
+fn unwritable_history_only_warns() {
+    let dir = TempDir::new().expect("create temp dir");
+    write_files(dir.path(), &["a", "b", "c"]);
+    fs::write(dir.path().join("pairs.txt"), "b\tc\n").expect("write pairs");
+    // A file where the state directory should be cannot hold the history.
+    fs::write(dir.path().join("state"), "").expect("write state file");
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_exchange-name"))
+            .args(args)
+            .current_dir(dir.path())
+            .env("XDG_STATE_HOME", dir.path().join("state"))
+            .output()
+            .expect("run exchange-name")
+    };
+
+    let output = run(&["a", "b"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("exchange-name: warning:"));
+    assert_eq!([read(dir.path(), "a"), read(dir.path(), "b")], ["b", "a"]);
+
+    assert!(run(&["--batch", "pairs.txt"]).status.success());
+    assert!(run(&["--batch", "pairs.txt", "--continue-on-error"])
+        .status
+        .success());
+    assert_eq!(read(dir.path(), "b"), "a");
+
+    // An operation ID cannot be honoured without its history.
+    assert!(!run(&["--operation-id", "job", "a", "b"]).status.success());
+    assert_eq!(read(dir.path(), "a"), "b");
+}
+
+#[cfg(unix)]
+#[test]
+fn operation_id_prevents_swapping_back() {
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("a"), "A").expect("write first");
+    fs::write(dir.path().join("b"), "B").expect("write second");
+
+    for _ in 0..2 {
+        let output = exchange_name(&["--operation-id", "job", "a", "b"], dir.path());
+        assert!(output.status.success());
+    }
+    assert_eq!(fs::read_to_string(dir.path().join("a")).expect("read"), "B");
+
+    let output = exchange_name(
+        &["--no-history", "--operation-id", "job", "a", "b"],
+        dir.path(),
+    );
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[test]
+fn exit_code_matches_error_code() {
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("a"), "A").expect("write file");
+
+    let output = exchange_name(&["a", "missing"], dir.path());
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("missing"));
+
+    let output = exchange_name(&["--json", "a", "missing"], dir.path());
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains(r#""code":1"#));
+
+    let output = exchange_name(&["--bogus", "a", "b"], dir.path());
+    assert_eq!(output.status.code(), Some(5));
+}
+
+#[test]
+fn diagnose_lists_every_problem() {
+    let dir = TempDir::new().expect("create temp dir");
+
+    let output = exchange_name(&["--diagnose", "--json", "a", "b"], dir.path());
+
+    assert_eq!(output.status.code(), Some(1));
+    let report: Value = serde_json::from_slice(&output.stdout).expect("JSON output");
+    assert_eq!(report["status"], "error");
+    let findings = report["findings"].as_array().expect("findings");
+    assert_eq!(findings.len(), 2);
+    assert!(findings
+        .iter()
+        .all(|finding| finding["severity"] == "error"));
+
+    write_files(dir.path(), &["a", "b"]);
+    let output = exchange_name(&["--diagnose", "a", "b"], dir.path());
+    assert!(output.status.success());
+    assert_eq!(read(dir.path(), "a"), "a");
+}
+
+fn exchange_name_with_input(args: &[&str], dir: &Path, input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_exchange-name"))
+        .args(args)
+        .current_dir(dir)
+        .env("XDG_STATE_HOME", dir.join(".state"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("spawn exchange-name");
+    child
+        .stdin
+        .take()
+        .expect("stdin")
+        .write_all(input)
+        .expect("write stdin");
+    child.wait_with_output().expect("wait for exchange-name")
+}
+
+fn write_files(dir: &Path, names: &[&str]) {
+    for name in names {
+        fs::write(dir.join(name), name).expect("write file");
+    }
+}
+
+fn read(dir: &Path, name: &str) -> String {
+    fs::read_to_string(dir.join(name)).expect("read file")
+}
+
+#[test]
+fn batch_reads_tab_separated_file() {
+    let dir = TempDir::new().expect("create temp dir");
+    write_files(dir.path(), &["a", "b", "c"]);
+    fs::write(dir.path().join("pairs.txt"), "a\tb\n\nb\tc\r\n").expect("write pairs");
+
+    let output = exchange_name(&["--batch", "pairs.txt"], dir.path());
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("2 exchanged, 0 failed"));
+    assert_eq!(read(dir.path(), "a"), "b");
+    assert_eq!(read(dir.path(), "b"), "c");
+    assert_eq!(read(dir.path(), "c"), "a");
+}
+
+#[test]
+fn batch_is_all_or_nothing_by_default() {
+    let dir = TempDir::new().expect("create temp dir");
+    write_files(dir.path(), &["a", "b", "c"]);
+
+    let output = exchange_name_with_input(
+        &["--batch", "-", "-0", "--json"],
+        dir.path(),
+        b"a\0b\0c\0missing\0",
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).expect("UTF-8 output");
+    assert!(stdout.contains(r#""summary":{"succeeded":0,"failed":1,"skipped":1}"#));
+    assert_eq!(read(dir.path(), "a"), "a");
+}
+
+#[test]
+fn batch_can_continue_after_errors() {
+    let dir = TempDir::new().expect("create temp dir");
+    write_files(dir.path(), &["a", "b", "c", "d"]);
+
+    let output = exchange_name_with_input(
+        &["--batch", "-", "--continue-on-error"],
+        dir.path(),
+        b"a\tb\nc\tmissing\nc\td\n",
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("2 exchanged, 1 failed, 0 not"));
+    assert_eq!(read(dir.path(), "a"), "b");
+    assert_eq!(read(dir.path(), "c"), "d");
+}
+
+#[test]
+fn batch_rejects_malformed_input() {
+    let dir = TempDir::new().expect("create temp dir");
+
+    let output = exchange_name_with_input(&["--batch", "-"], dir.path(), b"only-one-path\n");
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("line 1"));
+
+    let output = exchange_name_with_input(&["--batch", "-", "-0"], dir.path(), b"a\0b\0c");
+    assert_eq!(output.status.code(), Some(5));
+
+    let output = exchange_name(&["--batch", "missing.txt"], dir.path());
+    assert_eq!(output.status.code(), Some(1));
+}
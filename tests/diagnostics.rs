@@ -0,0 +1,74 @@
+use std::fs;
+
+use exchange_name_lib::{
+    diagnose_exchange, ExchangeOptions, Phase, Precondition, RenameError, Severity,
+};
+use tempfile::TempDir;
+
+#[test]
+fn finds_nothing_for_a_valid_pair() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    fs::write(&a, "A").expect("write first");
+    fs::write(&b, "B").expect("write second");
+
+    assert!(diagnose_exchange(&a, &b, &ExchangeOptions::new()).is_empty());
+    assert_eq!(fs::read_to_string(&a).expect("read"), "A");
+}
+
+#[test]
+fn reports_every_missing_path() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+
+    let findings = diagnose_exchange(&a, &b, &ExchangeOptions::new());
+
+    assert_eq!(findings.len(), 2);
+    for (finding, path) in findings.iter().zip([&a, &b]) {
+        assert_eq!(finding.severity(), Severity::Error);
+        assert_eq!(finding.error().kind(), &RenameError::NotExists);
+        assert_eq!(finding.path(), Some(path.as_path()));
+    }
+}
+
+#[test]
+fn keeps_checking_after_the_first_problem() {
+    let dir = TempDir::new().expect("create temp dir");
+    let outer = dir.path().join("outer");
+    let inner = outer.join("inner");
+    fs::create_dir_all(&inner).expect("create directories");
+    let options = ExchangeOptions::new().expect(&inner, Precondition::new().size(u64::MAX));
+
+    let findings = diagnose_exchange(&outer, &inner, &options);
+
+    let kinds: Vec<_> = findings
+        .iter()
+        .map(|finding| finding.error().kind().clone())
+        .collect();
+    assert_eq!(
+        kinds,
+        [RenameError::Changed, RenameError::NestedDirectories]
+    );
+    assert!(findings
+        .iter()
+        .all(|finding| finding.severity() == Severity::Error));
+}
+
+#[test]
+fn reports_target_names_that_are_too_long() {
+    let dir = TempDir::new().expect("create temp dir");
+    let a = dir
+        .path()
+        .join(format!("{}.{}", "a".repeat(200), "b".repeat(40)));
+    let b = dir.path().join(format!("c.{}", "d".repeat(200)));
+    fs::write(&a, "A").expect("write first");
+    fs::write(&b, "B").expect("write second");
+
+    let findings = diagnose_exchange(&a, &b, &ExchangeOptions::new().preserve_ext(true));
+
+    assert_eq!(findings.len(), 1);
+    let error = findings[0].error();
+    assert_eq!(error.kind(), &RenameError::NameTooLong);
+    assert_eq!(error.phase(), Phase::Plan);
+    assert!(a.exists() && b.exists());
+}
@@ -0,0 +1,102 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use exchange_name_lib::{
+    exchange_with, Durability, ExchangeError, ExchangeObserver, ExchangeOptions, PreparedExchange,
+    RenameStage,
+};
+use tempfile::TempDir;
+
+fn write(path: &Path, value: &str) {
+    fs::write(path, value).expect("write test file");
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).expect("read test file")
+}
+
+/// Records, for each rename, whether a journal sat beside the staged entry at that moment.
+#[derive(Default)]
+struct JournalWatch {
+    temp_dir: Mutex<Option<PathBuf>>,
+    journaled: Mutex<Vec<(RenameStage, bool)>>,
+}
+
+impl ExchangeObserver for JournalWatch {
+    fn temp_dir_created(&self, path: &Path) {
+        *self.temp_dir.lock().expect("lock") = Some(path.to_path_buf());
+    }
+
+    fn renamed(
+        &self,
+        stage: RenameStage,
+        _from: &Path,
+        _to: &Path,
+        result: Result<(), &ExchangeError>,
+    ) {
+        result.expect("rename succeeds");
+        let temp_dir = self
+            .temp_dir
+            .lock()
+            .expect("lock")
+            .clone()
+            .expect("temp dir");
+        let journaled = temp_dir.join("journal").exists();
+        self.journaled
+            .lock()
+            .expect("lock")
+            .push((stage, journaled));
+    }
+}
+
+#[test]
+fn durable_exchanges_are_journaled_until_complete() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    fs::create_dir(&b).expect("create directory");
+    write(&b.join("inner"), "B");
+
+    for durability in [Durability::Directories, Durability::Full] {
+        let watch = Arc::new(JournalWatch::default());
+        let options = ExchangeOptions::new()
+            .durability(durability)
+            .observer(watch.clone());
+        exchange_with(&a, &b, &options).expect("exchange");
+
+        assert_eq!(
+            *watch.journaled.lock().expect("lock"),
+            [
+                (RenameStage::ToTemporary, true),
+                (RenameStage::FirstToTarget, true),
+                (RenameStage::TemporaryToTarget, true),
+            ]
+        );
+        assert!(PreparedExchange::journals_in(dir.path())
+            .expect("list journals")
+            .is_empty());
+    }
+    assert_eq!(read(&a), "A");
+    assert_eq!(read(&b.join("inner")), "B");
+}
+
+#[test]
+fn exchanges_without_durability_skip_the_journal() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+
+    let watch = Arc::new(JournalWatch::default());
+    exchange_with(&a, &b, &ExchangeOptions::new().observer(watch.clone())).expect("exchange");
+    assert!(watch
+        .journaled
+        .lock()
+        .expect("lock")
+        .iter()
+        .all(|&(_, journaled)| !journaled));
+    assert_eq!([read(&a), read(&b)], ["B", "A"]);
+}
@@ -1,6 +1,8 @@
 use std::{fs, path::Path};
 
-use exchange_name_lib::{exchange_rs, RenameError};
+use exchange_name_lib::{
+    exchange_rs, exchange_with, plan_exchange, ExchangeOptions, Phase, RenameError,
+};
 use tempfile::TempDir;
 
 fn write(path: &Path, value: &str) {
@@ -100,9 +102,340 @@ fn rejects_nested_directories_without_mutation() {
     let child = parent.join("child");
     fs::create_dir_all(&child).expect("create nested dirs");
 
-    assert!(matches!(
+    assert_eq!(
         exchange_rs(&parent, &child, false),
-        Err(RenameError::InvalidPath(_))
-    ));
+        Err(RenameError::NestedDirectories)
+    );
     assert!(child.is_dir());
 }
+
+#[test]
+fn concurrent_exchanges_keep_entries_consistent() {
+    let dir = TempDir::new().expect("create temp dir");
+    let shared = [dir.path().join("alpha.txt"), dir.path().join("beta.txt")];
+    write(&shared[0], "A");
+    write(&shared[1], "B");
+    let disjoint = (0..4)
+        .map(|index| {
+            let subdir = dir.path().join(format!("dir{index}"));
+            fs::create_dir(&subdir).expect("create subdir");
+            let pair = [subdir.join("one"), subdir.join("two")];
+            write(&pair[0], "1");
+            write(&pair[1], "2");
+            pair
+        })
+        .collect::<Vec<_>>();
+
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..25 {
+                    exchange_rs(&shared[0], &shared[1], false).expect("exchange shared pair");
+                }
+            });
+        }
+        for pair in &disjoint {
+            scope.spawn(move || {
+                for _ in 0..25 {
+                    exchange_rs(&pair[0], &pair[1], false).expect("exchange disjoint pair");
+                }
+            });
+        }
+    });
+
+    assert_eq!(read(&shared[0]), "A");
+    assert_eq!(read(&shared[1]), "B");
+    for pair in &disjoint {
+        assert_eq!(read(&pair[0]), "2");
+        assert_eq!(read(&pair[1]), "1");
+    }
+}
+
+#[test]
+fn planned_exchange_lists_steps_and_detects_changes() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.ext1");
+    let second = dir.path().join("beta.ext2");
+    write(&first, "A");
+    write(&second, "B");
+    let options = ExchangeOptions::new().preserve_ext(true);
+
+    let plan = plan_exchange(&first, &second, &options).expect("plan exchange");
+    let steps = plan
+        .steps()
+        .map(|(from, to)| (from.to_path_buf(), to.to_path_buf()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        steps,
+        [
+            (first.clone(), dir.path().join("beta.ext1")),
+            (second.clone(), dir.path().join("alpha.ext2")),
+        ]
+    );
+    assert_eq!(read(&first), "A");
+
+    fs::remove_file(&second).expect("remove second");
+    fs::create_dir(&second).expect("replace second with directory");
+    let error = plan.execute().expect_err("entry changed");
+    assert_eq!(error.kind(), &RenameError::Changed);
+    assert_eq!(error.to_code(), 16);
+
+    fs::remove_dir(&second).expect("remove directory");
+    write(&second, "B");
+    plan_exchange(&first, &second, &options)
+        .expect("plan exchange")
+        .execute()
+        .expect("execute plan");
+    assert_eq!(read(&dir.path().join("beta.ext1")), "A");
+}
+
+#[test]
+fn resolves_relative_paths_against_base_dir() {
+    let dir = TempDir::new().expect("create temp dir");
+    write(&dir.path().join("one.txt"), "1");
+    write(&dir.path().join("two.txt"), "2");
+
+    let options = ExchangeOptions::new().base_dir(dir.path());
+    exchange_with(Path::new("one.txt"), Path::new("two.txt"), &options)
+        .expect("exchange relative paths");
+    assert_eq!(read(&dir.path().join("one.txt")), "2");
+
+    let options = ExchangeOptions::new().base_dir("relative");
+    let error = exchange_with(Path::new("one.txt"), Path::new("two.txt"), &options)
+        .expect_err("relative base dir");
+    assert_eq!(error.to_code(), 5);
+    assert_eq!(error.path(), Some(Path::new("relative")));
+}
+
+#[test]
+fn structured_error_names_missing_path() {
+    let dir = TempDir::new().expect("create temp dir");
+    let present = dir.path().join("present.txt");
+    let missing = dir.path().join("missing.txt");
+    write(&present, "A");
+
+    let error = exchange_with(&present, &missing, &ExchangeOptions::new())
+        .expect_err("missing path must fail");
+
+    assert_eq!(error.kind(), &RenameError::NotExists);
+    assert_eq!(error.to_code(), 1);
+    assert_eq!(error.phase(), Phase::Inspect);
+    assert_eq!(error.path().and_then(Path::file_name), missing.file_name());
+    assert!(error.raw_os_error().is_some());
+    let source = std::error::Error::source(&error).expect("io error source");
+    assert!(source.downcast_ref::<std::io::Error>().is_some());
+}
+
+#[cfg(unix)]
+#[test]
+fn reports_name_too_long() {
+    let dir = TempDir::new().expect("create temp dir");
+    let file = dir.path().join("short.txt");
+    write(&file, "A");
+
+    let error = exchange_rs(&file, &dir.path().join("n".repeat(4096)), false)
+        .expect_err("overlong name must fail");
+
+    assert_eq!(error, RenameError::NameTooLong);
+    assert_eq!(error.to_code(), 10);
+}
+
+#[cfg(unix)]
+#[test]
+fn exchanges_names_relative_to_directory_handles() {
+    use std::os::fd::AsFd;
+
+    use exchange_name_lib::exchange_at;
+
+    let dir = TempDir::new().expect("create temp dir");
+    let left = dir.path().join("left");
+    let right = dir.path().join("right");
+    fs::create_dir(&left).expect("create left dir");
+    fs::create_dir(&right).expect("create right dir");
+    write(&left.join("one.txt"), "1");
+    write(&right.join("two.txt"), "2");
+    let left_handle = fs::File::open(&left).expect("open left dir");
+    let right_handle = fs::File::open(&right).expect("open right dir");
+    let options = ExchangeOptions::new();
+
+    exchange_at(
+        left_handle.as_fd(),
+        Path::new("one.txt"),
+        right_handle.as_fd(),
+        Path::new("two.txt"),
+        &options,
+    )
+    .expect("exchange relative to handles");
+
+    assert_eq!(read(&left.join("two.txt")), "1");
+    assert_eq!(read(&right.join("one.txt")), "2");
+
+    // The handles keep working after their directories move.
+    let moved = dir.path().join("moved");
+    fs::rename(&left, &moved).expect("move left dir");
+    exchange_at(
+        left_handle.as_fd(),
+        Path::new("two.txt"),
+        right_handle.as_fd(),
+        Path::new("one.txt"),
+        &options,
+    )
+    .expect("exchange after moving a directory");
+    assert_eq!(read(&moved.join("one.txt")), "1");
+    assert_eq!(read(&right.join("two.txt")), "2");
+    let leftovers = fs::read_dir(&right)
+        .expect("list right dir")
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .expect("read entry")
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".name-exchange-")
+        })
+        .count();
+    assert_eq!(leftovers, 0);
+
+    for name in ["../right/two.txt", "/etc/passwd", ".."] {
+        let error = exchange_at(
+            left_handle.as_fd(),
+            Path::new("one.txt"),
+            right_handle.as_fd(),
+            Path::new(name),
+            &options,
+        )
+        .expect_err("name with more than one component");
+        assert!(matches!(error.kind(), RenameError::InvalidPath(_)));
+    }
+    let error = exchange_at(
+        left_handle.as_fd(),
+        Path::new("one.txt"),
+        right_handle.as_fd(),
+        Path::new("two.txt"),
+        &ExchangeOptions::new().verify(true),
+    )
+    .expect_err("verification needs paths");
+    assert!(matches!(error.kind(), RenameError::InvalidPath(_)));
+    assert_eq!(read(&moved.join("one.txt")), "1");
+
+    let file = fs::File::open(moved.join("one.txt")).expect("open file");
+    let error = exchange_at(
+        file.as_fd(),
+        Path::new("x"),
+        right_handle.as_fd(),
+        Path::new("two.txt"),
+        &options,
+    )
+    .expect_err("file is not a directory");
+    assert_eq!(error.to_code(), 5);
+}
+
+#[cfg(unix)]
+#[test]
+fn directory_handle_and_path_exchanges_serialize() {
+    use std::{
+        os::fd::AsFd,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use exchange_name_lib::{exchange_at, ExchangeError, ExchangeObserver, RenameStage};
+
+    type Attempt = Box<dyn Fn() -> Result<(), ExchangeError> + Send + Sync>;
+
+    /// Attempts another exchange in the same directory while the observed one is halfway.
+    struct Interloper {
+        attempt: Attempt,
+        codes: Mutex<Vec<i32>>,
+    }
+
+    impl ExchangeObserver for Interloper {
+        fn renamed(
+            &self,
+            stage: RenameStage,
+            _from: &Path,
+            _to: &Path,
+            _result: Result<(), &ExchangeError>,
+        ) {
+            if stage == RenameStage::ToTemporary {
+                let code = (self.attempt)().map_or_else(|error| error.to_code(), |()| 0);
+                self.codes.lock().expect("lock codes").push(code);
+            }
+        }
+    }
+
+    let dir = TempDir::new().expect("create temp dir");
+    for name in ["a", "b", "c", "d"] {
+        write(&dir.path().join(name), name);
+    }
+    let handle = Arc::new(fs::File::open(dir.path()).expect("open dir"));
+    let impatient = ExchangeOptions::new().lock_timeout(Duration::from_millis(20));
+
+    let (c, d) = (dir.path().join("c"), dir.path().join("d"));
+    let by_path = Arc::new(Interloper {
+        attempt: Box::new(move || exchange_with(&c, &d, &impatient)),
+        codes: Mutex::new(Vec::new()),
+    });
+    exchange_at(
+        handle.as_fd(),
+        Path::new("a"),
+        handle.as_fd(),
+        Path::new("b"),
+        &ExchangeOptions::new().observer(by_path.clone()),
+    )
+    .expect("exchange through handles");
+
+    let impatient = ExchangeOptions::new().lock_timeout(Duration::from_millis(20));
+    let moved = handle.clone();
+    let by_handle = Arc::new(Interloper {
+        attempt: Box::new(move || {
+            exchange_at(
+                moved.as_fd(),
+                Path::new("a"),
+                moved.as_fd(),
+                Path::new("b"),
+                &impatient,
+            )
+        }),
+        codes: Mutex::new(Vec::new()),
+    });
+    exchange_with(
+        &dir.path().join("c"),
+        &dir.path().join("d"),
+        &ExchangeOptions::new().observer(by_handle.clone()),
+    )
+    .expect("exchange through paths");
+
+    let timeout = RenameError::LockTimeout.to_code();
+    assert_eq!(*by_path.codes.lock().expect("lock codes"), [timeout]);
+    assert_eq!(*by_handle.codes.lock().expect("lock codes"), [timeout]);
+    for (name, value) in [("a", "b"), ("b", "a"), ("c", "d"), ("d", "c")] {
+        assert_eq!(read(&dir.path().join(name)), value);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn directory_handles_refuse_nested_directories() {
+    use std::os::fd::AsFd;
+
+    use exchange_name_lib::exchange_at;
+
+    let dir = TempDir::new().expect("create temp dir");
+    let outer = dir.path().join("outer");
+    let inner = outer.join("inner");
+    fs::create_dir_all(inner.join("deep")).expect("create nested dirs");
+    let root_handle = fs::File::open(dir.path()).expect("open root dir");
+    let inner_handle = fs::File::open(&inner).expect("open inner dir");
+
+    let error = exchange_at(
+        root_handle.as_fd(),
+        Path::new("outer"),
+        inner_handle.as_fd(),
+        Path::new("deep"),
+        &ExchangeOptions::new(),
+    )
+    .expect_err("outer holds deep");
+    assert_eq!(error.kind(), &RenameError::NestedDirectories);
+    assert!(inner.join("deep").is_dir());
+}
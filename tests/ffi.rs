@@ -1,6 +1,12 @@
-use std::{ffi::CString, fs};
+use std::{
+    ffi::{c_void, CString},
+    fs,
+};
 
-use exchange_name_lib::{exchange, exchange_n};
+use exchange_name_lib::{
+    exchange, exchange_last_error_message, exchange_last_error_os_code, exchange_lib_capabilities,
+    exchange_lib_version, exchange_n, exchange_n_observed, exchange_raw_n, ExchangeEvent,
+};
 use tempfile::TempDir;
 
 #[test]
@@ -42,3 +48,226 @@ fn legacy_interface_exchanges_files() {
     // SAFETY: CString pointers are valid and NUL-terminated for this call.
     assert_eq!(unsafe { exchange(first.as_ptr(), second.as_ptr(), 0) }, 0);
 }
+
+extern "C" fn record_event(event: *const ExchangeEvent, user_data: *mut c_void) {
+    // SAFETY: The library passes a valid event, and the test passes a `Vec` as `user_data`.
+    let (event, events) = unsafe { (&*event, &mut *user_data.cast::<Vec<(u32, u32, i32)>>()) };
+    events.push((event.kind, event.stage, event.result));
+}
+
+#[test]
+fn observed_interface_reports_events() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("one.txt");
+    let second = dir.path().join("two.txt");
+    fs::write(&first, "1").expect("write first");
+    fs::write(&second, "2").expect("write second");
+    let (first, second) = (first.to_string_lossy(), second.to_string_lossy());
+    let mut events = Vec::<(u32, u32, i32)>::new();
+
+    // SAFETY: Buffers are readable for their lengths, and `events` outlives the call.
+    let result = unsafe {
+        exchange_n_observed(
+            first.as_ptr(),
+            first.len(),
+            second.as_ptr(),
+            second.len(),
+            0,
+            Some(record_event),
+            (&raw mut events).cast(),
+        )
+    };
+
+    assert_eq!(result, 0);
+    assert_eq!(
+        events,
+        [(0, 0, 0), (1, 1, 0), (1, 2, 0), (1, 3, 0), (4, 0, 0)]
+    );
+    assert_eq!(
+        fs::read_to_string(dir.path().join("one.txt")).expect("read"),
+        "2"
+    );
+}
+
+fn last_error_message() -> String {
+    // SAFETY: A null buffer with zero length only queries the message length.
+    let length = unsafe { exchange_last_error_message(std::ptr::null_mut(), 0) };
+    let mut buffer = vec![0_u8; length + 1];
+    // SAFETY: The buffer is writable for its full length.
+    let written = unsafe { exchange_last_error_message(buffer.as_mut_ptr().cast(), buffer.len()) };
+    assert_eq!(written, length);
+    assert_eq!(buffer.pop(), Some(0));
+    String::from_utf8(buffer).expect("UTF-8 message")
+}
+
+#[test]
+fn last_error_describes_failed_path() {
+    let dir = TempDir::new().expect("create temp dir");
+    let present = dir.path().join("present.txt");
+    let missing = dir.path().join("missing.txt");
+    fs::write(&present, "1").expect("write file");
+    let (present, missing) = (present.to_string_lossy(), missing.to_string_lossy());
+
+    // SAFETY: Both buffers are readable for the supplied lengths.
+    let result = unsafe {
+        exchange_n(
+            present.as_ptr(),
+            present.len(),
+            missing.as_ptr(),
+            missing.len(),
+            0,
+        )
+    };
+
+    assert_eq!(result, 1);
+    assert!(last_error_message().contains(missing.as_ref()));
+    assert_ne!(exchange_last_error_os_code(), 0);
+
+    let mut small = [0xff_u8; 4];
+    // SAFETY: The buffer is writable for its full length.
+    unsafe { exchange_last_error_message(small.as_mut_ptr().cast(), small.len()) };
+    assert_eq!(small[3], 0);
+}
+
+#[test]
+fn last_error_is_cleared_after_success() {
+    let value = b"unused";
+    // SAFETY: Both buffers are readable for the supplied lengths.
+    unsafe { exchange_n(value.as_ptr(), value.len(), value.as_ptr(), value.len(), 2) };
+    assert!(last_error_message().contains("preserve_ext"));
+    assert_eq!(exchange_last_error_os_code(), 0);
+
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("a");
+    let second = dir.path().join("b");
+    fs::write(&first, "1").expect("write first");
+    fs::write(&second, "2").expect("write second");
+    let (first, second) = (first.to_string_lossy(), second.to_string_lossy());
+    // SAFETY: Both buffers are readable for the supplied lengths.
+    let result = unsafe {
+        exchange_n(
+            first.as_ptr(),
+            first.len(),
+            second.as_ptr(),
+            second.len(),
+            0,
+        )
+    };
+
+    assert_eq!(result, 0);
+    assert_eq!(last_error_message(), "");
+}
+
+#[cfg(unix)]
+#[test]
+fn raw_interface_accepts_non_utf8_names() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let dir = TempDir::new().expect("create temp dir");
+    let mut first = dir.path().as_os_str().as_bytes().to_vec();
+    first.extend_from_slice(b"/caf\xe9.txt");
+    let mut second = dir.path().as_os_str().as_bytes().to_vec();
+    second.extend_from_slice(b"/plain.txt");
+    fs::write(OsStr::from_bytes(&first), "1").expect("write first");
+    fs::write(OsStr::from_bytes(&second), "2").expect("write second");
+
+    // SAFETY: Both buffers are readable for the supplied lengths.
+    let result = unsafe {
+        exchange_raw_n(
+            first.as_ptr(),
+            first.len(),
+            second.as_ptr(),
+            second.len(),
+            0,
+        )
+    };
+
+    assert_eq!(result, 0);
+    assert_eq!(
+        fs::read_to_string(OsStr::from_bytes(&first)).expect("read first"),
+        "2"
+    );
+
+    let mut embedded = second.clone();
+    embedded.insert(1, 0);
+    // SAFETY: Both buffers are readable for the supplied lengths.
+    let result = unsafe {
+        exchange_raw_n(
+            embedded.as_ptr(),
+            embedded.len(),
+            second.as_ptr(),
+            second.len(),
+            0,
+        )
+    };
+    assert_eq!(result, 5);
+}
+
+#[test]
+fn reports_version_and_capabilities() {
+    let version = exchange_lib_version();
+    assert_eq!(
+        format!("{}.{}.{}", version.major, version.minor, version.patch),
+        env!("CARGO_PKG_VERSION")
+    );
+
+    let capabilities = exchange_lib_capabilities();
+    assert_eq!(capabilities & 0b110, 0b110);
+    assert_eq!(capabilities & (1 << 3) != 0, cfg!(unix));
+    assert_eq!(capabilities & (1 << 4) != 0, cfg!(feature = "tokio"));
+    assert_eq!(capabilities & (1 << 5) != 0, cfg!(feature = "tracing"));
+    assert_eq!(capabilities & (1 << 6) != 0, cfg!(target_os = "linux"));
+    assert_ne!(capabilities & (1 << 7), 0);
+    assert_eq!(capabilities & 1, 0);
+    assert_eq!(capabilities & (0b1_1011 << 8), 0b1_1011 << 8);
+    assert_eq!(capabilities & (1 << 10) != 0, cfg!(unix));
+    assert_eq!(capabilities & (1 << 13) != 0, cfg!(unix));
+    assert_eq!(exchange_lib_capabilities(), capabilities);
+}
+
+#[cfg(unix)]
+#[test]
+fn exchanges_relative_to_directory_descriptors() {
+    use std::os::fd::AsRawFd;
+
+    use exchange_name_lib::c_abi::exchange_at;
+
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("one.txt"), "1").expect("write first");
+    fs::write(dir.path().join("two.txt"), "2").expect("write second");
+    let handle = fs::File::open(dir.path()).expect("open dir");
+    let fd = handle.as_raw_fd();
+    let (first, second) = (b"one.txt", b"two.txt");
+
+    // SAFETY: `fd` stays open for the call and both names are readable for their lengths.
+    let result = unsafe {
+        exchange_at(
+            fd,
+            first.as_ptr(),
+            first.len(),
+            fd,
+            second.as_ptr(),
+            second.len(),
+            std::ptr::null(),
+        )
+    };
+    assert_eq!(result, 0);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("one.txt")).expect("read"),
+        "2"
+    );
+
+    // SAFETY: Negative descriptors are rejected before use.
+    let result = unsafe {
+        exchange_at(
+            -1,
+            first.as_ptr(),
+            first.len(),
+            fd,
+            second.as_ptr(),
+            second.len(),
+            std::ptr::null(),
+        )
+    };
+    assert_eq!(result, 5);
+}
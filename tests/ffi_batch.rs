@@ -0,0 +1,78 @@
+use std::{fs, ptr};
+
+use exchange_name_lib::{exchange_batch_n, exchange_rotate_n, ExchangePath, ExchangePathPair};
+use tempfile::TempDir;
+
+#[test]
+fn batch_reports_per_pair_results() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c, missing] =
+        ["a", "b", "c", "missing"].map(|name| dir.path().join(name).to_string_lossy().into_owned());
+    for (path, value) in [(&a, "A"), (&b, "B"), (&c, "C")] {
+        fs::write(path, value).expect("write file");
+    }
+    let pair = |first: &String, second: &String| ExchangePathPair {
+        path1: first.as_ptr(),
+        path1_len: first.len(),
+        path2: second.as_ptr(),
+        path2_len: second.len(),
+    };
+    let mut results = [-1; 3];
+
+    let pairs = [pair(&a, &b), pair(&b, &c), pair(&c, &missing)];
+    // SAFETY: Every pair's buffers outlive the call, and `results` holds one value per pair.
+    let result = unsafe { exchange_batch_n(ptr::null(), pairs.as_ptr(), 3, results.as_mut_ptr()) };
+
+    assert_eq!(result, 1);
+    assert_eq!(results, [17, 17, 1]);
+    assert_eq!(fs::read_to_string(&a).expect("read"), "A");
+
+    let pairs = [pair(&a, &b), pair(&b, &c)];
+    // SAFETY: As above; a null result array is allowed.
+    let result = unsafe { exchange_batch_n(ptr::null(), pairs.as_ptr(), 2, ptr::null_mut()) };
+    assert_eq!(result, 0);
+    assert_eq!(fs::read_to_string(&c).expect("read"), "A");
+}
+
+#[test]
+fn batch_marks_invalid_pair() {
+    let invalid = [0xff_u8];
+    let pairs = [ExchangePathPair {
+        path1: invalid.as_ptr(),
+        path1_len: 1,
+        path2: ptr::null(),
+        path2_len: 0,
+    }];
+    let mut results = [-1];
+
+    // SAFETY: The pair's non-null buffer is readable, and `results` holds one value.
+    let result = unsafe { exchange_batch_n(ptr::null(), pairs.as_ptr(), 1, results.as_mut_ptr()) };
+
+    assert_eq!(result, 5);
+    assert_eq!(results, [5]);
+    // SAFETY: A null array is only read when the count is non-zero.
+    assert_eq!(
+        unsafe { exchange_batch_n(ptr::null(), ptr::null(), 1, ptr::null_mut()) },
+        5
+    );
+}
+
+#[test]
+fn rotates_paths() {
+    let dir = TempDir::new().expect("create temp dir");
+    let names = ["a", "b", "c"].map(|name| dir.path().join(name).to_string_lossy().into_owned());
+    for (path, value) in names.iter().zip(["A", "B", "C"]) {
+        fs::write(path, value).expect("write file");
+    }
+    let paths = names.each_ref().map(|name| ExchangePath {
+        path: name.as_ptr(),
+        len: name.len(),
+    });
+
+    // SAFETY: Every path buffer outlives the call.
+    let result = unsafe { exchange_rotate_n(ptr::null(), paths.as_ptr(), paths.len()) };
+
+    assert_eq!(result, 0);
+    assert_eq!(fs::read_to_string(&names[0]).expect("read"), "C");
+    assert_eq!(fs::read_to_string(&names[1]).expect("read"), "A");
+}
@@ -0,0 +1,491 @@
+use std::{ffi::c_void, fs, ptr};
+
+use exchange_name_lib::{
+    exchange_last_error_message, exchanger_diagnose, exchanger_options_expect,
+    exchanger_options_free, exchanger_options_new, exchanger_options_set_base_dir,
+    exchanger_options_set_durability, exchanger_options_set_history,
+    exchanger_options_set_lock_timeout_ms, exchanger_options_set_observer,
+    exchanger_options_set_operation_id, exchanger_options_set_preserve_ext,
+    exchanger_options_set_verify, exchanger_plan, exchanger_plan_abort, exchanger_plan_commit,
+    exchanger_plan_execute, exchanger_plan_free, exchanger_plan_journal_dir,
+    exchanger_plan_prepare, exchanger_plan_source, exchanger_plan_step_count,
+    exchanger_plan_target, exchanger_recover, ExchangeEvent, ExchangeFinding, ExchangePrecondition,
+    ExchangerPlan,
+};
+use tempfile::TempDir;
+
+const EXCHANGE_EXPECT_SIZE: u32 = 1 << 1;
+
+fn step_path(
+    copy: unsafe extern "C" fn(*const ExchangerPlan, usize, *mut std::ffi::c_char, usize) -> usize,
+    plan: *const ExchangerPlan,
+    index: usize,
+) -> String {
+    // SAFETY: `plan` is live, and a null buffer with zero length only queries the length.
+    let length = unsafe { copy(plan, index, ptr::null_mut(), 0) };
+    let mut buffer = vec![0_u8; length + 1];
+    // SAFETY: The buffer is writable for its full length.
+    assert_eq!(
+        unsafe { copy(plan, index, buffer.as_mut_ptr().cast(), buffer.len()) },
+        length
+    );
+    assert_eq!(buffer.pop(), Some(0));
+    String::from_utf8(buffer).expect("UTF-8 path")
+}
+
+extern "C" fn count_event(_event: *const ExchangeEvent, user_data: *mut c_void) {
+    // SAFETY: The test passes a `usize` counter as `user_data`.
+    unsafe { *user_data.cast::<usize>() += 1 };
+}
+
+extern "C" fn collect_finding(finding: *const ExchangeFinding, user_data: *mut c_void) {
+    // SAFETY: The library passes a valid finding, and the test passes a `Vec` as `user_data`.
+    let (finding, codes) = unsafe { (&*finding, &mut *user_data.cast::<Vec<(u32, i32)>>()) };
+    codes.push((finding.severity, finding.code));
+}
+
+#[test]
+fn plans_and_executes_with_options() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.ext1");
+    let second = dir.path().join("beta.ext2");
+    fs::write(&first, "A").expect("write first");
+    fs::write(&second, "B").expect("write second");
+    let (first, second) = (first.to_string_lossy(), second.to_string_lossy());
+    let mut events = 0_usize;
+
+    let options = exchanger_options_new();
+    assert!(!options.is_null());
+    let mut plan = ptr::null_mut();
+    // SAFETY: Handles are live, buffers are readable for their lengths, and `events` outlives
+    // the plan's execution.
+    unsafe {
+        assert_eq!(exchanger_options_set_preserve_ext(options, 1), 0);
+        assert_eq!(exchanger_options_set_lock_timeout_ms(options, 1_000), 0);
+        assert_eq!(exchanger_options_set_verify(options, 2), 5);
+        assert_eq!(exchanger_options_set_durability(options, 3), 5);
+        assert_eq!(exchanger_options_set_durability(options, 2), 0);
+        assert_eq!(
+            exchanger_options_set_verify(options, u8::from(cfg!(unix))),
+            0
+        );
+        assert_eq!(
+            exchanger_options_set_observer(options, Some(count_event), (&raw mut events).cast()),
+            0
+        );
+        assert_eq!(
+            exchanger_plan(
+                options,
+                first.as_ptr(),
+                first.len(),
+                second.as_ptr(),
+                second.len(),
+                &raw mut plan,
+            ),
+            0
+        );
+        exchanger_options_free(options);
+    }
+
+    // SAFETY: `plan` is live until freed below.
+    unsafe {
+        assert_eq!(exchanger_plan_step_count(plan), 2);
+        assert_eq!(step_path(exchanger_plan_source, plan, 0), first);
+        assert_eq!(
+            step_path(exchanger_plan_target, plan, 1),
+            dir.path().join("alpha.ext2").to_string_lossy()
+        );
+        assert_eq!(exchanger_plan_source(plan, 2, ptr::null_mut(), 0), 0);
+
+        assert_eq!(exchanger_plan_execute(plan), 0);
+        assert_eq!(exchanger_plan_execute(plan), 5);
+        exchanger_plan_free(plan);
+    }
+
+    assert_eq!(events, 5);
+    assert_eq!(
+        fs::read_to_string(dir.path().join("beta.ext1")).expect("read"),
+        "A"
+    );
+}
+
+#[test]
+fn failed_plan_clears_output_and_reports_changes() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("one.txt");
+    let second = dir.path().join("two.log");
+    let missing = dir.path().join("missing.txt");
+    fs::write(&first, "1").expect("write first");
+    fs::write(&second, "2").expect("write second");
+    let (first_name, second_name, missing_name) = (
+        first.to_string_lossy(),
+        second.to_string_lossy(),
+        missing.to_string_lossy(),
+    );
+
+    let mut plan = ptr::NonNull::<ExchangerPlan>::dangling().as_ptr();
+    // SAFETY: Buffers are readable for their lengths and `plan` is writable.
+    let result = unsafe {
+        exchanger_plan(
+            ptr::null(),
+            first_name.as_ptr(),
+            first_name.len(),
+            missing_name.as_ptr(),
+            missing_name.len(),
+            &raw mut plan,
+        )
+    };
+    assert_eq!(result, 1);
+    assert!(plan.is_null());
+
+    let options = exchanger_options_new();
+    // SAFETY: `options` is live, buffers are readable for their lengths and `plan` is writable.
+    unsafe {
+        assert_eq!(exchanger_options_set_preserve_ext(options, 1), 0);
+        assert_eq!(
+            exchanger_plan(
+                options,
+                first_name.as_ptr(),
+                first_name.len(),
+                second_name.as_ptr(),
+                second_name.len(),
+                &raw mut plan,
+            ),
+            0
+        );
+        exchanger_options_free(options);
+    }
+    fs::remove_file(&second).expect("remove second");
+    fs::create_dir(&second).expect("replace second with directory");
+
+    // SAFETY: `plan` is live until freed.
+    unsafe {
+        assert_eq!(exchanger_plan_execute(plan), 16);
+        exchanger_plan_free(plan);
+        // Freeing keeps the error of the failed call for the caller to read.
+        assert!(exchange_last_error_message(ptr::null_mut(), 0) > 0);
+    }
+    assert_eq!(fs::read_to_string(&first).expect("read"), "1");
+}
+
+#[test]
+fn plans_relative_to_base_dir() {
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("one.txt"), "1").expect("write first");
+    fs::write(dir.path().join("two.txt"), "2").expect("write second");
+    let base = dir.path().to_string_lossy();
+    let (first, second, relative) = (b"one.txt", b"two.txt", b"relative");
+    let options = exchanger_options_new();
+    let mut plan = ptr::null_mut();
+
+    // SAFETY: `options` is live, buffers are readable for their lengths and `plan` is writable.
+    unsafe {
+        assert_eq!(
+            exchanger_options_set_base_dir(options, relative.as_ptr(), relative.len()),
+            5
+        );
+        assert_eq!(
+            exchanger_options_set_base_dir(options, base.as_ptr(), base.len()),
+            0
+        );
+        assert_eq!(
+            exchanger_plan(
+                options,
+                first.as_ptr(),
+                first.len(),
+                second.as_ptr(),
+                second.len(),
+                &raw mut plan,
+            ),
+            0
+        );
+        exchanger_options_free(options);
+        assert_eq!(exchanger_plan_execute(plan), 0);
+        exchanger_plan_free(plan);
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("one.txt")).expect("read"),
+        "2"
+    );
+}
+
+#[test]
+fn rejects_null_handles() {
+    // SAFETY: Null handles verify validation before dereference.
+    unsafe {
+        assert_eq!(exchanger_options_set_preserve_ext(ptr::null_mut(), 1), 5);
+        assert_eq!(exchanger_plan_execute(ptr::null_mut()), 5);
+        assert_eq!(exchanger_plan_step_count(ptr::null()), 0);
+        exchanger_plan_free(ptr::null_mut());
+        exchanger_options_free(ptr::null_mut());
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn operation_id_turns_retries_into_no_ops() {
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("one"), "1").expect("write first");
+    fs::write(dir.path().join("two"), "2").expect("write second");
+    let base = dir.path().to_string_lossy();
+    let history = dir.path().join("history");
+    let history = history.to_string_lossy();
+    let (first, second, operation_id) = (b"one", b"two", b"job-17");
+    let options = exchanger_options_new();
+
+    // SAFETY: `options` is live, buffers are readable for their lengths and `plan` is writable.
+    unsafe {
+        assert_eq!(
+            exchanger_options_set_base_dir(options, base.as_ptr(), base.len()),
+            0
+        );
+        assert_eq!(
+            exchanger_options_set_operation_id(options, operation_id.as_ptr(), 0),
+            20
+        );
+        assert_eq!(
+            exchanger_options_set_history(options, history.as_ptr(), history.len()),
+            0
+        );
+        assert_eq!(
+            exchanger_options_set_operation_id(options, operation_id.as_ptr(), operation_id.len()),
+            0
+        );
+        for _ in 0..2 {
+            let mut plan = ptr::null_mut();
+            assert_eq!(
+                exchanger_plan(
+                    options,
+                    first.as_ptr(),
+                    first.len(),
+                    second.as_ptr(),
+                    second.len(),
+                    &raw mut plan,
+                ),
+                0
+            );
+            assert_eq!(exchanger_plan_execute(plan), 0);
+            exchanger_plan_free(plan);
+        }
+        exchanger_options_free(options);
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("one")).expect("read"),
+        "2"
+    );
+}
+
+#[test]
+fn prepares_commits_and_recovers() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("one");
+    let second = dir.path().join("two");
+    fs::write(&first, "1").expect("write first");
+    fs::write(&second, "2").expect("write second");
+    let (first, second) = (first.to_string_lossy(), second.to_string_lossy());
+    let mut plan = ptr::null_mut();
+
+    // SAFETY: Buffers are readable for their lengths, outputs are writable, and each plan is
+    // freed once.
+    unsafe {
+        assert_eq!(
+            exchanger_plan(
+                ptr::null(),
+                first.as_ptr(),
+                first.len(),
+                second.as_ptr(),
+                second.len(),
+                &raw mut plan,
+            ),
+            0
+        );
+        assert_eq!(exchanger_plan_commit(plan), 5);
+        assert_eq!(exchanger_plan_journal_dir(plan, ptr::null_mut(), 0), 0);
+        assert_eq!(exchanger_plan_prepare(plan), 0);
+        assert_eq!(exchanger_plan_prepare(plan), 5);
+        let length = exchanger_plan_journal_dir(plan, ptr::null_mut(), 0);
+        let mut journal = vec![0_u8; length + 1];
+        exchanger_plan_journal_dir(plan, journal.as_mut_ptr().cast(), journal.len());
+        journal.pop();
+
+        assert_eq!(exchanger_plan_abort(plan), 0);
+        assert_eq!(exchanger_plan_abort(plan), 5);
+        exchanger_plan_free(plan);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("two")).expect("read"),
+            "2"
+        );
+
+        // Aborting removed the journal, so there is nothing left to recover.
+        let mut state = u8::MAX;
+        assert_eq!(
+            exchanger_recover(
+                ptr::null(),
+                journal.as_ptr(),
+                journal.len(),
+                &raw mut plan,
+                &raw mut state,
+            ),
+            1
+        );
+        assert!(plan.is_null());
+
+        assert_eq!(
+            exchanger_plan(
+                ptr::null(),
+                first.as_ptr(),
+                first.len(),
+                second.as_ptr(),
+                second.len(),
+                &raw mut plan,
+            ),
+            0
+        );
+        assert_eq!(exchanger_plan_prepare(plan), 0);
+        assert_eq!(exchanger_plan_commit(plan), 0);
+        exchanger_plan_free(plan);
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("one")).expect("read"),
+        "2"
+    );
+}
+
+#[test]
+fn preconditions_guard_exchanges() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("one");
+    let second = dir.path().join("two");
+    fs::write(&first, "1").expect("write first");
+    fs::write(&second, "22").expect("write second");
+    let (first, second) = (first.to_string_lossy(), second.to_string_lossy());
+    let mut precondition = ExchangePrecondition {
+        fields: EXCHANGE_EXPECT_SIZE,
+        dev: 0,
+        ino: 0,
+        size: 1,
+        modified_sec: 0,
+        modified_nsec: 0,
+        sha256: [0; 32],
+    };
+    let options = exchanger_options_new();
+
+    // SAFETY: `options` is live, buffers are readable for their lengths, `precondition` is
+    // readable and `plan` is writable.
+    unsafe {
+        assert_eq!(
+            exchanger_options_expect(options, second.as_ptr(), second.len(), ptr::null()),
+            5
+        );
+        assert_eq!(
+            exchanger_options_expect(
+                options,
+                second.as_ptr(),
+                second.len(),
+                &raw const precondition
+            ),
+            0
+        );
+        let mut plan = ptr::null_mut();
+        assert_eq!(
+            exchanger_plan(
+                options,
+                first.as_ptr(),
+                first.len(),
+                second.as_ptr(),
+                second.len(),
+                &raw mut plan,
+            ),
+            16
+        );
+        assert!(plan.is_null());
+
+        precondition.size = 2;
+        assert_eq!(
+            exchanger_options_expect(
+                options,
+                second.as_ptr(),
+                second.len(),
+                &raw const precondition
+            ),
+            0
+        );
+        precondition.fields = 1 << 31;
+        assert_eq!(
+            exchanger_options_expect(
+                options,
+                first.as_ptr(),
+                first.len(),
+                &raw const precondition
+            ),
+            5
+        );
+        assert_eq!(
+            exchanger_plan(
+                options,
+                first.as_ptr(),
+                first.len(),
+                second.as_ptr(),
+                second.len(),
+                &raw mut plan,
+            ),
+            0
+        );
+        assert_eq!(exchanger_plan_execute(plan), 0);
+        exchanger_plan_free(plan);
+        exchanger_options_free(options);
+    }
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("one")).expect("read"),
+        "22"
+    );
+}
+
+#[test]
+fn diagnoses_without_renaming() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("one");
+    let second = dir.path().join("two");
+    fs::write(&first, "1").expect("write first");
+    let (first, second) = (first.to_string_lossy(), second.to_string_lossy());
+    let mut findings: Vec<(u32, i32)> = Vec::new();
+
+    // SAFETY: Buffers are readable for their lengths and `findings` outlives the call.
+    unsafe {
+        assert_eq!(
+            exchanger_diagnose(
+                ptr::null(),
+                first.as_ptr(),
+                first.len(),
+                second.as_ptr(),
+                second.len(),
+                Some(collect_finding),
+                (&raw mut findings).cast(),
+            ),
+            1
+        );
+    }
+    assert_eq!(findings, [(1, 1)]);
+
+    fs::write(second.as_ref(), "2").expect("write second");
+    // SAFETY: Buffers are readable for their lengths; a null callback is allowed.
+    unsafe {
+        assert_eq!(
+            exchanger_diagnose(
+                ptr::null(),
+                first.as_ptr(),
+                first.len(),
+                second.as_ptr(),
+                second.len(),
+                None,
+                ptr::null_mut(),
+            ),
+            0
+        );
+    }
+    assert_eq!(fs::read_to_string(first.as_ref()).expect("read"), "1");
+}
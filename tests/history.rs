@@ -0,0 +1,315 @@
+#![cfg(unix)]
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write as _,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use exchange_name_lib::{
+    exchange_batch, exchange_with, plan_exchange, ExchangeError, ExchangeObserver, ExchangeOptions,
+    History, HistoryEntry, Phase, RenameError, RenameStage,
+};
+use tempfile::TempDir;
+
+fn write(path: &Path, value: &str) {
+    fs::write(path, value).expect("write test file");
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).expect("read test file")
+}
+
+fn recording(dir: &TempDir) -> (History, ExchangeOptions) {
+    let history = History::new(dir.path().join("state").join("history"));
+    let options = ExchangeOptions::new().history(history.clone());
+    (history, options)
+}
+
+#[test]
+fn undo_last_restores_names() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let [a, b] = ["a.txt", "b.log"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+
+    exchange_with(&a, &b, &options.clone().preserve_ext(true)).expect("exchange");
+    assert_eq!(read(&dir.path().join("b.txt")), "A");
+
+    let entries = history.entries().expect("read history");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id(), 1);
+    assert_eq!(
+        entries[0].steps().next(),
+        Some((a.as_path(), dir.path().join("b.txt").as_path()))
+    );
+
+    let undone = history.undo_last(&ExchangeOptions::new()).expect("undo");
+    assert_eq!(undone.id(), 1);
+    assert_eq!(read(&a), "A");
+    assert_eq!(read(&b), "B");
+    assert!(history.entries().expect("read history")[0].is_undone());
+
+    let error = history
+        .undo_last(&ExchangeOptions::new())
+        .expect_err("nothing left to undo");
+    assert!(matches!(error.kind(), RenameError::InvalidPath(_)));
+    assert_eq!(error.phase(), Phase::History);
+}
+
+#[test]
+fn undo_refuses_replaced_entries() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    exchange_with(&a, &b, &options).expect("exchange");
+
+    fs::rename(&a, dir.path().join("moved")).expect("move entry away");
+    write(&a, "replacement");
+
+    let error = history
+        .undo(1, &ExchangeOptions::new())
+        .expect_err("entry was replaced");
+    assert_eq!(error.kind(), &RenameError::Changed);
+    assert_eq!(read(&a), "replacement");
+    assert_eq!(read(&b), "A");
+    assert!(!history.entries().expect("read history")[0].is_undone());
+}
+
+#[test]
+fn undo_selects_entries_by_id() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let [a, b, c, d] = ["a", "b", "c", "d"].map(|name| dir.path().join(name));
+    for path in [&a, &b, &c, &d] {
+        write(
+            path,
+            &path.file_name().expect("file name").to_string_lossy(),
+        );
+    }
+    exchange_batch(&[(&a, &b), (&c, &d)], &options).expect("exchange batch");
+
+    history
+        .undo(1, &ExchangeOptions::new())
+        .expect("undo first");
+    assert_eq!(read(&a), "a");
+    assert_eq!(read(&c), "d");
+
+    let error = history
+        .undo(1, &ExchangeOptions::new())
+        .expect_err("already undone");
+    assert!(matches!(error.kind(), RenameError::InvalidPath(_)));
+    let error = history
+        .undo(7, &ExchangeOptions::new())
+        .expect_err("unknown id");
+    assert!(matches!(error.kind(), RenameError::InvalidPath(_)));
+
+    history
+        .undo_last(&ExchangeOptions::new())
+        .expect("undo second");
+    assert_eq!(read(&c), "c");
+}
+
+#[test]
+fn history_keeps_unusual_names() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let [a, b] = ["tab\there", "100%\nnew"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    exchange_with(&a, &b, &options).expect("exchange");
+
+    let entries = history.entries().expect("read history");
+    assert_eq!(entries[0].steps().next(), Some((a.as_path(), b.as_path())));
+    history.undo_last(&ExchangeOptions::new()).expect("undo");
+    assert_eq!(read(&a), "A");
+}
+
+#[test]
+fn operation_id_makes_retries_no_ops() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let options = options.operation_id("job-1");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+
+    exchange_with(&a, &b, &options).expect("exchange");
+    exchange_with(&a, &b, &options).expect("retry");
+    plan_exchange(&a, &b, &options)
+        .expect("plan")
+        .execute()
+        .expect("retry planned exchange");
+    assert_eq!(read(&a), "B");
+    let entries = history.entries().expect("read history");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].operation_id(), Some("job-1"));
+
+    history.undo_last(&ExchangeOptions::new()).expect("undo");
+    exchange_with(&a, &b, &options).expect("run again after undo");
+    assert_eq!(read(&a), "B");
+    assert_eq!(history.entries().expect("read history").len(), 2);
+}
+
+#[test]
+fn operation_id_checks_recorded_state() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (_history, options) = recording(&dir);
+    let options = options.operation_id("job-2");
+    let [a, b, c] = ["a", "b", "c"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    write(&c, "C");
+    exchange_batch(&[(&a, &b), (&b, &c)], &options).expect("exchange batch");
+    exchange_batch(&[(&a, &b), (&b, &c)], &options).expect("retry batch");
+    assert_eq!([read(&a), read(&b), read(&c)], ["B", "C", "A"]);
+
+    let error = exchange_with(&a, &c, &options).expect_err("other paths");
+    assert!(matches!(error.kind(), RenameError::InvalidOperationId(_)));
+    assert_eq!(error.to_code(), 20);
+
+    exchange_with(&a, &b, &ExchangeOptions::new()).expect("unrecorded exchange");
+    let error = exchange_batch(&[(&a, &b), (&b, &c)], &options).expect_err("entries moved");
+    assert_eq!(error.error().kind(), &RenameError::Changed);
+
+    let error = exchange_with(&a, &b, &ExchangeOptions::new().operation_id("job-3"))
+        .expect_err("no history");
+    assert!(matches!(error.kind(), RenameError::InvalidOperationId(_)));
+
+    let error =
+        exchange_with(&a, &b, &options.clone().operation_id("")).expect_err("empty operation ID");
+    assert!(matches!(error.kind(), RenameError::InvalidOperationId(_)));
+}
+
+/// Removes the second entry as soon as the temporary directory exists, so the exchange fails.
+struct Vanish(PathBuf);
+
+impl ExchangeObserver for Vanish {
+    fn temp_dir_created(&self, _path: &Path) {
+        fs::remove_file(&self.0).expect("remove entry");
+    }
+}
+
+#[test]
+fn failed_exchanges_are_not_listed() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+
+    let error = exchange_with(
+        &a,
+        &b,
+        &options.clone().observer(Arc::new(Vanish(b.clone()))),
+    )
+    .expect_err("entry vanished");
+    assert_eq!(error.phase(), Phase::Rename);
+    assert!(history.entries().expect("read history").is_empty());
+
+    write(&b, "B");
+    exchange_with(&a, &b, &options).expect("exchange");
+    let entries = history.entries().expect("read history");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id(), 2);
+}
+
+#[test]
+fn interrupted_appends_are_dropped() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    exchange_with(&a, &b, &options).expect("exchange");
+    let mut file = OpenOptions::new()
+        .append(true)
+        .open(history.path())
+        .expect("open history");
+    file.write_all(b"exchange\t2\t17")
+        .expect("write partial line");
+
+    assert_eq!(history.entries().expect("read history").len(), 1);
+    exchange_with(&a, &b, &options).expect("exchange again");
+    let ids = history
+        .entries()
+        .expect("read history")
+        .iter()
+        .map(HistoryEntry::id)
+        .collect::<Vec<_>>();
+    assert_eq!(ids, [1, 2]);
+    assert!(read(history.path()).ends_with('\n'));
+}
+
+/// Replaces the history with a directory once the last rename of an exchange succeeded.
+struct Blocker {
+    history: PathBuf,
+    failures: Mutex<Vec<PathBuf>>,
+}
+
+impl ExchangeObserver for Blocker {
+    fn renamed(
+        &self,
+        stage: RenameStage,
+        _from: &Path,
+        _to: &Path,
+        result: Result<(), &ExchangeError>,
+    ) {
+        if stage == RenameStage::TemporaryToTarget && result.is_ok() {
+            fs::remove_file(&self.history).expect("remove history");
+            fs::create_dir(&self.history).expect("block history");
+        }
+    }
+
+    fn history_failed(&self, path: &Path, error: &ExchangeError) {
+        assert_eq!(error.phase(), Phase::History);
+        self.failures.lock().expect("lock").push(path.to_path_buf());
+    }
+}
+
+#[test]
+fn undo_reports_history_failures_after_renaming() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    exchange_with(&a, &b, &options).expect("exchange");
+
+    let observer = Arc::new(Blocker {
+        history: history.path().to_path_buf(),
+        failures: Mutex::new(Vec::new()),
+    });
+    let undone = history
+        .undo_last(&ExchangeOptions::new().observer(observer.clone()))
+        .expect("undo stands");
+    assert!(undone.is_undone());
+    assert_eq!([read(&a), read(&b)], ["A", "B"]);
+    assert_eq!(*observer.failures.lock().expect("lock"), [history.path()]);
+}
+
+#[test]
+fn operation_id_runs_again_when_the_entries_never_moved() {
+    let dir = TempDir::new().expect("create temp dir");
+    let (history, options) = recording(&dir);
+    let options = options.operation_id("job-4");
+    let [a, b, aside] = ["a", "b", "aside"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    exchange_with(&a, &b, &options).expect("exchange");
+
+    // As if the process had died after recording the exchange but before renaming anything.
+    fs::rename(&a, &aside).expect("move aside");
+    fs::rename(&b, &a).expect("restore first");
+    fs::rename(&aside, &b).expect("restore second");
+    exchange_with(&a, &b, &options).expect("run again");
+
+    assert_eq!([read(&a), read(&b)], ["B", "A"]);
+    let entries = history.entries().expect("read history");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id(), 2);
+}
@@ -0,0 +1,273 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use exchange_name_lib::{
+    exchange_with, ExchangeError, ExchangeObserver, ExchangeOptions, Phase, RenameError,
+    RenameStage,
+};
+use tempfile::TempDir;
+
+#[derive(Default)]
+struct Recorder {
+    events: Mutex<Vec<String>>,
+    block_target: Option<PathBuf>,
+}
+
+impl Recorder {
+    fn push(&self, event: String) {
+        self.events.lock().expect("lock events").push(event);
+    }
+
+    fn events(&self) -> Vec<String> {
+        self.events.lock().expect("lock events").clone()
+    }
+}
+
+impl ExchangeObserver for Recorder {
+    fn temp_dir_created(&self, _path: &Path) {
+        self.push("temp".to_owned());
+    }
+
+    fn renamed(
+        &self,
+        stage: RenameStage,
+        _from: &Path,
+        _to: &Path,
+        result: Result<(), &ExchangeError>,
+    ) {
+        if let (RenameStage::ToTemporary, Some(target)) = (stage, &self.block_target) {
+            fs::create_dir(target).expect("create blocking dir");
+            fs::write(target.join("inside"), "X").expect("write blocking file");
+        }
+        self.push(format!("{stage:?}:{}", result.is_ok()));
+    }
+
+    fn rollback_started(&self, _cause: &ExchangeError) {
+        self.push("rollback".to_owned());
+    }
+
+    fn rollback_step(&self, _from: &Path, _to: &Path, result: Result<(), &ExchangeError>) {
+        self.push(format!("undo:{}", result.is_ok()));
+    }
+
+    fn cleanup(&self, _path: &Path, result: Result<(), &io::Error>) {
+        self.push(format!("cleanup:{}", result.is_ok()));
+    }
+}
+
+#[test]
+fn reports_every_step_of_a_successful_exchange() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.txt");
+    let second = dir.path().join("beta.txt");
+    fs::write(&first, "A").expect("write first");
+    fs::write(&second, "B").expect("write second");
+    let recorder = Arc::new(Recorder::default());
+
+    exchange_with(
+        &first,
+        &second,
+        &ExchangeOptions::new().observer(recorder.clone()),
+    )
+    .expect("exchange files");
+
+    assert_eq!(
+        recorder.events(),
+        [
+            "temp",
+            "ToTemporary:true",
+            "FirstToTarget:true",
+            "TemporaryToTarget:true",
+            "cleanup:true"
+        ]
+    );
+}
+
+#[test]
+fn reports_rollback_after_failed_rename() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("one").join("alpha");
+    let second = dir.path().join("two").join("beta");
+    fs::create_dir_all(&first).expect("create first");
+    fs::create_dir_all(&second).expect("create second");
+    let recorder = Arc::new(Recorder {
+        block_target: Some(dir.path().join("one").join("beta")),
+        ..Recorder::default()
+    });
+
+    let result = exchange_with(
+        &first,
+        &second,
+        &ExchangeOptions::new().observer(recorder.clone()),
+    );
+
+    let error = result.expect_err("exchange must fail");
+    assert_eq!(error.kind(), &RenameError::AlreadyExists);
+    assert_eq!(error.phase(), Phase::Rename);
+    assert!(error.raw_os_error().is_some());
+    assert!(first.is_dir() && second.is_dir());
+    assert_eq!(
+        recorder.events(),
+        [
+            "temp",
+            "ToTemporary:true",
+            "FirstToTarget:false",
+            "rollback",
+            "undo:true",
+            "cleanup:true"
+        ]
+    );
+}
+
+#[test]
+fn keeps_stranded_entry_when_rollback_fails() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha");
+    let second = dir.path().join("beta");
+    fs::create_dir(&first).expect("create first");
+    fs::create_dir(&second).expect("create second");
+    fs::write(second.join("data"), "B").expect("write second data");
+    let recorder = Arc::new(Recorder {
+        block_target: Some(second.clone()),
+        ..Recorder::default()
+    });
+
+    let result = exchange_with(
+        &first,
+        &second,
+        &ExchangeOptions::new().observer(recorder.clone()),
+    );
+
+    let error = result.expect_err("exchange must fail");
+    assert!(matches!(error.kind(), RenameError::RollbackFailed { .. }));
+    assert_eq!(error.phase(), Phase::Rollback);
+    assert_eq!(
+        error.operation().map(ExchangeError::phase),
+        Some(Phase::Rename)
+    );
+    assert!(std::error::Error::source(&error).is_some());
+    assert_eq!(
+        recorder.events(),
+        [
+            "temp",
+            "ToTemporary:true",
+            "FirstToTarget:false",
+            "rollback",
+            "undo:false",
+            "cleanup:false"
+        ]
+    );
+    let kept = fs::read_dir(dir.path())
+        .expect("list temp dir")
+        .map(|entry| entry.expect("read entry").path())
+        .find(|path| path.to_string_lossy().contains(".name-exchange-"))
+        .expect("temporary directory kept");
+    assert_eq!(
+        fs::read_to_string(kept.join("entry").join("data")).expect("read stranded data"),
+        "B"
+    );
+}
+
+struct Contender {
+    paths: (PathBuf, PathBuf),
+    result: Mutex<Option<Result<(), RenameError>>>,
+}
+
+impl ExchangeObserver for Contender {
+    fn temp_dir_created(&self, _path: &Path) {
+        let (first, second) = self.paths.clone();
+        let result = std::thread::spawn(move || {
+            exchange_with(
+                &first,
+                &second,
+                &ExchangeOptions::new().lock_timeout(Duration::from_millis(20)),
+            )
+            .map_err(RenameError::from)
+        })
+        .join()
+        .expect("join contender");
+        *self.result.lock().expect("lock result") = Some(result);
+    }
+}
+
+#[test]
+fn overlapping_exchange_times_out_while_lock_is_held() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("alpha.txt");
+    let second = dir.path().join("beta.txt");
+    fs::write(&first, "A").expect("write first");
+    fs::write(&second, "B").expect("write second");
+    let contender = Arc::new(Contender {
+        paths: (first.clone(), second.clone()),
+        result: Mutex::new(None),
+    });
+
+    exchange_with(
+        &first,
+        &second,
+        &ExchangeOptions::new().observer(contender.clone()),
+    )
+    .expect("exchange files");
+
+    assert_eq!(
+        contender.result.lock().expect("lock result").clone(),
+        Some(Err(RenameError::LockTimeout))
+    );
+    assert_eq!(fs::read_to_string(&first).expect("read first"), "B");
+}
+
+#[test]
+fn exchanges_inside_a_renamed_directory_wait_for_it() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [first, second] = ["a", "b"].map(|name| dir.path().join(name));
+    let inner = first.join("x");
+    fs::create_dir_all(&inner).expect("create directories");
+    fs::create_dir(&second).expect("create second");
+    let [one, two] = ["1", "2"].map(|name| inner.join(name));
+    fs::write(&one, "1").expect("write one");
+    fs::write(&two, "2").expect("write two");
+    let nested = Arc::new(Contender {
+        paths: (one.clone(), two.clone()),
+        result: Mutex::new(None),
+    });
+
+    exchange_with(
+        &first,
+        &second,
+        &ExchangeOptions::new().observer(nested.clone()),
+    )
+    .expect("exchange directories");
+
+    assert_eq!(
+        nested.result.lock().expect("lock result").clone(),
+        Some(Err(RenameError::LockTimeout))
+    );
+    assert_eq!(
+        fs::read_to_string(second.join("x").join("1")).expect("read"),
+        "1"
+    );
+
+    let other = TempDir::new().expect("create other temp dir");
+    let [three, four] = ["3", "4"].map(|name| other.path().join(name));
+    fs::write(&three, "3").expect("write three");
+    fs::write(&four, "4").expect("write four");
+    let unrelated = Arc::new(Contender {
+        paths: (three.clone(), four),
+        result: Mutex::new(None),
+    });
+    exchange_with(
+        &first,
+        &second,
+        &ExchangeOptions::new().observer(unrelated.clone()),
+    )
+    .expect("exchange directories back");
+    assert_eq!(
+        unrelated.result.lock().expect("lock result").clone(),
+        Some(Ok(()))
+    );
+    assert_eq!(fs::read_to_string(&three).expect("read"), "4");
+}
@@ -0,0 +1,85 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use exchange_name_lib::{
+    exchange_with, plan_exchange, rotate, ExchangeOptions, Phase, Precondition, RenameError,
+};
+use tempfile::TempDir;
+
+fn write(path: &Path, value: &str) {
+    fs::write(path, value).expect("write test file");
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).expect("read test file")
+}
+
+#[test]
+fn matching_preconditions_allow_the_exchange() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+
+    let options = ExchangeOptions::new()
+        .expect(&a, Precondition::of(&a).expect("capture first"))
+        .expect(
+            &b,
+            Precondition::new()
+                .size(1)
+                .sha256(Precondition::file_sha256(&b).expect("hash second")),
+        );
+    exchange_with(&a, &b, &options).expect("exchange");
+    assert_eq!([read(&a), read(&b)], ["B", "A"]);
+}
+
+#[test]
+fn mismatches_fail_without_renaming() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    let hash = Precondition::file_sha256(&b).expect("hash second");
+    write(&b, "b");
+
+    let preconditions = [
+        Precondition::new().size(2),
+        Precondition::new().sha256(hash),
+        Precondition::new().modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1)),
+    ];
+    for precondition in preconditions {
+        let options = ExchangeOptions::new()
+            .base_dir(dir.path())
+            .expect("b", precondition);
+        let error = exchange_with(&a, &b, &options).expect_err("precondition fails");
+        assert_eq!(error.kind(), &RenameError::Changed);
+        assert_eq!(error.phase(), Phase::Plan);
+        assert_eq!(error.path(), Some(b.as_path()));
+    }
+    assert_eq!([read(&a), read(&b)], ["A", "b"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn replaced_entries_fail_the_planned_exchange() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b, c] = ["a", "b", "c"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    write(&c, "C");
+
+    let options = ExchangeOptions::new().expect(&a, Precondition::of(&a).expect("capture"));
+    let plan = plan_exchange(&a, &b, &options).expect("plan");
+    fs::rename(&a, dir.path().join("old")).expect("move original away");
+    write(&a, "A");
+    let error = plan.execute().expect_err("entry was replaced");
+    assert_eq!(error.kind(), &RenameError::Changed);
+    assert_eq!([read(&a), read(&b)], ["A", "B"]);
+
+    let error = rotate(&[&a, &b, &c], &options).expect_err("rotation checks too");
+    assert_eq!(error.kind(), &RenameError::Changed);
+    assert_eq!(read(&c), "C");
+}
@@ -0,0 +1,154 @@
+#![cfg(unix)]
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use exchange_name_lib::{exchange_with, ExchangeOptions, Phase, RenameError};
+use tempfile::TempDir;
+
+fn set_mode(path: &Path, mode: u32) {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode)).expect("set permissions");
+}
+
+#[test]
+fn reports_every_unwritable_parent_before_renaming() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [first_dir, second_dir] = ["one", "two"].map(|name| dir.path().join(name));
+    let [a, b] = [first_dir.join("a"), second_dir.join("b")];
+    for (parent, entry) in [(&first_dir, &a), (&second_dir, &b)] {
+        fs::create_dir(parent).expect("create parent");
+        fs::write(entry, "entry").expect("write entry");
+        set_mode(parent, 0o555);
+    }
+    // Privileged users may write anyway, so there would be nothing to report.
+    let privileged = fs::write(first_dir.join("probe"), "").is_ok();
+
+    let result = exchange_with(&a, &b, &ExchangeOptions::new());
+    for parent in [&first_dir, &second_dir] {
+        set_mode(parent, 0o755);
+    }
+    if privileged {
+        return;
+    }
+    let error = result.expect_err("parents are read-only");
+    assert_eq!(error.kind(), &RenameError::PermissionDenied);
+    assert_eq!(error.phase(), Phase::Preflight);
+    assert_eq!(
+        error
+            .problems()
+            .map(|problem| problem.path().expect("path"))
+            .collect::<Vec<_>>(),
+        [first_dir.as_path(), second_dir.as_path()]
+    );
+    assert!(error.to_string().ends_with("and 1 more problem"));
+    assert!(a.exists() && b.exists());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn reports_mount_points() {
+    use exchange_name_lib::{diagnose_exchange, Severity};
+
+    let dir = TempDir::new().expect("create temp dir");
+    let file = dir.path().join("file");
+    fs::write(&file, "file").expect("write file");
+
+    let findings = diagnose_exchange(Path::new("/proc"), &file, &ExchangeOptions::new());
+
+    assert!(findings
+        .iter()
+        .any(|finding| finding.severity() == Severity::Error
+            && finding.error().kind() == &RenameError::MountPoint
+            && finding.path() == Some(Path::new("/proc"))));
+    assert!(!findings
+        .iter()
+        .any(|finding| finding.path() == Some(file.as_path())));
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn refuses_to_exchange_mount_points() {
+    use exchange_name_lib::exchange_rs;
+
+    let dir = TempDir::new().expect("create temp dir");
+    let file = dir.path().join("file");
+    fs::write(&file, "file").expect("write file");
+    let leftovers = || {
+        [Path::new("/"), dir.path()]
+            .iter()
+            .flat_map(|parent| fs::read_dir(parent).expect("list directory"))
+            .map(|entry| entry.expect("read entry").file_name())
+            .filter(|name| name.to_string_lossy().starts_with(".name-exchange-"))
+            .collect::<Vec<_>>()
+    };
+    let before = leftovers();
+
+    let error = exchange_with(Path::new("/proc"), &file, &ExchangeOptions::new())
+        .expect_err("/proc is a mount point");
+    let kind = exchange_rs(Path::new("/proc"), &file, false).expect_err("/proc is a mount point");
+
+    // Unprivileged users are refused the root directory first.
+    assert_eq!(&kind, error.kind());
+    assert!(error.problems().any(|problem| {
+        problem.kind() == &RenameError::MountPoint
+            && problem.phase() == Phase::Preflight
+            && problem.path() == Some(Path::new("/proc"))
+    }));
+    assert!(Path::new("/proc/self").exists());
+    assert_eq!(fs::read_to_string(&file).expect("read file"), "file");
+    assert!(!dir.path().join("proc").exists() && !Path::new("/file").exists());
+    assert_eq!(leftovers(), before);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn directory_handles_are_checked_before_renaming() {
+    use std::os::fd::AsFd;
+
+    use exchange_name_lib::exchange_at;
+
+    let dir = TempDir::new().expect("create temp dir");
+    fs::write(dir.path().join("file"), "file").expect("write file");
+    let root = fs::File::open("/").expect("open root");
+    let handle = fs::File::open(dir.path()).expect("open temp dir");
+
+    let error = exchange_at(
+        root.as_fd(),
+        Path::new("proc"),
+        handle.as_fd(),
+        Path::new("file"),
+        &ExchangeOptions::new(),
+    )
+    .expect_err("/proc is a mount point");
+    assert!(error.problems().any(|problem| {
+        problem.kind() == &RenameError::MountPoint
+            && problem.phase() == Phase::Preflight
+            && problem.path() == Some(Path::new("proc"))
+    }));
+    assert!(Path::new("/proc/self").exists());
+    assert_eq!(
+        fs::read_to_string(dir.path().join("file")).expect("read file"),
+        "file"
+    );
+
+    // Keeping each extension joins the long stem of one name to the long extension of the other.
+    let long_extension = format!("a.{}", "e".repeat(250));
+    let long_stem = format!("{}.t", "b".repeat(250));
+    fs::write(dir.path().join(&long_extension), "A").expect("write first");
+    fs::write(dir.path().join(&long_stem), "B").expect("write second");
+    let error = exchange_at(
+        handle.as_fd(),
+        Path::new(&long_extension),
+        handle.as_fd(),
+        Path::new(&long_stem),
+        &ExchangeOptions::new().preserve_ext(true),
+    )
+    .expect_err("target name is too long");
+    assert_eq!(error.kind(), &RenameError::NameTooLong);
+    assert_eq!(error.phase(), Phase::Plan);
+
+    let names = fs::read_dir(dir.path())
+        .expect("list temp dir")
+        .map(|entry| entry.expect("read entry").file_name())
+        .collect::<Vec<_>>();
+    assert_eq!(names.len(), 3);
+}
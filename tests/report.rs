@@ -0,0 +1,48 @@
+#![cfg(feature = "serde")]
+
+use std::fs;
+
+use exchange_name_lib::{plan_exchange, ExchangeOptions, OutcomeReport, PlanReport};
+use serde_json::{json, Value};
+use tempfile::TempDir;
+
+#[test]
+fn outcome_serializes_plan_steps() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("a.txt");
+    let second = dir.path().join("b.log");
+    fs::write(&first, "A").expect("write first");
+    fs::write(&second, "B").expect("write second");
+
+    let plan =
+        plan_exchange(&first, &second, &ExchangeOptions::new().preserve_ext(true)).expect("plan");
+    let report = serde_json::to_value(OutcomeReport::success(PlanReport::from(&plan), false))
+        .expect("serialize");
+
+    assert_eq!(report["status"], "ok");
+    assert_eq!(report["code"], 0);
+    assert_eq!(report["executed"], false);
+    assert_eq!(
+        report["steps"][0]["to"],
+        dir.path().join("b.txt").to_string_lossy().as_ref()
+    );
+    assert!(report.get("error").is_none());
+}
+
+#[test]
+fn failure_serializes_error_details() {
+    let dir = TempDir::new().expect("create temp dir");
+    let first = dir.path().join("a");
+    fs::write(&first, "A").expect("write file");
+
+    let error = plan_exchange(&first, &dir.path().join("missing"), &ExchangeOptions::new())
+        .expect_err("missing entry");
+    let report = serde_json::to_value(OutcomeReport::failure(None, &error)).expect("serialize");
+
+    assert_eq!(report["status"], "error");
+    assert_eq!(report["code"], 1);
+    assert_eq!(report["steps"], json!([]));
+    assert_eq!(report["error"]["phase"], "inspect");
+    assert_eq!(report["error"]["code"], 1);
+    assert!(matches!(report["error"]["os_error"], Value::Number(_)));
+}
@@ -0,0 +1,164 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use exchange_name_lib::{plan_exchange, ExchangeOptions, PreparedExchange, Recovered};
+use tempfile::TempDir;
+
+fn write(path: &Path, value: &str) {
+    fs::write(path, value).expect("write test file");
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).expect("read test file")
+}
+
+fn pair(dir: &TempDir) -> [PathBuf; 2] {
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+    [a, b]
+}
+
+fn prepare(a: &Path, b: &Path) -> PreparedExchange {
+    plan_exchange(a, b, &ExchangeOptions::new())
+        .expect("plan")
+        .prepare()
+        .expect("prepare")
+}
+
+/// Leaves a journal directory behind as if the process had died right after preparing.
+fn crash_after_prepare(dir: &TempDir, a: &Path, b: &Path) -> PathBuf {
+    let prepared = prepare(a, b);
+    let journal = prepared.journal_dir().expect("staged").to_path_buf();
+    let hidden = dir.path().join(".name-exchange-crashed");
+    // Moving the journal away makes the abort on drop fail, like a process that never ran it.
+    fs::rename(&journal, &hidden).expect("hide journal");
+    drop(prepared);
+    hidden
+}
+
+#[test]
+fn prepare_stages_until_commit() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = pair(&dir);
+
+    let prepared = prepare(&a, &b);
+    let journal = prepared.journal_dir().expect("staged").to_path_buf();
+    assert_eq!(read(&a), "A");
+    assert!(!b.exists());
+    assert_eq!(read(&journal.join("entry")), "B");
+    assert_eq!(
+        PreparedExchange::journals_in(dir.path()).expect("list journals"),
+        std::slice::from_ref(&journal)
+    );
+
+    prepared.commit().expect("commit");
+    assert_eq!(read(&a), "B");
+    assert_eq!(read(&b), "A");
+    assert!(!journal.exists());
+}
+
+#[test]
+fn abort_and_drop_restore_names() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = pair(&dir);
+
+    prepare(&a, &b).abort().expect("abort");
+    assert_eq!([read(&a), read(&b)], ["A", "B"]);
+
+    drop(prepare(&a, &b));
+    assert_eq!([read(&a), read(&b)], ["A", "B"]);
+    assert!(PreparedExchange::journals_in(dir.path())
+        .expect("list journals")
+        .is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn only_committed_exchanges_stay_in_the_history() {
+    use exchange_name_lib::History;
+
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = pair(&dir);
+    let history = History::new(dir.path().join("history"));
+    let options = ExchangeOptions::new().history(history.clone());
+    let prepare = || {
+        plan_exchange(&a, &b, &options)
+            .expect("plan")
+            .prepare()
+            .expect("prepare")
+    };
+
+    prepare().abort().expect("abort");
+    drop(prepare());
+    assert!(history.entries().expect("read history").is_empty());
+
+    prepare().commit().expect("commit");
+    let entries = history.entries().expect("read history");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id(), 3);
+}
+
+#[test]
+fn recovers_staged_exchange() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = pair(&dir);
+    let journal = crash_after_prepare(&dir, &a, &b);
+    assert!(!b.exists());
+    assert_eq!(
+        PreparedExchange::journals_in(dir.path()).expect("list journals"),
+        std::slice::from_ref(&journal)
+    );
+
+    let Recovered::Prepared(prepared) =
+        PreparedExchange::recover(&journal, &ExchangeOptions::new()).expect("recover")
+    else {
+        panic!("exchange should still be staged");
+    };
+    prepared.commit().expect("commit");
+    assert_eq!([read(&a), read(&b)], ["B", "A"]);
+    assert!(!journal.exists());
+}
+
+#[test]
+fn recovery_resumes_interrupted_commit() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = pair(&dir);
+    let journal = crash_after_prepare(&dir, &a, &b);
+    // The process died after journaling the commit and moving the first entry.
+    OpenOptions::new()
+        .append(true)
+        .open(journal.join("journal"))
+        .and_then(|mut file| file.write_all(b"commit\n"))
+        .expect("journal commit");
+    fs::rename(&a, &b).expect("move first entry");
+
+    let Recovered::Prepared(prepared) =
+        PreparedExchange::recover(&journal, &ExchangeOptions::new()).expect("recover")
+    else {
+        panic!("exchange should still be staged");
+    };
+    prepared.abort().expect("abort");
+    assert_eq!([read(&a), read(&b)], ["A", "B"]);
+    assert!(!journal.exists());
+}
+
+#[test]
+fn recovery_removes_finished_journals() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = pair(&dir);
+    let journal = crash_after_prepare(&dir, &a, &b);
+    fs::rename(journal.join("entry"), &b).expect("put entry back");
+
+    let recovered = PreparedExchange::recover(&journal, &ExchangeOptions::new()).expect("recover");
+    assert!(matches!(recovered, Recovered::Aborted));
+    assert!(!journal.exists());
+    assert_eq!([read(&a), read(&b)], ["A", "B"]);
+
+    let error =
+        PreparedExchange::recover(&journal, &ExchangeOptions::new()).expect_err("journal is gone");
+    assert_eq!(error.path(), Some(journal.join("journal").as_path()));
+}
@@ -0,0 +1,94 @@
+#![cfg(unix)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use exchange_name_lib::{
+    exchange_with, ExchangeError, ExchangeObserver, ExchangeOptions, Phase, RenameError,
+    RenameStage,
+};
+use tempfile::TempDir;
+
+fn write(path: &Path, value: &str) {
+    fs::write(path, value).expect("write test file");
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path).expect("read test file")
+}
+
+/// Replaces the entry at the last target with an impostor as soon as it arrives.
+struct Impostor {
+    aside: PathBuf,
+    rollback_started: AtomicBool,
+}
+
+impl ExchangeObserver for Impostor {
+    fn renamed(
+        &self,
+        stage: RenameStage,
+        _from: &Path,
+        to: &Path,
+        result: Result<(), &ExchangeError>,
+    ) {
+        if stage == RenameStage::TemporaryToTarget && result.is_ok() && !self.aside.exists() {
+            fs::rename(to, &self.aside).expect("move entry aside");
+            write(to, "X");
+        }
+    }
+
+    fn rollback_started(&self, _cause: &ExchangeError) {
+        self.rollback_started.store(true, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn verified_exchange_succeeds() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    write(&a, "A");
+    write(&b, "B");
+
+    exchange_with(&a, &b, &ExchangeOptions::new().verify(true)).expect("exchange");
+    assert_eq!([read(&a), read(&b)], ["B", "A"]);
+}
+
+#[test]
+fn mismatch_is_reported_without_moving_other_entries() {
+    let dir = TempDir::new().expect("create temp dir");
+    let [a, b] = ["a", "b"].map(|name| dir.path().join(name));
+    let observer = Arc::new(Impostor {
+        aside: dir.path().join("aside"),
+        rollback_started: AtomicBool::new(false),
+    });
+    write(&a, "A");
+    write(&b, "B");
+
+    let options = ExchangeOptions::new().observer(observer.clone());
+    exchange_with(&a, &b, &options).expect("unverified exchange");
+    assert_eq!(read(&a), "X");
+    assert!(!observer.rollback_started.load(Ordering::SeqCst));
+
+    fs::remove_file(&observer.aside).expect("reset");
+    write(&a, "A");
+    write(&b, "B");
+    let error = exchange_with(&a, &b, &options.verify(true)).expect_err("impostor detected");
+    let verification = error.operation().expect("verification failure");
+    assert!(
+        matches!(verification.kind(), RenameError::VerificationFailed(detail) if detail.contains("does not hold"))
+    );
+    assert_eq!(verification.phase(), Phase::Verify);
+    assert_eq!(verification.path(), Some(a.as_path()));
+    assert!(observer.rollback_started.load(Ordering::SeqCst));
+    // The impostor keeps its name, so nothing is exchanged back.
+    assert!(matches!(error.kind(), RenameError::RollbackFailed { .. }));
+    assert_eq!(error.path(), Some(a.as_path()));
+    assert_eq!([read(&a), read(&b)], ["X", "A"]);
+    assert_eq!(read(&observer.aside), "B");
+}